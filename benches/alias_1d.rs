@@ -14,6 +14,19 @@ fn alias_1d_build(c: &mut Criterion) {
     }));
 }
 
+fn alias_1d_build_large(c: &mut Criterion) {
+    let distr = (0..1_000_000).map(|i| ((i * 7919) % 101 + 1) as f32).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("alias_1d_build_large");
+    group.bench_function("sequential", |b| b.iter(|| {
+        <Alias1D<f32> as Discrete1D<f32>>::build(black_box(&distr))
+    }));
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel", |b| b.iter(|| {
+        Alias1D::par_build(black_box(&distr))
+    }));
+    group.finish();
+}
+
 fn alias_1d_sample_coherent(c: &mut Criterion) {
     let mut distr = [0.0; 100];
     for (i, weight) in distr.iter_mut().enumerate() {
@@ -44,6 +57,24 @@ fn alias_1d_sample_incoherent(c: &mut Criterion) {
     }));
 }
 
-criterion_group!(benches, alias_1d_build, alias_1d_sample_coherent, alias_1d_sample_incoherent);
+fn alias_1d_sample_batch(c: &mut Criterion) {
+    let distr = (0..10_000).map(|i| (i % 100 + 1) as f32).collect::<Vec<_>>();
+    let dist = <Alias1D<f32> as Discrete1D<f32>>::build(&distr);
+    let mut rng = StdRng::seed_from_u64(0);
+    let us = (0..4096).map(|_| rng.r#gen::<f32>()).collect::<Vec<_>>();
+    let mut out = vec![0; us.len()];
+    let mut group = c.benchmark_group("alias_1d_sample_batch");
+    group.bench_function("scalar", |b| b.iter(|| {
+        for (u, out) in us.iter().zip(out.iter_mut()) {
+            *out = dist.sample(black_box(*u));
+        }
+    }));
+    group.bench_function("batch", |b| b.iter(|| {
+        dist.sample_batch(black_box(&us), &mut out);
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, alias_1d_build, alias_1d_build_large, alias_1d_sample_coherent, alias_1d_sample_incoherent, alias_1d_sample_batch);
 criterion_main!(benches);
 
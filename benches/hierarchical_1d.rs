@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
+use discrete_sampling::distribution::{Discrete1D, Continuous1D};
+use discrete_sampling::Hierarchical1D;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn hierarchical_1d_sample_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hierarchical_1d_sample_batch");
+    for size in [1_000, 100_000] {
+        let weights = (0..size).map(|i| (i % 100 + 1) as f32).collect::<Vec<_>>();
+        let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut rng = StdRng::seed_from_u64(0);
+        let us = (0..4096).map(|_| rng.r#gen::<f32>()).collect::<Vec<_>>();
+        let mut out = vec![0; us.len()];
+
+        group.bench_with_input(BenchmarkId::new("scalar", size), &size, |b, _| b.iter(|| {
+            for (u, out) in us.iter().zip(out.iter_mut()) {
+                *out = dist.sample(black_box(*u));
+            }
+        }));
+        group.bench_with_input(BenchmarkId::new("batch", size), &size, |b, _| b.iter(|| {
+            dist.sample_batch(black_box(&us), &mut out);
+        }));
+    }
+    group.finish();
+}
+
+fn hierarchical_1d_sample_continuous_batch(c: &mut Criterion) {
+    let weights = (0..1_000).map(|i| (i % 100 + 1) as f32).collect::<Vec<_>>();
+    let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights);
+    let mut rng = StdRng::seed_from_u64(0);
+    let us = (0..4096).map(|_| rng.r#gen::<f32>()).collect::<Vec<_>>();
+    let mut out = vec![0.0; us.len()];
+
+    let mut group = c.benchmark_group("hierarchical_1d_sample_continuous_batch");
+    group.bench_function("scalar", |b| b.iter(|| {
+        for (u, out) in us.iter().zip(out.iter_mut()) {
+            *out = dist.sample_continuous(black_box(*u));
+        }
+    }));
+    group.bench_function("batch", |b| b.iter(|| {
+        dist.sample_continuous_batch(black_box(&us), &mut out);
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, hierarchical_1d_sample_batch, hierarchical_1d_sample_continuous_batch);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use discrete_sampling::distribution::Discrete1D;
 use discrete_sampling::Inversion1D;
+use discrete_sampling::EytzingerInversion1D;
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
@@ -14,6 +15,49 @@ fn inversion_1d_sample(c: &mut Criterion) {
     c.bench_function("inversion_1d_sample", |b| b.iter(|| dist.sample(rng.r#gen::<f32>())));
 }
 
-criterion_group!(benches, inversion_1d_build, inversion_1d_sample);
-criterion_main!(benches);
+fn inversion_1d_sample_guided(c: &mut Criterion) {
+    let mut dist = <Inversion1D<f32> as Discrete1D<f32>>::build(&[1.0; 1_000]);
+    dist.build_guide::<f32>(1_000);
+    let mut rng = StdRng::seed_from_u64(0);
+    c.bench_function("inversion_1d_sample_guided", |b| b.iter(|| dist.sample(rng.r#gen::<f32>())));
+}
 
+fn inversion_1d_sample_layouts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("inversion_1d_sample_layouts");
+    for size in [1_000, 100_000, 10_000_000] {
+        let weights = (0..size).map(|i| (i % 100 + 1) as f32).collect::<Vec<_>>();
+
+        let linear = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut rng = StdRng::seed_from_u64(0);
+        group.bench_with_input(BenchmarkId::new("linear", size), &size, |b, _| b.iter(|| linear.sample(rng.r#gen::<f32>())));
+
+        let eytzinger = <EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut rng = StdRng::seed_from_u64(0);
+        group.bench_with_input(BenchmarkId::new("eytzinger", size), &size, |b, _| b.iter(|| eytzinger.sample(rng.r#gen::<f32>())));
+    }
+    group.finish();
+}
+
+fn inversion_1d_sample_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("inversion_1d_sample_batch");
+    for size in [1_000, 100_000] {
+        let weights = (0..size).map(|i| (i % 100 + 1) as f32).collect::<Vec<_>>();
+        let dist = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut rng = StdRng::seed_from_u64(0);
+        let us = (0..4096).map(|_| rng.r#gen::<f32>()).collect::<Vec<_>>();
+        let mut out = vec![0; us.len()];
+
+        group.bench_with_input(BenchmarkId::new("scalar", size), &size, |b, _| b.iter(|| {
+            for (u, out) in us.iter().zip(out.iter_mut()) {
+                *out = dist.sample(black_box(*u));
+            }
+        }));
+        group.bench_with_input(BenchmarkId::new("batch", size), &size, |b, _| b.iter(|| {
+            dist.sample_batch(black_box(&us), &mut out);
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, inversion_1d_build, inversion_1d_sample, inversion_1d_sample_guided, inversion_1d_sample_layouts, inversion_1d_sample_batch);
+criterion_main!(benches);
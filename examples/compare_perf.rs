@@ -10,6 +10,9 @@ use discrete_sampling::Data2D;
 use discrete_sampling::Alias2D;
 use discrete_sampling::Inversion2D;
 use discrete_sampling::Hierarchical2D;
+use discrete_sampling::PackedInversion2D;
+use discrete_sampling::PackedAlias2D;
+use discrete_sampling::PackedAlias2DWithPdf;
 use discrete_sampling::distribution::Discrete2D;
 
 fn luminance([r, g, b]: [f32; 3]) -> f32 {
@@ -71,7 +74,10 @@ fn main() {
     }
 
     sample_perf::<Inversion2D<f32>>("Inversion", &density_image);
+    sample_perf::<PackedInversion2D<f32>>("Packed inversion", &density_image);
     sample_perf::<Alias2D<f32>>("Alias", &density_image);
+    sample_perf::<PackedAlias2D<f32>>("Packed alias", &density_image);
+    sample_perf::<PackedAlias2DWithPdf<f32>>("Packed alias with pdf", &density_image);
     sample_perf::<Hierarchical2D<f32>>("Hierarchical", &density_image);
 }
 
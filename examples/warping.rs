@@ -4,6 +4,7 @@ use discrete_sampling::Data2D;
 use discrete_sampling::ContinuousAlias2D;
 use discrete_sampling::Inversion2D;
 use discrete_sampling::Hierarchical2D;
+use discrete_sampling::Bilinear2D;
 use discrete_sampling::distribution::Continuous2D;
 
 fn luminance([r, g, b]: [f32; 3]) -> f32 {
@@ -39,5 +40,6 @@ fn main() {
     visualize_warping::<Inversion2D<f32>>("inversion_warping.exr", &density_image);
     visualize_warping::<ContinuousAlias2D<f32>>("alias_warping.exr", &density_image);
     visualize_warping::<Hierarchical2D<f32>>("hierarchical_warping.exr", &density_image);
+    visualize_warping::<Bilinear2D<f32>>("bilinear_warping.exr", &density_image);
 }
 
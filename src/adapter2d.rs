@@ -4,12 +4,32 @@ use crate::distribution::{
     Discrete1DPdf,
     Discrete2D,
     Discrete2DPdf,
+    Update2D,
     Continuous1D,
     Continuous2D,
+    ContinuousPdf1D,
+    ContinuousPdf2D,
+    RandomBits,
+    BuildError,
 };
+#[cfg(feature = "rkyv")]
+use crate::distribution::{
+    ArchivedDiscrete1D,
+    ArchivedDiscrete1DPdf,
+    ArchivedDiscrete2D,
+    ArchivedDiscrete2DPdf,
+    ArchivedValidate,
+};
+#[cfg(feature = "rkyv")]
+use crate::container::{
+    Algorithm,
+    Storable,
+};
+use crate::flat::{self, ExportFlat, FlatTable1D};
 use num_traits::{
     real::Real,
     AsPrimitive,
+    Zero,
 };
 
 #[cfg(not(feature = "std"))]
@@ -18,12 +38,60 @@ use alloc::{
     vec::Vec,
 };
 
+// samples per marginal batch in sample_batch
+const BATCH_CHUNK: usize = 64;
+
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Adapter2DFields<D>", bound(deserialize = "D: serde::Deserialize<'de> + crate::utils::TableSize")))]
 pub struct Adapter2D<D> {
     pub marginal: D,
     pub conditional: Box<[D]>,
 }
 
+// as serialized, checked before becoming an Adapter2D
+// the tables themselves are checked as they're deserialized
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Adapter2DFields<D> {
+    marginal: D,
+    conditional: Box<[D]>,
+}
+
+#[cfg(feature = "serde")]
+impl<D: crate::utils::TableSize> TryFrom<Adapter2DFields<D>> for Adapter2D<D> {
+    type Error = &'static str;
+
+    fn try_from(Adapter2DFields { marginal, conditional }: Adapter2DFields<D>) -> Result<Self, Self::Error> {
+        check_rows(&marginal, &conditional)?;
+        Ok(Self { marginal, conditional })
+    }
+}
+
+// shared by serde and ArchivedAdapter2D, the tables themselves are checked on their own
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_rows<T: crate::utils::TableSize>(marginal: &T, conditional: &[T]) -> Result<(), &'static str> {
+    if marginal.table_size() != conditional.len() {
+        return Err("Adapter2D marginal doesn't match its rows");
+    }
+    let width = conditional.first().map_or(0, |table| table.table_size());
+    if conditional.iter().any(|table| table.table_size() != width) {
+        return Err("Adapter2D rows differ in width");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::Archive> ArchivedValidate for ArchivedAdapter2D<D>
+    where rkyv::Archived<D>: ArchivedValidate + crate::utils::TableSize,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        self.marginal.validate()?;
+        self.conditional.iter().try_for_each(ArchivedValidate::validate)?;
+        check_rows(&self.marginal, &self.conditional)
+    }
+}
+
 impl<D: Discrete1D<R>, R> Discrete2D<R> for Adapter2D<D> {
     type Weight = D::Weight;
 
@@ -45,6 +113,32 @@ impl<D: Discrete1D<R>, R> Discrete2D<R> for Adapter2D<D> {
         }
     }
 
+    fn try_build(weights: &Data2D<D::Weight>) -> Result<Self, BuildError>
+        where D::Weight: PartialOrd + Copy,
+    {
+        crate::utils::validate_weights(weights.iter().flatten().copied())?;
+
+        let mut conditional = Vec::with_capacity(weights.height());
+        let mut marginal_weights = Vec::with_capacity(weights.height());
+
+        for row in weights.iter() {
+            // rows of all zero are fine, they just never get sampled
+            let table = match D::try_build(row) {
+                Err(BuildError::ZeroIntegral) => D::build(row),
+                table => table?,
+            };
+            marginal_weights.push(table.integral());
+            conditional.push(table);
+        }
+
+        let marginal = D::try_build(&marginal_weights)?;
+
+        Ok(Self {
+            marginal,
+            conditional: conditional.into_boxed_slice(),
+        })
+    }
+
     fn sample(&self, [u, v]: [R; 2]) -> [usize; 2] {
         let y = self.marginal.sample(v);
         let x = self.conditional[y].sample(u);
@@ -52,6 +146,38 @@ impl<D: Discrete1D<R>, R> Discrete2D<R> for Adapter2D<D> {
         [x, y]
     }
 
+    fn sample_bits<B: RandomBits>(&self, [u, v]: [B; 2]) -> [usize; 2] {
+        let y = self.marginal.sample_bits(v);
+        let x = self.conditional[y].sample_bits(u);
+
+        [x, y]
+    }
+
+    fn sample_remap(&self, [u, v]: [R; 2]) -> ([usize; 2], [R; 2]) {
+        let (y, v) = self.marginal.sample_remap(v);
+        let (x, u) = self.conditional[y].sample_remap(u);
+
+        ([x, y], [u, v])
+    }
+
+    // rows differ from one sample to the next, so only the marginal is sampled as a batch
+    fn sample_batch(&self, uvs: &[[R; 2]], out: &mut [[usize; 2]])
+        where R: Copy,
+    {
+        assert_eq!(uvs.len(), out.len(), "batch output length doesn't match input");
+        for (uvs, out) in uvs.chunks(BATCH_CHUNK).zip(out.chunks_mut(BATCH_CHUNK)) {
+            let mut vs = [uvs[0][1]; BATCH_CHUNK];
+            let mut ys = [0; BATCH_CHUNK];
+            for (v, uv) in vs.iter_mut().zip(uvs) {
+                *v = uv[1];
+            }
+            self.marginal.sample_batch(&vs[..uvs.len()], &mut ys[..uvs.len()]);
+            for ((out, [u, _]), y) in out.iter_mut().zip(uvs).zip(ys) {
+                *out = [self.conditional[y].sample(*u), y];
+            }
+        }
+    }
+
     fn integral(&self) -> D::Weight {
         self.marginal.integral()
     }
@@ -61,16 +187,131 @@ impl<D: Discrete1D<R>, R> Discrete2D<R> for Adapter2D<D> {
     }
 
     fn width(&self) -> usize {
-        self.conditional[0].size()
+        self.conditional.first().map_or(0, |table| table.size())
     }
 }
 
-impl<D: Discrete1DPdf<R>, R> Discrete2DPdf<R> for Adapter2D<D> {
+impl<D: Discrete1DPdf<R>, R: core::ops::Mul<Output = R>> Discrete2DPdf<R> for Adapter2D<D> {
+    // each conditional is built from its row of weights, so its unnormalized pdf already is the weight,
+    // scaling that by the marginal, which is the row's sum, would be off by that sum, and not divide out by integral
     fn pdf(&self, [u, v]: [usize; 2]) -> D::Weight {
-        let pdf_y = self.marginal.pdf(v);
-        let pdf_x = self.conditional[v].pdf(u);
+        self.conditional[v].pdf(u)
+    }
 
-        pdf_y * pdf_x
+    fn sample_with_pdf(&self, [u, v]: [R; 2]) -> ([usize; 2], R) {
+        let (y, pdf_y) = self.marginal.sample_with_pdf(v);
+        let (x, pdf_x) = self.conditional[y].sample_with_pdf(u);
+
+        ([x, y], pdf_y * pdf_x)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::Archive, R> ArchivedDiscrete2D<R> for ArchivedAdapter2D<D>
+    where rkyv::Archived<D>: ArchivedDiscrete1D<R>,
+{
+    type Weight = <rkyv::Archived<D> as ArchivedDiscrete1D<R>>::Weight;
+
+    fn sample(&self, [u, v]: [R; 2]) -> [usize; 2] {
+        let y = self.marginal.sample(v);
+        let x = self.conditional[y].sample(u);
+
+        [x, y]
+    }
+
+    fn sample_bits<B: RandomBits>(&self, [u, v]: [B; 2]) -> [usize; 2] {
+        let y = self.marginal.sample_bits(v);
+        let x = self.conditional[y].sample_bits(u);
+
+        [x, y]
+    }
+
+    fn sample_remap(&self, [u, v]: [R; 2]) -> ([usize; 2], [R; 2]) {
+        let (y, v) = self.marginal.sample_remap(v);
+        let (x, u) = self.conditional[y].sample_remap(u);
+
+        ([x, y], [u, v])
+    }
+
+    fn integral(&self) -> Self::Weight {
+        self.marginal.integral()
+    }
+
+    fn height(&self) -> usize {
+        self.conditional.len()
+    }
+
+    fn width(&self) -> usize {
+        self.conditional.first().map_or(0, |table| table.size())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::Archive, R: core::ops::Mul<Output = R>> ArchivedDiscrete2DPdf<R> for ArchivedAdapter2D<D>
+    where rkyv::Archived<D>: ArchivedDiscrete1DPdf<R>,
+{
+    // same as Adapter2D, just the weight
+    fn pdf(&self, [u, v]: [usize; 2]) -> Self::Weight {
+        self.conditional[v].pdf(u)
+    }
+
+    fn sample_with_pdf(&self, [u, v]: [R; 2]) -> ([usize; 2], R) {
+        let (y, pdf_y) = self.marginal.sample_with_pdf(v);
+        let (x, pdf_x) = self.conditional[y].sample_with_pdf(u);
+
+        ([x, y], pdf_y * pdf_x)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: Storable> Storable for Adapter2D<D> {
+    type Weight = D::Weight;
+    const ALGORITHM: Algorithm = D::ALGORITHM;
+    const ADAPTER2D: bool = true;
+    const LAYOUT_VERSION: u32 = D::LAYOUT_VERSION;
+
+    fn dimensions(&self) -> [u64; 2] {
+        let width = self.conditional.first().map_or(0, |table| table.dimensions()[0]);
+        [width, self.conditional.len() as u64]
+    }
+}
+
+impl<D: FlatTable1D> ExportFlat for Adapter2D<D> {
+    fn export_flat(&self) -> Vec<u32> {
+        let mut buffer = flat::new_buffer();
+        let marginal = buffer.len();
+        self.marginal.write_flat(&mut buffer);
+
+        // rows all have the same number of entries, so the same number of words
+        let conditional = buffer.len();
+        for table in self.conditional.iter() {
+            table.write_flat(&mut buffer);
+        }
+        let row_stride = (buffer.len() - conditional).checked_div(self.conditional.len()).unwrap_or(0);
+
+        flat::finish_buffer(&mut buffer, flat::Header {
+            algorithm: D::ALGORITHM as u32,
+            width: flat::word(self.conditional.first().map_or(0, |table| table.flat_size())),
+            height: flat::word(self.conditional.len()),
+            levels: 0,
+            marginal: flat::word(marginal),
+            conditional: flat::word(conditional),
+            row_stride: flat::word(row_stride),
+            len: 0,
+        });
+        buffer
+    }
+}
+
+impl<D: Discrete1D<R>, R> Update2D<R> for Adapter2D<D> {
+    // rebuilds touched rows, then marginal
+    fn update_rect(&mut self, weights: &Data2D<D::Weight>, min: [usize; 2], max: [usize; 2]) {
+        for y in min[1]..max[1] {
+            self.conditional[y] = D::build(weights.iter().nth(y).unwrap());
+        }
+
+        let marginal_weights = self.conditional.iter().map(|table| table.integral()).collect::<Vec<_>>();
+        self.marginal = D::build(&marginal_weights);
     }
 }
 
@@ -79,21 +320,97 @@ impl<D: Continuous1D<R>, R: Real + AsPrimitive<usize> + 'static> Continuous2D<R>
 {
     fn sample_continuous(&self, [u, v]: [R; 2]) -> [R; 2] {
         let y = self.marginal.sample_continuous(v);
-        let offset_y = (y * <usize as AsPrimitive<R>>::as_(self.height())).as_();
+        let offset_y = self.sampled_row(y, v);
         let x = self.conditional[offset_y].sample_continuous(u);
 
         [x, y]
     }
 
+    fn sample_continuous_with_pdf(&self, [u, v]: [R; 2]) -> ([R; 2], R) {
+        let (y, pdf_y) = self.marginal.sample_continuous_with_pdf(v);
+        let offset_y = self.sampled_row(y, v);
+        let (x, pdf_x) = self.conditional[offset_y].sample_continuous_with_pdf(u);
+
+        ([x, y], pdf_y * pdf_x)
+    }
+
+    // same as sample_batch, only the marginal is sampled as a batch
+    fn sample_continuous_batch(&self, uvs: &[[R; 2]], out: &mut [[R; 2]]) {
+        assert_eq!(uvs.len(), out.len(), "batch output length doesn't match input");
+        for (uvs, out) in uvs.chunks(BATCH_CHUNK).zip(out.chunks_mut(BATCH_CHUNK)) {
+            let mut vs = [R::zero(); BATCH_CHUNK];
+            let mut ys = [R::zero(); BATCH_CHUNK];
+            for (v, uv) in vs.iter_mut().zip(uvs) {
+                *v = uv[1];
+            }
+            self.marginal.sample_continuous_batch(&vs[..uvs.len()], &mut ys[..uvs.len()]);
+            for ((out, [u, v]), y) in out.iter_mut().zip(uvs).zip(ys) {
+                *out = [self.conditional[self.sampled_row(y, *v)].sample_continuous(*u), y];
+            }
+        }
+    }
+
     fn invert_continuous(&self, [u, v]: [R; 2]) -> [R; 2] {
         let y = self.marginal.invert_continuous(v);
-        let offset_y = (y * <usize as AsPrimitive<R>>::as_(self.height())).as_();
-        let x = self.conditional[offset_y].invert_continuous(u);
+        let offset_y = <R as AsPrimitive<usize>>::as_(y * self.height().as_()).min(self.height() - 1);
+        let row = &self.conditional[offset_y];
+        // all zero rows never get sampled, so have nothing to invert
+        let x = if row.integral().is_zero() { u } else { row.invert_continuous(u) };
 
         [x, y]
     }
 }
 
+impl<D> Adapter2D<D> {
+    // same as build, with the rows built across threads, results are identical
+    #[cfg(feature = "rayon")]
+    pub fn par_build<R>(weights: &Data2D<<D as Discrete1D<R>>::Weight>) -> Self
+        where D: Discrete1D<R> + Send,
+              <D as Discrete1D<R>>::Weight: Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let conditional = weights.par_iter().map(|row| D::build(row)).collect::<Box<[D]>>();
+        let marginal_weights = conditional.iter().map(|table| table.integral()).collect::<Vec<_>>();
+        let marginal = D::build(&marginal_weights);
+
+        Self {
+            marginal,
+            conditional,
+        }
+    }
+
+    // row that continuous y sampled from v lands in
+    // y at the very end of a row can round into the next one, which may be all zero,
+    // in which case go by the discrete sample instead
+    fn sampled_row<R: Real + AsPrimitive<usize> + 'static>(&self, y: R, v: R) -> usize
+        where D: Continuous1D<R>,
+              usize: AsPrimitive<R>,
+    {
+        let offset_y = <R as AsPrimitive<usize>>::as_(y * self.height().as_()).min(self.height() - 1);
+        if self.conditional[offset_y].integral().is_zero() {
+            self.marginal.sample(v)
+        } else {
+            offset_y
+        }
+    }
+}
+
+impl<D: ContinuousPdf1D<R>, R: Real + AsPrimitive<usize> + 'static> ContinuousPdf2D<R> for Adapter2D<D>
+    where usize: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, [x, y]: [R; 2]) -> R {
+        let pdf_y = self.marginal.pdf_continuous(y);
+        if pdf_y.is_zero() {
+            return pdf_y; // conditional is all zero, and so undefined
+        }
+        let offset_y = <R as AsPrimitive<usize>>::as_(y * self.height().as_()).min(self.height() - 1);
+        let pdf_x = self.conditional[offset_y].pdf_continuous(x);
+
+        pdf_y * pdf_x
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::distribution::distribution_2d_tests;
@@ -101,5 +418,146 @@ mod tests {
     pub type Inversion2D<R> = crate::Adapter2D<crate::Inversion1D<R>>;
 
     distribution_2d_tests!(crate::adapter2d::tests::Inversion2D);
-}
 
+    #[test]
+    fn update_2d() {
+        crate::distribution::test_update_2d::<Inversion2D<f32>>(17, 10);
+        crate::distribution::test_update_2d::<crate::Alias2D<f32>>(17, 10);
+    }
+
+    #[test]
+    fn zero_integral() {
+        crate::distribution::test_zero_integral_2d::<crate::Alias2DWithPdf<f32>>(5, 4);
+        crate::distribution::test_zero_integral_2d::<crate::ContinuousAlias2DWithPdf<f32>>(5, 4);
+        crate::distribution::test_zero_integral_continuous_2d::<Inversion2D<f32>>(5, 4);
+        crate::distribution::test_zero_integral_continuous_2d::<crate::ContinuousAlias2D<f32>>(5, 4);
+    }
+
+    #[test]
+    fn continuous_batch() {
+        use crate::distribution::test_sample_continuous_batch_2d;
+        test_sample_continuous_batch_2d::<f32, Inversion2D<f32>>();
+        test_sample_continuous_batch_2d::<f64, Inversion2D<f64>>();
+        test_sample_continuous_batch_2d::<f32, crate::ContinuousAlias2D<f32>>();
+    }
+
+    #[test]
+    fn edge_cases() {
+        use crate::distribution::{test_edge_cases_2d, test_edge_cases_continuous_2d};
+        test_edge_cases_2d::<f32, crate::Alias2D<f32>>();
+        test_edge_cases_2d::<f64, crate::Alias2D<f64>>();
+        test_edge_cases_2d::<f32, crate::ContinuousAlias2D<f32>>();
+        test_edge_cases_2d::<f64, crate::ContinuousAlias2D<f64>>();
+        test_edge_cases_continuous_2d::<f32, Inversion2D<f32>>();
+        test_edge_cases_continuous_2d::<f64, Inversion2D<f64>>();
+        test_edge_cases_continuous_2d::<f32, crate::ContinuousAlias2D<f32>>();
+        test_edge_cases_continuous_2d::<f64, crate::ContinuousAlias2D<f64>>();
+    }
+
+    #[test]
+    fn chisq_pdf() {
+        use crate::distribution::Discrete2D;
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1);
+        weights[[4, 0]] = 30;
+        weights[[2, 1]] = 0;
+        crate::distribution::chisq_distribution_2d_pdf(&<Inversion2D<usize> as Discrete2D<f64>>::build(&weights), &weights, 100_000);
+    }
+
+    // pdf used to be marginal times conditional, which is the weight times its row's sum
+    #[test]
+    fn pdf_is_weight() {
+        use crate::distribution::{Discrete2D, Discrete2DPdf};
+        let mut weights = crate::data2d::Data2D::new_same(3, 2, 1.0f64);
+        weights[[0, 1]] = 5.0;
+        let dist = <Inversion2D<f64> as Discrete2D<f64>>::build(&weights);
+        let integral = <Inversion2D<f64> as Discrete2D<f64>>::integral(&dist);
+        assert_eq!(integral, 10.0);
+        for j in 0..2 {
+            for i in 0..3 {
+                assert_eq!(<Inversion2D<f64> as Discrete2DPdf<f64>>::pdf(&dist, [i, j]), weights[[i, j]]);
+            }
+        }
+        // normalized, sums to one, where the row sums 3 and 7 would have made it 58 / 10
+        let sum = (0..2).flat_map(|j| (0..3).map(move |i| [i, j])).map(|idx| <Inversion2D<f64> as Discrete2DPdf<f64>>::pdf(&dist, idx) / integral).sum::<f64>();
+        assert_eq!(sum, 1.0);
+    }
+
+    #[test]
+    fn continuous_with_pdf() {
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 4.0;
+        crate::distribution::test_continuous_with_pdf_2d::<Inversion2D<f64>>(&weights, 300);
+        crate::distribution::test_continuous_with_pdf_2d::<crate::ContinuousAlias2D<f64>>(&weights, 300);
+        crate::distribution::test_pdf_continuous_2d::<Inversion2D<f64>>(&weights, 300);
+        crate::distribution::test_pdf_continuous_2d::<crate::ContinuousAlias2D<f64>>(&weights, 300);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use crate::distribution::Discrete2D;
+        use crate::adapter2d::ArchivedAdapter2D;
+        use rkyv::rancor::Error;
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 0.0;
+        let dist = <Inversion2D<f32> as Discrete2D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_2d(&dist, crate::distribution::access_checked::<ArchivedAdapter2D<crate::Inversion1D<f32>>>(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_invalid() {
+        use crate::distribution::{Discrete1D, Discrete2D, test_archived_invalid};
+        use crate::Adapter2D;
+
+        let weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        let dist = <Inversion2D<f32> as Discrete2D<f32>>::build(&weights);
+        let row = |weights: &[f32]| <crate::Inversion1D<f32> as Discrete1D<f32>>::build(weights);
+
+        // rows of different widths, and an invalid row, which validate checks too
+        let mut invalid = Adapter2D { marginal: row(&[5.0; 3]), conditional: (0..3).map(|_| row(&[1.0; 5])).collect() };
+        invalid.conditional[1] = row(&[1.0; 4]);
+        test_archived_invalid(&dist, &invalid);
+        invalid.conditional[1] = crate::Inversion1D { cdf: [0.0, 1.0, 0.5, 2.0, 3.0, 4.0].into(), guide: Box::default() };
+        test_archived_invalid(&dist, &invalid);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_build() {
+        use crate::distribution::{Discrete2D, Discrete2DPdf};
+
+        let mut weights = crate::data2d::Data2D::new_same(40_000, 3, 0.0f32);
+        for y in 0..3 {
+            for x in 0..40_000 {
+                weights[[x, y]] = ((x * 7919 + y * 104729) % 13) as f32 * 0.1;
+            }
+        }
+        let dist = <Inversion2D<f32> as Discrete2D<f32>>::build(&weights);
+        let par = Inversion2D::par_build::<f32>(&weights);
+        assert_eq!(<Inversion2D<f32> as Discrete2D<f32>>::integral(&par), <Inversion2D<f32> as Discrete2D<f32>>::integral(&dist));
+        for y in 0..3 {
+            for x in 0..40_000 {
+                assert_eq!(<Inversion2D<f32> as Discrete2DPdf<f32>>::pdf(&par, [x, y]), <Inversion2D<f32> as Discrete2DPdf<f32>>::pdf(&dist, [x, y]));
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::distribution::{Discrete2D, test_serde_2d, test_serde_invalid};
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 0.0;
+        let dist = <Inversion2D<f32> as Discrete2D<f32>>::build(&weights);
+        test_serde_2d(&dist);
+        test_serde_invalid(&dist, |json| { json["conditional"].as_array_mut().unwrap().pop(); });
+        test_serde_invalid(&dist, |json| { json["conditional"][1]["cdf"].as_array_mut().unwrap().push(100.0.into()); });
+    }
+}
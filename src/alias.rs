@@ -1,13 +1,40 @@
 use crate::distribution::{
     Discrete1D,
+    Discrete1DPdf,
     Continuous1D,
+    ContinuousPdf1D,
+    RandomBits,
+    BuildError,
 };
-use crate::utils;
+#[cfg(feature = "rkyv")]
+use crate::distribution::{
+    ArchivedDiscrete1D,
+    ArchivedDiscrete1DPdf,
+    ArchivedValidate,
+};
+#[cfg(feature = "rkyv")]
+use crate::container::{
+    Algorithm,
+    Storable,
+    StorableWeight,
+};
+use crate::flat::{self, FlatTable1D};
+use crate::packed2d::{
+    PackedLayout,
+    PackedRow,
+    PackedRowPdf,
+};
+use crate::utils::{self, FixedPoint};
 use num_traits::{
     Num,
     real::Real,
     AsPrimitive,
 };
+use core::simd::{
+    Simd,
+    Select,
+    cmp::SimdPartialOrd,
+};
 
 #[cfg(not(feature = "std"))]
 use alloc::{
@@ -17,22 +44,278 @@ use alloc::{
 };
 
 pub type Alias2D<R> = crate::Adapter2D<Alias1D<R>>;
+pub type PackedAlias2D<R> = crate::Packed2D<Alias1D<R>>;
 
 #[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry<W> {
     select: W,
     alias: u32,
 }
 
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Alias1DFields<W>"))]
 pub struct Alias1D<W> {
     pub weight_sum: W,
     pub entries: Box<[Entry<W>]>,
+    pub pdf: Box<[W]>, // optional, empty unless kept by Alias1DWithPdf
+}
+
+// as serialized, checked before becoming an Alias1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Alias1DFields<W> {
+    weight_sum: W,
+    entries: Box<[Entry<W>]>,
+    pdf: Box<[W]>,
+}
+
+#[cfg(feature = "serde")]
+impl<W> TryFrom<Alias1DFields<W>> for Alias1D<W> {
+    type Error = &'static str;
+
+    fn try_from(Alias1DFields { weight_sum, entries, pdf }: Alias1DFields<W>) -> Result<Self, Self::Error> {
+        check_alias(entries.iter().map(|entry| entry.alias), pdf.len())?;
+        Ok(Self { weight_sum, entries, pdf })
+    }
+}
+
+// shared by serde and ArchivedAlias1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_alias(mut aliases: impl ExactSizeIterator<Item = u32>, pdf: usize) -> Result<(), &'static str> {
+    let n = aliases.len();
+    if n == 0 || aliases.any(|alias| alias as usize >= n) {
+        return Err("Alias1D alias is out of range");
+    }
+    // pdf is optional
+    if pdf != 0 && pdf != n {
+        return Err("Alias1D pdf doesn't match its entries");
+    }
+    Ok(())
+}
+
+// shared by the tables that always keep their pdf, on top of the checks of the table itself
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_kept_pdf(size: usize, pdf: usize) -> Result<(), &'static str> {
+    if pdf != size {
+        return Err("alias table doesn't have its pdf");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl<W> utils::TableSize for Alias1D<W> {
+    fn table_size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> utils::TableSize for ArchivedAlias1D<W> {
+    fn table_size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> ArchivedValidate for ArchivedAlias1D<W> {
+    fn validate(&self) -> Result<(), &'static str> {
+        check_alias(self.entries.iter().map(|entry| entry.alias.to_native()), self.pdf.len())
+    }
+}
+
+// entries as stored, either as is or archived, so that sampling is shared with the archived tables
+trait StoredEntry<T> {
+    fn load(&self) -> T;
+}
+
+impl<W: Copy> StoredEntry<Entry<W>> for Entry<W> {
+    fn load(&self) -> Entry<W> {
+        *self
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> StoredEntry<Entry<W>> for ArchivedEntry<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn load(&self) -> Entry<W> {
+        Entry { select: self.select.into(), alias: self.alias.to_native() }
+    }
+}
+
+fn sample_alias<W, R, S>(entries: &[S], weight_sum: W, u: R) -> usize
+    where W: Num + PartialOrd + AsPrimitive<R>,
+          R: Real + AsPrimitive<f64> + 'static,
+          S: StoredEntry<Entry<W>>,
+          f64: AsPrimitive<R>,
+{
+    let (index, v) = utils::scale_unit(u, entries.len());
+    let entry = entries[index].load();
+    let v = v * weight_sum.as_();
+    if entry.select.as_() <= v {
+        entry.alias as usize
+    } else {
+        index
+    }
+}
+
+fn sample_bits_alias<W, S>(entries: &[S], weight_sum: W, bits: u64) -> usize
+    where W: Num + PartialOrd + Copy,
+          S: StoredEntry<Entry<W>>,
+{
+    let (index, remainder) = utils::scale_bits(bits, entries.len());
+    let entry = entries[index].load();
+    // select is out of weight_sum, compared exactly against the remainder as a fraction of that
+    if weight_sum.is_zero() || entry.select.fixed_threshold(weight_sum) <= remainder as u128 {
+        entry.alias as usize
+    } else {
+        index
+    }
+}
+
+fn sample_remap_alias<W, R, S>(entries: &[S], weight_sum: W, u: R) -> (usize, R)
+    where W: Num + PartialOrd + AsPrimitive<R>,
+          R: Real + AsPrimitive<f64> + 'static,
+          S: StoredEntry<Entry<W>>,
+          f64: AsPrimitive<R>,
+{
+    let (index, v) = utils::scale_unit(u, entries.len());
+    let entry = entries[index].load();
+    let weight_sum: R = weight_sum.as_();
+    let select: R = entry.select.as_();
+    let v = v * weight_sum;
+    if select <= v {
+        (entry.alias as usize, utils::clamp_unit((v - select) / (weight_sum - select)))
+    } else {
+        (index, utils::clamp_unit(v / select))
+    }
+}
+
+// same as sample_alias, a lane at a time
+// entries are gathered one by one, as select and alias are interleaved
+fn sample_alias_lanes(entries: &[Entry<f32>], weight_sum: f32, u: [f32; utils::LANES]) -> [usize; utils::LANES] {
+    let (index, v) = utils::scale_unit_lanes(Simd::from_array(u), entries.len());
+    let v = v * Simd::splat(weight_sum);
+    let loaded = index.to_array().map(|i| entries[i]);
+    let select = Simd::from_array(loaded.map(|entry| entry.select));
+    let alias = Simd::from_array(loaded.map(|entry| entry.alias as usize));
+    select.simd_le(v).cast::<isize>().select(alias, index).to_array()
+}
+
+// sample_batch of Alias1D, with SIMD for f32, and one at a time otherwise
+trait AliasBatch<R>: Sized {
+    fn sample_batch(dist: &Alias1D<Self>, us: &[R], out: &mut [usize]);
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> AliasBatch<R> for W
+    where f64: AsPrimitive<R>,
+{
+    default fn sample_batch(dist: &Alias1D<W>, us: &[R], out: &mut [usize]) {
+        utils::batch(us, out, |u| sample_alias(&dist.entries, dist.weight_sum, u));
+    }
+}
+
+impl AliasBatch<f32> for f32 {
+    fn sample_batch(dist: &Alias1D<f32>, us: &[f32], out: &mut [usize]) {
+        utils::batch_lanes(us, out,
+            |u| sample_alias_lanes(&dist.entries, dist.weight_sum, u),
+            |u| sample_alias(&dist.entries, dist.weight_sum, u));
+    }
+}
+
+// parallel construction, after the split method of Hübschle-Schneider and Sanders
+// entries are laid out on two lines, in descending order as build pops the highest indices first,
+// one by how much each lacks of threshold (light), the other by how much each has spare (heavy)
+// a light aliases into the heavy whose spare covers where its lack starts, and a heavy whose spare ends
+// partway through a light's lack makes up the overrun from its own weight, aliasing into the next heavy
+// so the lines are split into blocks, each swept on its own from one binary search
+#[cfg(feature = "rayon")]
+const SPLIT_BLOCK: usize = 1 << 14;
+
+#[cfg(feature = "rayon")]
+struct Split<W> {
+    select: W,
+    alias: u32,
+    taken: [W; 2], // which part of what alias gives away this takes, from the top of it
+    given: W, // how much of its own weight this gives away
+}
+
+// entry of each split written to out, which must be as long as adjusted
+#[cfg(feature = "rayon")]
+fn par_split<W, T>(adjusted: &[W], threshold: W, out: &mut [T], entry: impl Fn(usize, Split<W>) -> T + Sync)
+    where W: Num + PartialOrd + Copy + Send + Sync,
+          T: Send,
+{
+    use rayon::prelude::*;
+
+    let n = adjusted.len();
+    let line = |length: fn(W, W) -> W| {
+        let mut line = vec![W::zero(); n + 1];
+        line[1..].par_iter_mut().zip(adjusted.par_iter().rev()).for_each(|(l, &a)| *l = length(a, threshold));
+        utils::par_prefix_sum(&mut line);
+        line
+    };
+    let lacks = line(|a, threshold| if a < threshold { threshold - a } else { W::zero() });
+    let spares = line(|a, threshold| if a < threshold { W::zero() } else { a - threshold });
+    let total_lack = lacks[n];
+    let total_spare = spares[n];
+
+    // within a block, positions on the lines only move forward from where the first binary search put them
+    out.par_chunks_mut(SPLIT_BLOCK).zip(adjusted.par_chunks(SPLIT_BLOCK)).enumerate().for_each(|(block_index, (out, block))| {
+        let start = block_index * SPLIT_BLOCK;
+        let [mut light_cover, mut overrun_cover, mut straddle] = [None; 3];
+
+        for (offset, &weight) in block.iter().enumerate().rev() {
+            let i = start + offset;
+            let p = n - 1 - i;
+            let unchanged = Split { select: threshold, alias: i as u32, taken: [W::zero(); 2], given: W::zero() };
+            let split = if weight < threshold {
+                // lack past the end of all spare is only from rounding, these are actually heavy, same as in build
+                if lacks[p] >= total_spare {
+                    unchanged
+                } else {
+                    // heavy whose spare covers where this lack starts, skipping any with nothing spare
+                    let j = advance(&mut light_cover, &spares[..n], lacks[p]) - 1;
+                    Split { select: weight, alias: (n - 1 - j) as u32, taken: [lacks[p] - spares[j], lacks[p + 1] - spares[j]], given: W::zero() }
+                }
+            } else {
+                let end = spares[p + 1];
+                if end > spares[p] && total_lack > spares[p] {
+                    // light whose lack runs past the end of this spare, if any
+                    // given is computed the same way as taken of the entries aliasing in, so that their regions meet exactly
+                    let k = advance(&mut straddle, &lacks, end);
+                    if end < total_spare && k <= n && lacks[k - 1] < end {
+                        let overrun = lacks[k] - end;
+                        let j = advance(&mut overrun_cover, &spares[..n], end) - 1;
+                        Split { select: threshold - overrun, alias: (n - 1 - j) as u32, taken: [W::zero(), overrun], given: lacks[k] - spares[p] }
+                    } else {
+                        let given = (if end < total_lack { end } else { total_lack }) - spares[p];
+                        Split { given, ..unchanged }
+                    }
+                } else {
+                    unchanged
+                }
+            };
+            out[offset] = entry(i, split);
+        }
+    });
+}
+
+// first index past position on a prefix sum line, searching only the first time as positions only increase
+#[cfg(feature = "rayon")]
+fn advance<W: PartialOrd + Copy>(cursor: &mut Option<usize>, line: &[W], position: W) -> usize {
+    let index = cursor.get_or_insert_with(|| line.partition_point(|&p| p <= position));
+    while *index < line.len() && line[*index] <= position {
+        *index += 1;
+    }
+    *index
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<usize> + 'static> Discrete1D<R> for Alias1D<W>
-    where usize: AsPrimitive<R>,
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> Discrete1D<R> for Alias1D<W>
+    where f64: AsPrimitive<R>,
           usize: AsPrimitive<W>,
 {
     type Weight = W;
@@ -41,16 +324,6 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<usize> + 'stati
     fn build(weights: &[W]) -> Self {
         let n = weights.len();
 
-        let is_f32 = core::any::TypeId::of::<f32>() == core::any::TypeId::of::<W>();
-
-        if is_f32 {
-            // due to the fact that we use f32s, multiplying a [0-1) f32 by about 2 million or so
-            // will give us numbers rounded to nearest float, which might be the next integer over, not
-            // the actual one
-            // would be nice if rust supported other float rounding modes...
-            assert!(n < 2_000_000, "Alias1D on f32s not reliable for distributions with more than 2,000,000 elements");
-        }
-
         assert!(n < u32::MAX as usize, "Current Alias1D implementation doesn't work for distributions with more than or equal to u32::MAX elements");
 
         let weight_sum = <W as utils::Sum>::sum(weights.iter().cloned());
@@ -91,14 +364,15 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<usize> + 'stati
             }
         }
 
-        // the select for entries in `large` should already all be >= sum, so 
-        // we don't need to update them here
-        // while large_head != u32::MAX {
-        //     let large = large_head as usize;
-        //     large_head = entries[large].alias;
+        // the select for entries in `large` should already all be >= sum, so
+        // we don't need to update them here, but alias is still a link in the list,
+        // so point it back at the entry itself in case v rounds up to the weight sum
+        while large_head != u32::MAX {
+            let large = large_head as usize;
+            large_head = entries[large].alias;
 
-        //     entries[large].select = weight_sum;
-        // }
+            entries[large].alias = large as u32;
+        }
 
         // these are actually large but are in small due to float error
         // they are currently slightly less than the weight sum, we need to make sure they're the weight sum
@@ -107,24 +381,43 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<usize> + 'stati
             small_head = entries[small].alias;
 
             entries[small].select = weight_sum;
+            entries[small].alias = small as u32;
         }
 
         Self {
             weight_sum,
             entries,
+            pdf: Box::default(),
         }
     }
 
-    fn sample(&self, u: R) -> usize {
-        let scaled: R = <usize as AsPrimitive<R>>::as_(self.entries.len()) * u;
-        let index: usize = scaled.as_();
-        let entry = self.entries[index];
-        let v = (scaled - index.as_()) * self.weight_sum.as_();
-        if entry.select.as_() <= v {
-            entry.alias as usize
-        } else {
-            index
+    fn try_build(weights: &[W]) -> Result<Self, BuildError>
+        where W: Copy,
+    {
+        let n = weights.len();
+        if n >= u32::MAX as usize {
+            return Err(BuildError::TooLarge);
         }
+        let weight_sum = utils::validate_weights(weights.iter().copied())?;
+        // weights are scaled by n in build
+        utils::CheckedWeight::checked_mul(weight_sum, n.as_()).ok_or(BuildError::Overflow)?;
+        Ok(<Self as Discrete1D<R>>::build(weights))
+    }
+
+    fn sample(&self, u: R) -> usize {
+        sample_alias::<W, R, _>(&self.entries, self.weight_sum, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_alias::<W, _>(&self.entries, self.weight_sum, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_alias::<W, R, _>(&self.entries, self.weight_sum, u)
+    }
+
+    fn sample_batch(&self, us: &[R], out: &mut [usize]) {
+        <W as AliasBatch<R>>::sample_batch(self, us, out);
     }
 
     fn integral(&self) -> W {
@@ -136,188 +429,1467 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<usize> + 'stati
     }
 }
 
-pub type ContinuousAlias2D<W> = crate::Adapter2D<ContinuousAlias1D<W>>;
-
-#[derive(Clone, Copy, Debug)]
-#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
-pub struct ContinuousEntry<W: Real> {
-    select: W,
-    alias: u32,
-    own_region: [W; 2], // which region of own entry do we sample
-    alias_region: [W; 2], // which region of alias entry do we sample
-}
-
-#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
-pub struct ContinuousAlias1D<W: Real> {
-    pub weight_sum: W,
-    pub entries: Box<[ContinuousEntry<W>]>,
-}
-
-impl<W: Real + AsPrimitive<usize>> Discrete1D<W> for ContinuousAlias1D<W>
-    where usize: AsPrimitive<W>,
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<f64> + 'static> ArchivedDiscrete1D<R> for ArchivedAlias1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
 {
     type Weight = W;
 
-    fn build(weights: &[W]) -> Self {
-        let n = weights.len();
+    fn sample(&self, u: R) -> usize {
+        sample_alias::<W, R, _>(&self.entries, self.weight_sum.into(), u)
+    }
 
-        // due to the fact that we use f32s, multiplying a [0-1) f32 by about 2 million or so
-        // will give us numbers rounded to nearest float, which might be the next integer over, not
-        // the actual one
-        // would be nice if rust supported other float rounding modes...
-        // TODO: disable if not f32
-        assert!(n < 2_000_000, "Alias1D not reliable for distributions with more than 2,000,000 elements");
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_alias::<W, _>(&self.entries, self.weight_sum.into(), bits.to_u64())
+    }
 
-        let mut entries = vec![ContinuousEntry { select: W::zero(), alias: 0, own_region: [W::zero(), W::one()], alias_region: [W::zero(), W::zero()] }; n].into_boxed_slice();
-        let mut adjusted_weights = vec![W::zero(); n].into_boxed_slice();
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_alias::<W, R, _>(&self.entries, self.weight_sum.into(), u)
+    }
 
-        let mut small = Vec::new();
-        let mut large = Vec::new();
+    fn integral(&self) -> W {
+        self.weight_sum.into()
+    }
 
-        let weight_sum = <W as utils::Sum>::sum(weights.iter().cloned());
+    fn size(&self) -> usize {
+        self.entries.len()
+    }
+}
 
-        for (i, weight) in weights.iter().enumerate() {
-            let adjusted_weight = (*weight * n.as_()) / weight_sum;
-            adjusted_weights[i] = adjusted_weight;
-            entries[i].select = adjusted_weight;
-            if adjusted_weight < W::one() {
-                small.push(i as u32);
-            } else {
-                large.push(i as u32);
-            }
-        }
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for Alias1D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Alias1D;
 
-        // always create a < 1.0 entry here
-        while !small.is_empty() && !large.is_empty() {
-            let less = small.pop().unwrap();
-            let more = large.pop().unwrap();
+    fn dimensions(&self) -> [u64; 2] {
+        [self.entries.len() as u64, 1]
+    }
+}
 
-            entries[less as usize].alias = more;
-            let mut region = entries[more as usize].own_region;
-            region[0] = region[1] - (W::one() - entries[less as usize].select) / adjusted_weights[more as usize];
-            entries[less as usize].alias_region = region;
-            entries[more as usize].own_region[1] = region[0];
+impl<W: AsPrimitive<f32>> FlatTable1D for Alias1D<W> {
+    const ALGORITHM: flat::Algorithm = flat::Algorithm::Alias2D;
 
-            // more numerically stable version of this
-            // entries[more as usize].select -= (W::one() - entries[less as usize].select);
-            entries[more as usize].select = (entries[more as usize].select + entries[less as usize].select) - W::one();
+    fn flat_size(&self) -> usize {
+        self.entries.len()
+    }
 
-            if entries[more as usize].select < W::one() {
-                small.push(more);
-            } else {
-                large.push(more);
-            }
+    fn write_flat(&self, buffer: &mut Vec<u32>) {
+        buffer.push(flat::weight_word(self.weight_sum));
+        for entry in self.entries.iter() {
+            buffer.extend([flat::weight_word(entry.select), entry.alias]);
         }
+    }
+}
 
-        // the select for entries in `large` should already all be >= 1.0, so 
-        // technically we don't need to update them here,
-        // but we use division by select in our continuous sampling methods, so we
-        // still need to make sure it's exactly one
-        while let Some(g) = large.pop() {
-            entries[g as usize].select = W::one();
-        }
+// a row is the weight sum, as the select of a leading entry, followed by the entries
+// pdf isn't packed, only sampling is
+impl<W: Copy> PackedLayout for Alias1D<W> {
+    type Entry = Entry<W>;
 
-        // these are actually large but are in small due to float error
-        // should be slightly less than 1.0, we need to make sure they're 1.0
-        while let Some(l) = small.pop() {
-            entries[l as usize].select = W::one();
-        }
+    fn stride(width: usize) -> usize {
+        width + 1
+    }
 
-        Self {
-            weight_sum,
-            entries,
-        }
+    fn pack(&self, entries: &mut Vec<Entry<W>>) {
+        entries.push(Entry { select: self.weight_sum, alias: 0 });
+        entries.extend_from_slice(&self.entries);
     }
+}
 
-    fn sample(&self, u: W) -> usize {
-        let scaled: W = self.entries.len().as_() * u;
-        let mut index: usize = scaled.as_();
-        let entry = self.entries[index];
-        let v = scaled - index.as_();
-        if entry.select <= v {
-            index = entry.alias as usize;
-        }
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> PackedRow<R> for Alias1D<W>
+    where f64: AsPrimitive<R>,
+          usize: AsPrimitive<W>,
+{
+    fn row_integral(row: &[Entry<W>]) -> W {
+        row[0].select
+    }
 
-        index
+    fn sample_row(row: &[Entry<W>], u: R) -> usize {
+        sample_alias::<W, R, _>(&row[1..], row[0].select, u)
     }
 
-    fn integral(&self) -> W {
-        self.weight_sum
+    fn sample_bits_row(row: &[Entry<W>], bits: u64) -> usize {
+        sample_bits_alias::<W, _>(&row[1..], row[0].select, bits)
     }
 
-    fn size(&self) -> usize {
-        self.entries.len()
+    fn sample_remap_row(row: &[Entry<W>], u: R) -> (usize, R) {
+        sample_remap_alias::<W, R, _>(&row[1..], row[0].select, u)
     }
 }
 
-impl<W: Real + AsPrimitive<usize>> Continuous1D<W> for ContinuousAlias1D<W>
+impl<W: Num + PartialOrd + Copy + 'static> Alias1D<W>
     where usize: AsPrimitive<W>,
 {
-    fn sample_continuous(&self, u: W) -> W {
-        let scaled: W = self.entries.len().as_() * u;
-        let initial_index: usize = scaled.as_();
-        let initial_entry = self.entries[initial_index];
-        let v = scaled - initial_index.as_();
+    // same distribution as build, built across threads, though not the same table
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &[W]) -> Self
+        where W: Send + Sync,
+    {
+        use rayon::prelude::*;
 
-        let (index, du) = if initial_entry.select <= v {
-            // selected alias of initial entry
-            let v_remapped = (v - initial_entry.select) / (W::one() - initial_entry.select);
+        let n = weights.len();
 
-            let du = v_remapped * (initial_entry.alias_region[1] - initial_entry.alias_region[0]) + initial_entry.alias_region[0];
-            let index = initial_entry.alias as usize;
-            (index, du)
-        } else {
-            // selected initial entry
-            let v_remapped = v / initial_entry.select;
+        assert!(n < u32::MAX as usize, "Current Alias1D implementation doesn't work for distributions with more than or equal to u32::MAX elements");
 
-            let index = initial_index;
-            let du = v_remapped * (initial_entry.own_region[1] - initial_entry.own_region[0]) + initial_entry.own_region[0];
+        let weight_sum = <W as utils::Sum>::sum(weights.iter().cloned());
+        let adjusted = weights.par_iter().map(|weight| *weight * n.as_()).collect::<Vec<_>>();
+        let mut entries = vec![Entry { select: W::zero(), alias: 0 }; n].into_boxed_slice();
+        par_split(&adjusted, weight_sum, &mut entries, |_, split| Entry { select: split.select, alias: split.alias });
 
-            (index, du)
-        };
+        Self {
+            weight_sum,
+            entries,
+            pdf: Box::default(),
+        }
+    }
+}
 
+pub type Alias2DWithPdf<R> = crate::Adapter2D<Alias1DWithPdf<R>>;
+pub type PackedAlias2DWithPdf<R> = crate::Packed2D<Alias1DWithPdf<R>>;
 
-        (index.as_() + du) / self.size().as_()
+// Alias1D that keeps the weights as its pdf, so it can be sampled with one
+// archived the same as an Alias1D with its pdf, so containers of either load as the other, if they have one
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Alias1D<W>", bound(deserialize = "W: serde::Deserialize<'de>")))]
+pub struct Alias1DWithPdf<W> {
+    table: Alias1D<W>, // pdf always filled in
+}
+
+impl<W: Clone> Alias1DWithPdf<W> {
+    fn keep_pdf(mut table: Alias1D<W>, weights: &[W]) -> Self {
+        table.pdf = weights.into();
+        Self { table }
     }
 
-    // O(n) at worst
-    fn invert_continuous(&self, u: W) -> W {
-        let scaled: W = self.entries.len().as_() * u;
-        let initial_index: usize = scaled.as_();
-        let initial_entry = self.entries[initial_index];
-        let v = scaled - initial_index.as_();
+    pub fn table(&self) -> &Alias1D<W> {
+        &self.table
+    }
 
-        let (index, du) = if initial_entry.own_region[0] == W::zero() && initial_entry.own_region[1] == W::one() {
-            (initial_index, v * initial_entry.select)
-        } else {
-            if v <= initial_entry.own_region[1] {
-                (initial_index, (v / initial_entry.own_region[1]) * initial_entry.select)
-            } else {
-                let mut index = None;
-                let mut du = None;
-                for (entry_idx, entry) in self.entries.iter().enumerate() {
-                    if entry.alias == initial_index as u32 {
-                        if entry.alias_region[0] <= v && v <= entry.alias_region[1] {
-                            let v_remapped = (v - entry.alias_region[0]) / (entry.alias_region[1] - entry.alias_region[0]);
-                            index = Some(entry_idx);
-                            du = Some(v_remapped * (W::one() - entry.select) + entry.select);
-                        }
-                    }
-                }
-                (index.unwrap(), du.unwrap())
-            }
-        };
+    pub fn into_table(self) -> Alias1D<W> {
+        self.table
+    }
+}
 
-        (index.as_() + du) / self.size().as_()
+#[cfg(feature = "serde")]
+impl<W: serde::Serialize> serde::Serialize for Alias1DWithPdf<W> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.table.serialize(serializer)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::distribution::distribution_1d_tests;
-    use crate::distribution::continuous_distribution_1d_tests;
+#[cfg(feature = "serde")]
+impl<W> TryFrom<Alias1D<W>> for Alias1DWithPdf<W> {
+    type Error = &'static str;
+
+    fn try_from(table: Alias1D<W>) -> Result<Self, Self::Error> {
+        check_kept_pdf(table.entries.len(), table.pdf.len())?;
+        Ok(Self { table })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W> utils::TableSize for Alias1DWithPdf<W> {
+    fn table_size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> utils::TableSize for ArchivedAlias1DWithPdf<W> {
+    fn table_size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
 
-    distribution_1d_tests!(crate::alias::Alias1D);
-    continuous_distribution_1d_tests!(crate::alias::ContinuousAlias1D);
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> ArchivedValidate for ArchivedAlias1DWithPdf<W> {
+    fn validate(&self) -> Result<(), &'static str> {
+        self.table.validate()?;
+        check_kept_pdf(self.table.entries.len(), self.table.pdf.len())
+    }
 }
 
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> Discrete1D<R> for Alias1DWithPdf<W>
+    where f64: AsPrimitive<R>,
+          usize: AsPrimitive<W>,
+{
+    type Weight = W;
+
+    fn build(weights: &[W]) -> Self {
+        Self::keep_pdf(<Alias1D<W> as Discrete1D<R>>::build(weights), weights)
+    }
+
+    fn try_build(weights: &[W]) -> Result<Self, BuildError>
+        where W: Copy,
+    {
+        Ok(Self::keep_pdf(<Alias1D<W> as Discrete1D<R>>::try_build(weights)?, weights))
+    }
+
+    fn sample(&self, u: R) -> usize {
+        self.table.sample(u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        Discrete1D::<R>::sample_bits(&self.table, bits)
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        self.table.sample_remap(u)
+    }
+
+    fn sample_batch(&self, us: &[R], out: &mut [usize]) {
+        self.table.sample_batch(us, out);
+    }
+
+    fn integral(&self) -> W {
+        self.table.weight_sum
+    }
+
+    fn size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> Discrete1DPdf<R> for Alias1DWithPdf<W>
+    where f64: AsPrimitive<R>,
+          usize: AsPrimitive<W>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.table.pdf[u]
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let idx = self.sample(u);
+        (idx, <Self as Discrete1DPdf<R>>::pdf(self, idx).as_() / self.table.weight_sum.as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<f64> + 'static> ArchivedDiscrete1D<R> for ArchivedAlias1DWithPdf<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: R) -> usize {
+        self.table.sample(u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        ArchivedDiscrete1D::<R>::sample_bits(&self.table, bits)
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        self.table.sample_remap(u)
+    }
+
+    fn integral(&self) -> W {
+        self.table.weight_sum.into()
+    }
+
+    fn size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<f64> + 'static> ArchivedDiscrete1DPdf<R> for ArchivedAlias1DWithPdf<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.table.pdf[u].into()
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let idx = self.sample(u);
+        (idx, self.pdf(idx).as_() / self.integral().as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for Alias1DWithPdf<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Alias1D;
+
+    fn dimensions(&self) -> [u64; 2] {
+        self.table.dimensions()
+    }
+}
+
+impl<W: AsPrimitive<f32>> FlatTable1D for Alias1DWithPdf<W> {
+    const ALGORITHM: flat::Algorithm = flat::Algorithm::Alias2D;
+
+    fn flat_size(&self) -> usize {
+        self.table.flat_size()
+    }
+
+    fn write_flat(&self, buffer: &mut Vec<u32>) {
+        self.table.write_flat(buffer);
+    }
+}
+
+// entry of a packed row with its weight alongside, so the pdf costs a word per weight, same as unpacked
+#[derive(Clone, Copy, Debug)]
+pub struct PdfEntry<W> {
+    select: W,
+    alias: u32,
+    pdf: W,
+}
+
+impl<W: Copy> StoredEntry<Entry<W>> for PdfEntry<W> {
+    fn load(&self) -> Entry<W> {
+        Entry { select: self.select, alias: self.alias }
+    }
+}
+
+// same as Alias1D, a row is the weight sum as the select of a leading entry, followed by the entries
+impl<W: Copy> PackedLayout for Alias1DWithPdf<W> {
+    type Entry = PdfEntry<W>;
+
+    fn stride(width: usize) -> usize {
+        width + 1
+    }
+
+    fn pack(&self, entries: &mut Vec<PdfEntry<W>>) {
+        let weight_sum = self.table.weight_sum;
+        entries.push(PdfEntry { select: weight_sum, alias: 0, pdf: weight_sum });
+        entries.extend(self.table.entries.iter().zip(self.table.pdf.iter()).map(|(entry, pdf)| {
+            PdfEntry { select: entry.select, alias: entry.alias, pdf: *pdf }
+        }));
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> PackedRow<R> for Alias1DWithPdf<W>
+    where f64: AsPrimitive<R>,
+          usize: AsPrimitive<W>,
+{
+    fn row_integral(row: &[PdfEntry<W>]) -> W {
+        row[0].select
+    }
+
+    fn sample_row(row: &[PdfEntry<W>], u: R) -> usize {
+        sample_alias::<W, R, _>(&row[1..], row[0].select, u)
+    }
+
+    fn sample_bits_row(row: &[PdfEntry<W>], bits: u64) -> usize {
+        sample_bits_alias::<W, _>(&row[1..], row[0].select, bits)
+    }
+
+    fn sample_remap_row(row: &[PdfEntry<W>], u: R) -> (usize, R) {
+        sample_remap_alias::<W, R, _>(&row[1..], row[0].select, u)
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<f64> + 'static> PackedRowPdf<R> for Alias1DWithPdf<W>
+    where f64: AsPrimitive<R>,
+          usize: AsPrimitive<W>,
+{
+    fn pdf_row(row: &[PdfEntry<W>], idx: usize) -> W {
+        row[1 + idx].pdf
+    }
+
+    fn sample_with_pdf_row(row: &[PdfEntry<W>], u: R) -> (usize, R) {
+        let idx = <Self as PackedRow<R>>::sample_row(row, u);
+        (idx, <Self as PackedRowPdf<R>>::pdf_row(row, idx).as_() / row[0].select.as_())
+    }
+}
+
+impl<W: Num + PartialOrd + Copy + 'static> Alias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+{
+    // same as Alias1D::par_build, keeping the weights as the pdf
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &[W]) -> Self
+        where W: Send + Sync,
+    {
+        Self::keep_pdf(Alias1D::par_build(weights), weights)
+    }
+}
+
+
+pub type ContinuousAlias2D<W> = crate::Adapter2D<ContinuousAlias1D<W>>;
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContinuousEntry<W: Real> {
+    select: W,
+    alias: u32,
+    own_region: [W; 2], // which region of own entry do we sample
+    alias_region: [W; 2], // which region of alias entry do we sample
+}
+
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "ContinuousAlias1DFields<W>"))]
+pub struct ContinuousAlias1D<W: Real> {
+    pub weight_sum: W,
+    pub entries: Box<[ContinuousEntry<W>]>,
+
+    // reverse of alias, for inversion
+    // donors[donor_offsets[i]..donor_offsets[i + 1]] are the entries aliasing into i,
+    // sorted by where their region starts
+    pub donor_offsets: Box<[u32]>,
+    pub donors: Box<[u32]>,
+
+    pub pdf: Box<[W]>, // optional, empty unless kept by ContinuousAlias1DWithPdf
+}
+
+// as serialized, checked before becoming a ContinuousAlias1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ContinuousAlias1DFields<W: Real> {
+    weight_sum: W,
+    entries: Box<[ContinuousEntry<W>]>,
+    donor_offsets: Box<[u32]>,
+    donors: Box<[u32]>,
+    pdf: Box<[W]>,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> TryFrom<ContinuousAlias1DFields<W>> for ContinuousAlias1D<W> {
+    type Error = &'static str;
+
+    fn try_from(fields: ContinuousAlias1DFields<W>) -> Result<Self, Self::Error> {
+        let ContinuousAlias1DFields { weight_sum, entries, donor_offsets, donors, pdf } = fields;
+        check_continuous_alias(&entries, &donor_offsets, &donors, pdf.len())?;
+        Ok(Self { weight_sum, entries, donor_offsets, donors, pdf })
+    }
+}
+
+// shared by serde and ArchivedContinuousAlias1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_continuous_alias<W: Real, S: StoredEntry<ContinuousEntry<W>>, G: Copy + Into<u32>>(entries: &[S], donor_offsets: &[G], donors: &[G], pdf: usize) -> Result<(), &'static str> {
+    let n = entries.len();
+    if n == 0 || entries.iter().any(|entry| entry.load().alias as usize >= n) {
+        return Err("ContinuousAlias1D alias is out of range");
+    }
+    let offset = |i: usize| donor_offsets[i].into() as usize;
+    if donor_offsets.len() != n + 1
+        || offset(0) != 0
+        || offset(n) != donors.len()
+        || (0..n).any(|i| offset(i) > offset(i + 1))
+        || donors.iter().any(|donor| (*donor).into() as usize >= n) {
+        return Err("ContinuousAlias1D donors are out of range");
+    }
+    // past its own region, invert_continuous searches the donors' alias regions by start,
+    // so they have to be sorted and cover the rest of [0, 1] without gaps
+    for i in 0..n {
+        let mut covered = entries[i].load().own_region[1];
+        if covered >= W::one() {
+            continue;
+        }
+        let mut start = W::zero();
+        for donor in &donors[offset(i)..offset(i + 1)] {
+            let region = entries[(*donor).into() as usize].load().alias_region;
+            if !(region[0] >= start && region[0] <= covered && region[1] >= covered) {
+                return Err("ContinuousAlias1D donors don't cover the alias regions");
+            }
+            start = region[0];
+            covered = region[1];
+        }
+        // NaN doesn't cover anything either
+        if covered.partial_cmp(&W::one()).is_none_or(core::cmp::Ordering::is_lt) {
+            return Err("ContinuousAlias1D donors don't cover the alias regions");
+        }
+    }
+    // pdf is optional
+    if pdf != 0 && pdf != n {
+        return Err("ContinuousAlias1D pdf doesn't match its entries");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> utils::TableSize for ContinuousAlias1D<W> {
+    fn table_size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> utils::TableSize for ArchivedContinuousAlias1D<W> {
+    fn table_size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> ArchivedValidate for ArchivedContinuousAlias1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_continuous_alias(&self.entries, &self.donor_offsets, &self.donors, self.pdf.len())
+    }
+}
+
+impl<W: Real> StoredEntry<ContinuousEntry<W>> for ContinuousEntry<W> {
+    fn load(&self) -> ContinuousEntry<W> {
+        *self
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> StoredEntry<ContinuousEntry<W>> for ArchivedContinuousEntry<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn load(&self) -> ContinuousEntry<W> {
+        ContinuousEntry {
+            select: self.select.into(),
+            alias: self.alias.to_native(),
+            own_region: self.own_region.map(Into::into),
+            alias_region: self.alias_region.map(Into::into),
+        }
+    }
+}
+
+// reverse of alias, only entries that were paired up are left with select < 1.0
+fn build_donors<W: Real>(entries: &[ContinuousEntry<W>]) -> (Box<[u32]>, Box<[u32]>) {
+    let n = entries.len();
+    let mut donor_offsets = vec![0u32; n + 1].into_boxed_slice();
+    for entry in entries.iter().filter(|entry| entry.select < W::one()) {
+        donor_offsets[entry.alias as usize + 1] += 1;
+    }
+    for i in 1..donor_offsets.len() {
+        donor_offsets[i] += donor_offsets[i - 1];
+    }
+
+    let mut donors = vec![0u32; donor_offsets[n] as usize].into_boxed_slice();
+    let mut cursors = donor_offsets[..n].to_vec();
+    for (i, entry) in entries.iter().enumerate().filter(|(_, entry)| entry.select < W::one()) {
+        donors[cursors[entry.alias as usize] as usize] = i as u32;
+        cursors[entry.alias as usize] += 1;
+    }
+    for i in 0..n {
+        donors[donor_offsets[i] as usize..donor_offsets[i + 1] as usize].sort_unstable_by(|a, b| {
+            entries[*a as usize].alias_region[0].partial_cmp(&entries[*b as usize].alias_region[0]).unwrap()
+        });
+    }
+    (donor_offsets, donors)
+}
+
+fn sample_continuous_alias<W, S>(entries: &[S], u: W) -> usize
+    where W: Real + AsPrimitive<f64>,
+          S: StoredEntry<ContinuousEntry<W>>,
+          f64: AsPrimitive<W>,
+{
+    let (mut index, v) = utils::scale_unit(u, entries.len());
+    let entry = entries[index].load();
+    if entry.select <= v {
+        index = entry.alias as usize;
+    }
+
+    index
+}
+
+fn sample_bits_continuous_alias<W, S>(entries: &[S], bits: u64) -> usize
+    where W: Real + AsPrimitive<f64>,
+          S: StoredEntry<ContinuousEntry<W>>,
+          f64: AsPrimitive<W>,
+{
+    let (mut index, remainder) = utils::scale_bits(bits, entries.len());
+    let entry = entries[index].load();
+    if entry.select.fixed_threshold(W::one()) <= remainder as u128 {
+        index = entry.alias as usize;
+    }
+
+    index
+}
+
+// returns sampled entry, where in it the sample lands, and normalized density there
+// each region is uniform, with density given by how much probability is spread over how much of the entry
+fn sample_entry<W, S>(entries: &[S], u: W) -> (usize, W, W)
+    where W: Real + AsPrimitive<f64>,
+          S: StoredEntry<ContinuousEntry<W>>,
+          f64: AsPrimitive<W>,
+{
+    let (initial_index, v) = utils::scale_unit(u, entries.len());
+    let initial_entry = entries[initial_index].load();
+
+    let (index, du, pdf) = if initial_entry.select <= v {
+        // selected alias of initial entry
+        let v_remapped = (v - initial_entry.select) / (W::one() - initial_entry.select);
+
+        let width = initial_entry.alias_region[1] - initial_entry.alias_region[0];
+        let du = v_remapped * width + initial_entry.alias_region[0];
+        let index = initial_entry.alias as usize;
+        (index, du, (W::one() - initial_entry.select) / width)
+    } else {
+        // selected initial entry
+        let v_remapped = v / initial_entry.select;
+
+        let index = initial_index;
+        let width = initial_entry.own_region[1] - initial_entry.own_region[0];
+        let du = v_remapped * width + initial_entry.own_region[0];
+
+        (index, du, initial_entry.select / width)
+    };
+
+    (index, utils::clamp_unit(du), pdf)
+}
+
+impl<W: Real + AsPrimitive<f64>> Discrete1D<W> for ContinuousAlias1D<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    type Weight = W;
+
+    fn build(weights: &[W]) -> Self {
+        let n = weights.len();
+
+        assert!(n < u32::MAX as usize, "Current ContinuousAlias1D implementation doesn't work for distributions with more than or equal to u32::MAX elements");
+
+        let mut entries = vec![ContinuousEntry { select: W::zero(), alias: 0, own_region: [W::zero(), W::one()], alias_region: [W::zero(), W::zero()] }; n].into_boxed_slice();
+        let mut adjusted_weights = vec![W::zero(); n].into_boxed_slice();
+
+        let mut small = Vec::new();
+        let mut large = Vec::new();
+
+        let weight_sum = <W as utils::Sum>::sum(weights.iter().cloned());
+
+        for (i, weight) in weights.iter().enumerate() {
+            let adjusted_weight = (*weight * n.as_()) / weight_sum;
+            adjusted_weights[i] = adjusted_weight;
+            entries[i].select = adjusted_weight;
+            if adjusted_weight < W::one() {
+                small.push(i as u32);
+            } else {
+                large.push(i as u32);
+            }
+        }
+
+        // always create a < 1.0 entry here
+        while !small.is_empty() && !large.is_empty() {
+            let less = small.pop().unwrap();
+            let more = large.pop().unwrap();
+
+            entries[less as usize].alias = more;
+            let mut region = entries[more as usize].own_region;
+            region[0] = region[1] - (W::one() - entries[less as usize].select) / adjusted_weights[more as usize];
+            entries[less as usize].alias_region = region;
+            entries[more as usize].own_region[1] = region[0];
+
+            // more numerically stable version of this
+            // entries[more as usize].select -= (W::one() - entries[less as usize].select);
+            entries[more as usize].select = (entries[more as usize].select + entries[less as usize].select) - W::one();
+
+            if entries[more as usize].select < W::one() {
+                small.push(more);
+            } else {
+                large.push(more);
+            }
+        }
+
+        // the select for entries in `large` should already all be >= 1.0, so 
+        // technically we don't need to update them here,
+        // but we use division by select in our continuous sampling methods, so we
+        // still need to make sure it's exactly one
+        while let Some(g) = large.pop() {
+            entries[g as usize].select = W::one();
+        }
+
+        // these are actually large but are in small due to float error
+        // should be slightly less than 1.0, we need to make sure they're 1.0
+        while let Some(l) = small.pop() {
+            entries[l as usize].select = W::one();
+        }
+
+        let (donor_offsets, donors) = build_donors(&entries);
+
+        Self {
+            weight_sum,
+            entries,
+            donor_offsets,
+            donors,
+            pdf: Box::default(),
+        }
+    }
+
+    fn try_build(weights: &[W]) -> Result<Self, BuildError> {
+        let n = weights.len();
+        if n >= u32::MAX as usize {
+            return Err(BuildError::TooLarge);
+        }
+        let weight_sum = utils::validate_weights(weights.iter().copied())?;
+        // weights are scaled by n in build
+        utils::CheckedWeight::checked_mul(weight_sum, n.as_()).ok_or(BuildError::Overflow)?;
+        Ok(Self::build(weights))
+    }
+
+    fn sample(&self, u: W) -> usize {
+        sample_continuous_alias(&self.entries, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_continuous_alias(&self.entries, bits.to_u64())
+    }
+
+    // remapped u is where in the entry the continuous sample lands, which is uniform
+    fn sample_remap(&self, u: W) -> (usize, W) {
+        let (index, du, _) = sample_entry(&self.entries, u);
+        (index, du)
+    }
+
+    fn integral(&self) -> W {
+        self.weight_sum
+    }
+
+    fn size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + AsPrimitive<f64> + rkyv::Archive> ArchivedDiscrete1D<W> for ArchivedContinuousAlias1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<W>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: W) -> usize {
+        sample_continuous_alias(&self.entries, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_continuous_alias(&self.entries, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: W) -> (usize, W) {
+        let (index, du, _) = sample_entry(&self.entries, u);
+        (index, du)
+    }
+
+    fn integral(&self) -> W {
+        self.weight_sum.into()
+    }
+
+    fn size(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + StorableWeight> Storable for ContinuousAlias1D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::ContinuousAlias1D;
+
+    fn dimensions(&self) -> [u64; 2] {
+        [self.entries.len() as u64, 1]
+    }
+}
+
+impl<W: Real + 'static> ContinuousAlias1D<W>
+    where usize: AsPrimitive<W>,
+{
+    // same distribution as build, built across threads, though not the same table
+    // each heavy gives away the top of its region, so own region stays at the bottom, as in build
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &[W]) -> Self
+        where W: Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        let n = weights.len();
+
+        assert!(n < u32::MAX as usize, "Current ContinuousAlias1D implementation doesn't work for distributions with more than or equal to u32::MAX elements");
+
+        let weight_sum = <W as utils::Sum>::sum(weights.iter().cloned());
+        let adjusted = weights.par_iter().map(|weight| (*weight * n.as_()) / weight_sum).collect::<Vec<_>>();
+        let mut entries = vec![ContinuousEntry { select: W::zero(), alias: 0, own_region: [W::zero(); 2], alias_region: [W::zero(); 2] }; n].into_boxed_slice();
+        par_split(&adjusted, W::one(), &mut entries, |i, split| {
+            let own_region = if split.given > W::zero() {
+                [W::zero(), (W::one() - split.given / adjusted[i]).max(W::zero())]
+            } else {
+                [W::zero(), W::one()]
+            };
+            let alias_region = if split.alias as usize == i {
+                [W::zero(); 2]
+            } else {
+                let alias_weight = adjusted[split.alias as usize];
+                [(W::one() - split.taken[1] / alias_weight).max(W::zero()), W::one() - split.taken[0] / alias_weight]
+            };
+            ContinuousEntry { select: split.select, alias: split.alias, own_region, alias_region }
+        });
+
+        let (donor_offsets, donors) = build_donors(&entries);
+
+        Self {
+            weight_sum,
+            entries,
+            donor_offsets,
+            donors,
+            pdf: Box::default(),
+        }
+    }
+}
+
+
+impl<W: Real + AsPrimitive<f64>> Continuous1D<W> for ContinuousAlias1D<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    fn sample_continuous(&self, u: W) -> W {
+        let (index, du) = self.sample_remap(u);
+        // du just below 1 can round up to the next index
+        utils::clamp_unit((index.as_() + du) / self.size().as_())
+    }
+
+    fn sample_continuous_with_pdf(&self, u: W) -> (W, W) {
+        let (index, du, pdf) = sample_entry(&self.entries, u);
+        (utils::clamp_unit((index.as_() + du) / self.size().as_()), pdf)
+    }
+
+    // O(log n) at worst, in the number of entries aliasing into the same one
+    fn invert_continuous(&self, u: W) -> W {
+        let (initial_index, v) = utils::scale_unit_inverse(u, self.entries.len());
+        let initial_entry = self.entries[initial_index];
+
+        let (index, du) = if initial_entry.own_region[0] == W::zero() && initial_entry.own_region[1] == W::one() {
+            (initial_index, v * initial_entry.select)
+        } else {
+            // own region can be empty if all of it went to entries aliasing into it
+            if v <= initial_entry.own_region[1] && initial_entry.own_region[1] > W::zero() {
+                (initial_index, (v / initial_entry.own_region[1]) * initial_entry.select)
+            } else {
+                let donors = &self.donors[self.donor_offsets[initial_index] as usize..self.donor_offsets[initial_index + 1] as usize];
+
+                // regions are contiguous, so can only be in the last one starting before v,
+                // or the one before it if exactly on the boundary, where the higher index wins
+                let mut candidate = donors.partition_point(|donor| self.entries[*donor as usize].alias_region[0] <= v);
+                let mut index = None;
+                while candidate > 0 && self.entries[donors[candidate - 1] as usize].alias_region[1] >= v {
+                    candidate -= 1;
+                    index = index.max(Some(donors[candidate] as usize));
+                }
+
+                let index = index.unwrap();
+                let entry = self.entries[index];
+                let v_remapped = (v - entry.alias_region[0]) / (entry.alias_region[1] - entry.alias_region[0]);
+                (index, v_remapped * (W::one() - entry.select) + entry.select)
+            }
+        };
+
+        (index.as_() + du) / self.size().as_()
+    }
+}
+
+impl<W: Real + AsPrimitive<f64>> ContinuousPdf1D<W> for ContinuousAlias1D<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    // density of whichever region x lands in, same as sample_continuous_with_pdf gives
+    fn pdf_continuous(&self, x: W) -> W {
+        let (index, t) = utils::scale_unit_inverse(x, self.entries.len());
+        let entry = self.entries[index];
+        let donors = &self.donors[self.donor_offsets[index] as usize..self.donor_offsets[index + 1] as usize];
+
+        if t < entry.own_region[1] || donors.is_empty() {
+            entry.select / (entry.own_region[1] - entry.own_region[0])
+        } else {
+            let candidate = donors.partition_point(|donor| self.entries[*donor as usize].alias_region[0] <= t).max(1);
+            let donor = self.entries[donors[candidate - 1] as usize];
+            (W::one() - donor.select) / (donor.alias_region[1] - donor.alias_region[0])
+        }
+    }
+}
+
+pub type ContinuousAlias2DWithPdf<W> = crate::Adapter2D<ContinuousAlias1DWithPdf<W>>;
+
+// ContinuousAlias1D that keeps the weights as its pdf, same as Alias1DWithPdf
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "ContinuousAlias1D<W>", bound(deserialize = "W: serde::Deserialize<'de>")))]
+pub struct ContinuousAlias1DWithPdf<W: Real> {
+    table: ContinuousAlias1D<W>, // pdf always filled in
+}
+
+impl<W: Real> ContinuousAlias1DWithPdf<W> {
+    fn keep_pdf(mut table: ContinuousAlias1D<W>, weights: &[W]) -> Self {
+        table.pdf = weights.into();
+        Self { table }
+    }
+
+    pub fn table(&self) -> &ContinuousAlias1D<W> {
+        &self.table
+    }
+
+    pub fn into_table(self) -> ContinuousAlias1D<W> {
+        self.table
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real + serde::Serialize> serde::Serialize for ContinuousAlias1DWithPdf<W> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.table.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> TryFrom<ContinuousAlias1D<W>> for ContinuousAlias1DWithPdf<W> {
+    type Error = &'static str;
+
+    fn try_from(table: ContinuousAlias1D<W>) -> Result<Self, Self::Error> {
+        check_kept_pdf(table.entries.len(), table.pdf.len())?;
+        Ok(Self { table })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> utils::TableSize for ContinuousAlias1DWithPdf<W> {
+    fn table_size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> utils::TableSize for ArchivedContinuousAlias1DWithPdf<W> {
+    fn table_size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> ArchivedValidate for ArchivedContinuousAlias1DWithPdf<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        self.table.validate()?;
+        check_kept_pdf(self.table.entries.len(), self.table.pdf.len())
+    }
+}
+
+impl<W: Real + AsPrimitive<f64>> Discrete1D<W> for ContinuousAlias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    type Weight = W;
+
+    fn build(weights: &[W]) -> Self {
+        Self::keep_pdf(ContinuousAlias1D::build(weights), weights)
+    }
+
+    fn try_build(weights: &[W]) -> Result<Self, BuildError> {
+        Ok(Self::keep_pdf(ContinuousAlias1D::try_build(weights)?, weights))
+    }
+
+    fn sample(&self, u: W) -> usize {
+        self.table.sample(u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        self.table.sample_bits(bits)
+    }
+
+    fn sample_remap(&self, u: W) -> (usize, W) {
+        self.table.sample_remap(u)
+    }
+
+    fn integral(&self) -> W {
+        self.table.weight_sum
+    }
+
+    fn size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+impl<W: Real + AsPrimitive<f64>> Discrete1DPdf<W> for ContinuousAlias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.table.pdf[u]
+    }
+
+    fn sample_with_pdf(&self, u: W) -> (usize, W) {
+        let idx = self.sample(u);
+        (idx, self.pdf(idx) / self.table.weight_sum)
+    }
+}
+
+impl<W: Real + AsPrimitive<f64>> Continuous1D<W> for ContinuousAlias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    fn sample_continuous(&self, u: W) -> W {
+        self.table.sample_continuous(u)
+    }
+
+    fn sample_continuous_with_pdf(&self, u: W) -> (W, W) {
+        self.table.sample_continuous_with_pdf(u)
+    }
+
+    fn invert_continuous(&self, u: W) -> W {
+        self.table.invert_continuous(u)
+    }
+}
+
+impl<W: Real + AsPrimitive<f64>> ContinuousPdf1D<W> for ContinuousAlias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+          f64: AsPrimitive<W>,
+{
+    fn pdf_continuous(&self, x: W) -> W {
+        self.table.pdf_continuous(x)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + AsPrimitive<f64> + rkyv::Archive> ArchivedDiscrete1D<W> for ArchivedContinuousAlias1DWithPdf<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<W>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: W) -> usize {
+        self.table.sample(u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        self.table.sample_bits(bits)
+    }
+
+    fn sample_remap(&self, u: W) -> (usize, W) {
+        self.table.sample_remap(u)
+    }
+
+    fn integral(&self) -> W {
+        self.table.weight_sum.into()
+    }
+
+    fn size(&self) -> usize {
+        self.table.entries.len()
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + AsPrimitive<f64> + rkyv::Archive> ArchivedDiscrete1DPdf<W> for ArchivedContinuousAlias1DWithPdf<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<W>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.table.pdf[u].into()
+    }
+
+    fn sample_with_pdf(&self, u: W) -> (usize, W) {
+        let idx = self.sample(u);
+        (idx, self.pdf(idx) / self.integral())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + StorableWeight> Storable for ContinuousAlias1DWithPdf<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::ContinuousAlias1D;
+
+    fn dimensions(&self) -> [u64; 2] {
+        self.table.dimensions()
+    }
+}
+
+impl<W: Real + 'static> ContinuousAlias1DWithPdf<W>
+    where usize: AsPrimitive<W>,
+{
+    // same as ContinuousAlias1D::par_build, keeping the weights as the pdf
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &[W]) -> Self
+        where W: Send + Sync,
+    {
+        Self::keep_pdf(ContinuousAlias1D::par_build(weights), weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distribution::distribution_1d_tests;
+    use crate::distribution::continuous_distribution_1d_tests;
+
+    // the shared discrete tests check the pdf too, Alias1D itself samples the same
+    distribution_1d_tests!(crate::alias::Alias1DWithPdf);
+    // the continuous sample jumps at the edges of the entries, so its inverse is checked between them
+    continuous_distribution_1d_tests!(crate::alias::ContinuousAlias1D, build, test_inv_1d_midpoints);
+
+    // the shared tests again, on tables built with par_build
+    #[cfg(feature = "rayon")]
+    mod par_build {
+        use crate::distribution::{distribution_1d_tests, continuous_distribution_1d_tests};
+        use crate::alias::{Alias1D, Alias1DWithPdf};
+
+        distribution_1d_tests!(crate::alias::Alias1DWithPdf, par_build);
+        continuous_distribution_1d_tests!(crate::alias::ContinuousAlias1D, par_build, test_inv_1d_midpoints);
+
+        #[test]
+        fn pdf() {
+            use crate::distribution::chisq_distribution_1d_pdf;
+
+            // pdf is the weights as given, same as build
+            let weights = [1, 0, 2, 4, 8, 100, 3, 3, 0, 40];
+            let dist = Alias1DWithPdf::par_build(&weights);
+            assert_eq!(&*dist.table().pdf, &weights);
+            chisq_distribution_1d_pdf(&dist, &weights, 100_000);
+            assert!(Alias1D::par_build(&weights).pdf.is_empty());
+        }
+
+        #[test]
+        fn large() {
+            use crate::distribution::chisq_distribution_1d;
+
+            // spans several prefix sum blocks, pdf is still the weights as given
+            let weights = (0..100_000).map(|i| (i * 7919) % 101).collect::<Vec<usize>>();
+            let dist = Alias1DWithPdf::par_build(&weights);
+            assert_eq!(&*dist.table().pdf, &weights);
+
+            let weights = (0..50_000).map(|i| (i % 13 + 5) as f64).collect::<Vec<_>>();
+            chisq_distribution_1d(Alias1D::<f64>::par_build, &weights, 2_000_000);
+            let mut sorted = weights.clone();
+            sorted.sort_floats();
+            chisq_distribution_1d(Alias1D::<f64>::par_build, &sorted, 2_000_000);
+        }
+
+        // the donors' alias regions of parallel built tables meet exactly, so they validate too
+        #[cfg(feature = "rkyv")]
+        #[test]
+        fn archived() {
+            use crate::alias::ContinuousAlias1D;
+            use crate::distribution::access_checked;
+
+            let weights = (0..100_000).map(|i| ((i * 7919) % 101) as f32 * 0.37).collect::<Vec<_>>();
+            let dist = ContinuousAlias1D::par_build(&weights);
+            let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&dist).unwrap();
+            assert!(access_checked::<crate::alias::ArchivedContinuousAlias1D<f32>>(&bytes).is_ok());
+        }
+    }
+
+    mod pdf {
+        use crate::distribution::{
+            Discrete1D,
+            Discrete2D,
+            chisq_distribution_1d_pdf,
+            chisq_distribution_2d_pdf,
+        };
+        use crate::alias::{
+            Alias1D,
+            Alias1DWithPdf,
+            Alias2DWithPdf,
+            ContinuousAlias1D,
+            ContinuousAlias1DWithPdf,
+            ContinuousAlias2DWithPdf,
+        };
+        use crate::data2d::Data2D;
+
+        fn weights_2d() -> Data2D<usize> {
+            let mut weights = Data2D::new_same(5, 3, 1);
+            weights[[4, 0]] = 30;
+            weights[[2, 1]] = 0;
+            weights[[1, 2]] = 4;
+            weights
+        }
+
+        #[test]
+        fn alias_1d() {
+            // pdf is the weights as given
+            let weights = [1, 0, 2, 4, 8, 100];
+            let dist = <Alias1DWithPdf<usize> as Discrete1D<f64>>::build(&weights);
+            assert_eq!(&*dist.table().pdf, &weights);
+            chisq_distribution_1d_pdf(&dist, &weights, 100_000);
+
+            let weights = [0.5, 3.0, 1.0, 0.0, 0.25, 7.0];
+            let dist = <Alias1DWithPdf<f64> as Discrete1D<f64>>::build(&weights);
+            chisq_distribution_1d_pdf(&dist, &weights, 100_000);
+        }
+
+        #[test]
+        fn continuous_alias_1d() {
+            let weights = [0.5, 3.0, 1.0, 0.0, 0.25, 7.0];
+            let dist = <ContinuousAlias1DWithPdf<f64> as Discrete1D<f64>>::build(&weights);
+            assert_eq!(&*dist.table().pdf, &weights);
+            chisq_distribution_1d_pdf(&dist, &weights, 100_000);
+        }
+
+        // only kept when asked for
+        #[test]
+        fn not_kept() {
+            let weights = [0.5, 3.0, 1.0, 0.0, 0.25, 7.0];
+            assert!(<Alias1D<f64> as Discrete1D<f64>>::build(&weights).pdf.is_empty());
+            assert!(<ContinuousAlias1D<f64> as Discrete1D<f64>>::build(&weights).pdf.is_empty());
+        }
+
+        #[test]
+        fn alias_2d() {
+            let weights = weights_2d();
+            let dist = <Alias2DWithPdf<usize> as Discrete2D<f64>>::build(&weights);
+            chisq_distribution_2d_pdf(&dist, &weights, 100_000);
+        }
+
+        #[test]
+        fn continuous_alias_2d() {
+            let mut weights = Data2D::new_same(5, 3, 0.0);
+            for (weight, integer) in weights.iter_mut().flatten().zip(weights_2d().iter().flatten()) {
+                *weight = *integer as f64 / 3.0;
+            }
+            let dist = <ContinuousAlias2DWithPdf<f64> as Discrete2D<f64>>::build(&weights);
+            chisq_distribution_2d_pdf(&dist, &weights, 100_000);
+        }
+    }
+
+    #[test]
+    fn try_build() {
+        use crate::distribution::{Discrete1D, BuildError};
+        use crate::alias::{Alias1D, Alias2D, ContinuousAlias1D, ContinuousAlias2D};
+
+        crate::distribution::test_try_build_1d::<Alias1D<f32>>();
+        crate::distribution::test_try_build_1d::<ContinuousAlias1D<f32>>();
+        crate::distribution::test_try_build_2d::<Alias2D<f32>>();
+        crate::distribution::test_try_build_2d::<ContinuousAlias2D<f32>>();
+
+        // sum fits, but not once scaled by n
+        assert_eq!(<Alias1D<usize> as Discrete1D<f32>>::try_build(&[usize::MAX / 2, 1, 0]).err(), Some(BuildError::Overflow));
+        assert_eq!(<ContinuousAlias1D<f32> as Discrete1D<f32>>::try_build(&[f32::MAX / 2.0, f32::MAX / 4.0]).err(), Some(BuildError::Overflow));
+    }
+
+    #[test]
+    fn continuous_remap() {
+        crate::distribution::chisq_distribution_1d_remap(<crate::alias::ContinuousAlias1D<f64> as crate::distribution::Discrete1D<f64>>::build, &[0.5, 3.0, 1.0, 0.25, 0.5, 7.0], 100_000);
+    }
+
+    mod large {
+        use crate::distribution::{
+            Discrete1D,
+            Continuous1D,
+        };
+        use crate::alias::{
+            Alias1D,
+            ContinuousAlias1D,
+        };
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // used to be unreliable past 2,000,000 elements due to u * n rounding up to the next index
+        #[test]
+        fn uniform_f32() {
+            let n = 5_000_000;
+            let weights = vec![1.0f32; n];
+            let alias = <Alias1D<f32> as Discrete1D<f32>>::build(&weights);
+            let continuous = <ContinuousAlias1D<f32> as Discrete1D<f32>>::build(&weights);
+
+            let mut rng = StdRng::seed_from_u64(0);
+            let edges = [0.0, f32::MIN_POSITIVE, 1.0 - f32::EPSILON / 2.0, 1.0];
+            for u in (0..100_000).map(|_| rng.r#gen::<f32>()).chain(edges) {
+                // exact in f64
+                let expected = ((u as f64 * n as f64) as usize).min(n - 1);
+                assert_eq!(alias.sample(u), expected);
+                assert_eq!(continuous.sample(u), expected);
+                assert!(continuous.sample_continuous(u) <= 1.0);
+            }
+        }
+
+        #[test]
+        fn uniform_bits() {
+            let n = 5_000_000;
+            let weights = vec![1.0f32; n];
+            let alias = <Alias1D<f32> as Discrete1D<f32>>::build(&weights);
+            let continuous = <ContinuousAlias1D<f32> as Discrete1D<f32>>::build(&weights);
+
+            let mut rng = StdRng::seed_from_u64(0);
+            for bits in (0..100_000).map(|_| rng.r#gen::<u64>()).chain([0, u64::MAX]) {
+                let expected = ((bits as u128 * n as u128) >> 64) as usize;
+                assert_eq!(Discrete1D::<f32>::sample_bits(&alias, bits), expected);
+                assert_eq!(continuous.sample_bits(bits), expected);
+            }
+        }
+    }
+
+    mod reverse_index {
+        use crate::distribution::{
+            Discrete1D,
+            Continuous1D,
+        };
+        use crate::alias::ContinuousAlias1D;
+
+        // original O(n) version
+        fn invert_linear_scan(dist: &ContinuousAlias1D<f64>, u: f64) -> f64 {
+            let scaled = dist.entries.len() as f64 * u;
+            let initial_index = scaled as usize;
+            let initial_entry = dist.entries[initial_index];
+            let v = scaled - initial_index as f64;
+
+            let (index, du) = if initial_entry.own_region[0] == 0.0 && initial_entry.own_region[1] == 1.0 {
+                (initial_index, v * initial_entry.select)
+            } else if v <= initial_entry.own_region[1] {
+                (initial_index, (v / initial_entry.own_region[1]) * initial_entry.select)
+            } else {
+                let mut index = None;
+                let mut du = None;
+                for (entry_idx, entry) in dist.entries.iter().enumerate() {
+                    if entry.alias == initial_index as u32 && entry.alias_region[0] <= v && v <= entry.alias_region[1] {
+                        let v_remapped = (v - entry.alias_region[0]) / (entry.alias_region[1] - entry.alias_region[0]);
+                        index = Some(entry_idx);
+                        du = Some(v_remapped * (1.0 - entry.select) + entry.select);
+                    }
+                }
+                (index.unwrap(), du.unwrap())
+            };
+
+            (index as f64 + du) / dist.entries.len() as f64
+        }
+
+        fn test_matching(weights: &[f64]) {
+            let dist = <ContinuousAlias1D<f64> as Discrete1D<f64>>::build(weights);
+            let sample_count = 10_000;
+            for i in 0..sample_count {
+                let u = i as f64 / sample_count as f64;
+                assert_eq!(dist.invert_continuous(u), invert_linear_scan(&dist, u));
+
+                // also exactly on region boundaries
+                let x = dist.sample_continuous(u);
+                assert_eq!(dist.invert_continuous(x), invert_linear_scan(&dist, x));
+            }
+        }
+
+        #[test]
+        fn matching_basic() {
+            test_matching(&[1.0, 1.0, 2.0, 4.0, 8.0]);
+        }
+
+        #[test]
+        fn matching_increasing() {
+            let mut distr = [0.0; 100];
+            for (i, weight) in distr.iter_mut().enumerate() {
+                *weight = (5 * (i + 1)) as f64;
+            }
+            test_matching(&distr);
+        }
+
+        #[test]
+        fn matching_spiky() {
+            let mut distr = [0.01; 1000];
+            distr[3] = 500.0;
+            distr[999] = 100.0;
+            test_matching(&distr);
+        }
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use crate::distribution::{Discrete1D, Discrete2D, ArchivedDiscrete1D, access_checked};
+        use crate::alias::{
+            Alias1D,
+            Alias1DWithPdf,
+            Alias2DWithPdf,
+            ArchivedAlias1D,
+            ArchivedAlias1DWithPdf,
+            ContinuousAlias1DWithPdf,
+            ArchivedContinuousAlias1DWithPdf,
+        };
+        use rkyv::rancor::Error;
+
+        let weights = [1.0f32, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0];
+        let dist = <Alias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, access_checked::<ArchivedAlias1DWithPdf<f32>>(&bytes).unwrap());
+
+        // same layout, with or without the pdf
+        let archived = access_checked::<ArchivedAlias1D<f32>>(&bytes).unwrap();
+        for i in 0..1000 {
+            let u = i as f32 / 1000.0;
+            assert_eq!(archived.sample_remap(u), dist.sample_remap(u));
+        }
+        let bytes = rkyv::to_bytes::<Error>(&<Alias1D<f32> as Discrete1D<f32>>::build(&weights)).unwrap();
+        assert!(access_checked::<ArchivedAlias1D<f32>>(&bytes).is_ok());
+        assert!(access_checked::<ArchivedAlias1DWithPdf<f32>>(&bytes).is_err());
+
+        let dist = <ContinuousAlias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, access_checked::<ArchivedContinuousAlias1DWithPdf<f32>>(&bytes).unwrap());
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        weights[[4, 0]] = 30.0;
+        weights[[2, 1]] = 0.0;
+        let dist = <Alias2DWithPdf<f32> as Discrete2D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_2d(&dist, access_checked::<crate::adapter2d::ArchivedAdapter2D<Alias1DWithPdf<f32>>>(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_invalid() {
+        use crate::distribution::{Discrete1D, test_archived_invalid};
+        use crate::alias::{Alias1D, Alias1DWithPdf, ContinuousAlias1D, ContinuousAlias1DWithPdf};
+
+        let weights = [1.0f32, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0];
+        let dist = <Alias1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut entries = dist.entries.clone();
+        entries[2].alias = 7;
+        test_archived_invalid(&dist, &Alias1D { weight_sum: dist.weight_sum, entries, pdf: dist.pdf.clone() });
+        test_archived_invalid(&dist, &Alias1D { weight_sum: dist.weight_sum, entries: dist.entries.clone(), pdf: weights[1..].into() });
+
+        // pdf is optional, but not for the tables that keep it
+        let with_pdf = <Alias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        test_archived_invalid(&with_pdf, &Alias1DWithPdf { table: dist });
+        let with_pdf = <ContinuousAlias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        test_archived_invalid(&with_pdf, &ContinuousAlias1DWithPdf { table: ContinuousAlias1D::build(&weights) });
+
+        let build = || <ContinuousAlias1D<f32> as Discrete1D<f32>>::build(&weights);
+        let mut invalid = build();
+        invalid.donors[0] = 7;
+        test_archived_invalid(&build(), &invalid);
+        let mut invalid = build();
+        invalid.donor_offsets[1] = invalid.donor_offsets[2] + 1;
+        test_archived_invalid(&build(), &invalid);
+
+        // donors in range, but not covering the alias regions past the own ones
+        let mut invalid = build();
+        invalid.donor_offsets.fill(0);
+        invalid.donors = Box::default();
+        test_archived_invalid(&build(), &invalid);
+        let mut invalid = build();
+        let i = (0..weights.len()).find(|&i| invalid.donor_offsets[i + 1] - invalid.donor_offsets[i] > 1).unwrap();
+        invalid.donors[invalid.donor_offsets[i] as usize..invalid.donor_offsets[i + 1] as usize].reverse();
+        test_archived_invalid(&build(), &invalid);
+        let mut invalid = build();
+        let donor = invalid.donors[0] as usize;
+        invalid.entries[donor].alias_region[1] = invalid.entries[donor].alias_region[0];
+        test_archived_invalid(&build(), &invalid);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::distribution::{Discrete1D, Discrete2D, test_serde_1d, test_serde_2d, test_serde_invalid};
+        use crate::alias::{Alias1D, Alias1DWithPdf, Alias2DWithPdf, ContinuousAlias1D, ContinuousAlias1DWithPdf};
+
+        let weights = [1.0f32, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0];
+        let dist = <Alias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| json["entries"][0]["alias"] = 7.into());
+        test_serde_invalid(&dist, |json| { json["pdf"].as_array_mut().unwrap().pop(); });
+        test_serde_invalid(&dist, |json| json["pdf"] = serde_json::Value::Array(Vec::new()));
+
+        // pdf is optional, and the same table with it serializes the same
+        let dist = <Alias1D<f32> as Discrete1D<f32>>::build(&weights);
+        test_serde_invalid(&dist, |json| json["pdf"] = weights[1..].into());
+        let json = serde_json::to_value(<Alias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights)).unwrap();
+        assert_eq!(&*serde_json::from_value::<Alias1D<f32>>(json).unwrap().pdf, &weights);
+
+        let dist = <ContinuousAlias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| json["entries"][0]["alias"] = 7.into());
+        test_serde_invalid(&dist, |json| json["donors"][0] = 7.into());
+        test_serde_invalid(&dist, |json| { json["donor_offsets"].as_array_mut().unwrap().pop(); });
+        test_serde_invalid(&dist, |json| {
+            json["donor_offsets"] = vec![0; weights.len() + 1].into();
+            json["donors"] = serde_json::Value::Array(Vec::new());
+        });
+        test_serde_invalid(&dist, |json| json["pdf"] = serde_json::Value::Array(Vec::new()));
+        test_serde_invalid(&<ContinuousAlias1D<f32> as Discrete1D<f32>>::build(&weights), |json| json["pdf"] = weights[1..].into());
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        weights[[4, 0]] = 30.0;
+        weights[[2, 1]] = 0.0;
+        let dist = <Alias2DWithPdf<f32> as Discrete2D<f32>>::build(&weights);
+        test_serde_2d(&dist);
+    }
+}
@@ -0,0 +1,636 @@
+// versioned binary container for precomputed distributions, so they don't need rebuilding at load
+//
+// layout, all integers little endian:
+//
+//   0   8  magic, "DSAMPLER"
+//   8   4  version, u32, see VERSION
+//  12   1  weight type, see WeightType
+//  13   1  algorithm, see Algorithm
+//  14   1  flags, bit 0 set if the algorithm is the table of each row of an Adapter2D
+//  15   1  reserved, zero
+//  16   8  width, u64, number of weights (segments for piecewise linear, cells for bilinear)
+//  24   8  height, u64, 1 for 1D
+//  32   8  payload length in bytes, u64
+//  40   8  checksum of payload, u64, see checksum
+//  48  16  reserved, zero
+//  64      payload, rkyv 0.8 archive of the distribution (little endian, 32 bit relative pointers)
+//
+// the payload is 64 byte aligned relative to the start, so a memory mapped file can be
+// accessed in place, and sampled directly with the Archived* distributions
+//
+// version history:
+//   1  initial
+//   2  Hierarchical1D and Hierarchical2D payloads are a single flat tree, rather than a list of levels
+//   3  Hierarchical2D leaves are padded along each axis separately, rather than to a square
+//
+// payloads of an older layout than a distribution's LAYOUT_VERSION can't be accessed in place,
+// but are still loaded by from_container, which upgrades them
+
+use rkyv::{
+    api::high::{HighSerializer, HighValidator, HighDeserializer},
+    bytecheck::CheckBytes,
+    rancor,
+    ser::allocator::ArenaHandle,
+    util::AlignedVec,
+    Portable,
+};
+
+use crate::distribution::{
+    access_checked,
+    ArchivedValidate,
+};
+
+pub const MAGIC: [u8; 8] = *b"DSAMPLER";
+pub const VERSION: u32 = 3;
+pub const HEADER_SIZE: usize = 64;
+pub const PAYLOAD_ALIGN: usize = 64;
+
+// why a container can't be read
+#[derive(Debug)]
+pub enum ContainerError {
+    Truncated, // fewer bytes than the header, or the payload it describes
+    Magic, // not a container
+    Version(u32), // written by a newer version of the format, or not a version at all
+    Outdated(u32), // written by an older version, with a payload layout that has to be upgraded by from_container
+    Mismatch, // holds a different distribution, weight type or dimensions than asked for
+    Misaligned, // payload isn't PAYLOAD_ALIGN aligned in memory, so can't be accessed in place
+    Checksum, // payload doesn't match its checksum
+    Invalid, // payload fails validation, or can't be serialized
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl core::fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "container is truncated"),
+            Self::Magic => write!(f, "not a distribution container"),
+            Self::Version(version) => write!(f, "container version {} isn't supported, only 1 to {}", version, VERSION),
+            Self::Outdated(version) => write!(f, "container version {} has an outdated layout, and can't be accessed in place", version),
+            Self::Mismatch => write!(f, "container holds a different distribution"),
+            Self::Misaligned => write!(f, "container payload isn't {} byte aligned", PAYLOAD_ALIGN),
+            Self::Checksum => write!(f, "container payload doesn't match its checksum"),
+            Self::Invalid => write!(f, "container payload is invalid"),
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl core::error::Error for ContainerError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ContainerError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+// tags, values are part of the format, so must never change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WeightType {
+    F32 = 1,
+    F64 = 2,
+    U32 = 3,
+    U64 = 4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Algorithm {
+    Inversion1D = 1,
+    EytzingerInversion1D = 2,
+    Alias1D = 3,
+    ContinuousAlias1D = 4,
+    Hierarchical1D = 5,
+    PiecewiseLinear1D = 6,
+    Hierarchical2D = 7,
+    Bilinear2D = 8,
+}
+
+impl WeightType {
+    fn from_tag(tag: u8) -> Option<Self> {
+        [Self::F32, Self::F64, Self::U32, Self::U64].into_iter().find(|t| *t as u8 == tag)
+    }
+}
+
+impl Algorithm {
+    fn from_tag(tag: u8) -> Option<Self> {
+        [
+            Self::Inversion1D,
+            Self::EytzingerInversion1D,
+            Self::Alias1D,
+            Self::ContinuousAlias1D,
+            Self::Hierarchical1D,
+            Self::PiecewiseLinear1D,
+            Self::Hierarchical2D,
+            Self::Bilinear2D,
+        ].into_iter().find(|a| *a as u8 == tag)
+    }
+}
+
+// weights that can be stored, usize isn't as its archived size depends on rkyv features
+pub trait StorableWeight: rkyv::Archive {
+    const TYPE: WeightType;
+}
+
+impl StorableWeight for f32 { const TYPE: WeightType = WeightType::F32; }
+impl StorableWeight for f64 { const TYPE: WeightType = WeightType::F64; }
+impl StorableWeight for u32 { const TYPE: WeightType = WeightType::U32; }
+impl StorableWeight for u64 { const TYPE: WeightType = WeightType::U64; }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u32,
+    pub weight_type: WeightType,
+    pub algorithm: Algorithm,
+    pub adapter2d: bool,
+    pub dimensions: [u64; 2],
+    pub payload_len: u64,
+    pub checksum: u64,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[0..8].copy_from_slice(&MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12] = self.weight_type as u8;
+        bytes[13] = self.algorithm as u8;
+        bytes[14] = self.adapter2d as u8;
+        bytes[16..24].copy_from_slice(&self.dimensions[0].to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.dimensions[1].to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[40..48].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    // only the header itself, the payload is checked by access_container
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContainerError> {
+        let bytes: &[u8; HEADER_SIZE] = bytes.get(..HEADER_SIZE).ok_or(ContainerError::Truncated)?.try_into().unwrap();
+        let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        if bytes[0..8] != MAGIC {
+            return Err(ContainerError::Magic);
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        // versions start at 1, so 0 is corruption
+        if version == 0 || version > VERSION {
+            return Err(ContainerError::Version(version));
+        }
+
+        Ok(Self {
+            version,
+            // unknown tags can only come from a newer version, or corruption
+            weight_type: WeightType::from_tag(bytes[12]).ok_or(ContainerError::Invalid)?,
+            algorithm: Algorithm::from_tag(bytes[13]).ok_or(ContainerError::Invalid)?,
+            adapter2d: bytes[14] & 1 != 0,
+            dimensions: [u64_at(16), u64_at(24)],
+            payload_len: u64_at(32),
+            checksum: u64_at(40),
+        })
+    }
+}
+
+// 64 bit FNV-1a, but over 8 byte little endian words rather than bytes, with the last zero padded
+// fast enough to not matter next to validating the payload
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        hash = (hash ^ u64::from_le_bytes(word.try_into().unwrap())).wrapping_mul(0x100000001b3);
+    }
+    if !words.remainder().is_empty() {
+        let mut last = [0; 8];
+        last[..words.remainder().len()].copy_from_slice(words.remainder());
+        hash = (hash ^ u64::from_le_bytes(last)).wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// header and payload, checked for everything but the payload's contents
+fn checked_payload<S: Storable>(bytes: &[u8]) -> Result<(Header, &[u8]), ContainerError> {
+    let header = Header::from_bytes(bytes)?;
+    if header.weight_type != <S::Weight as StorableWeight>::TYPE
+        || header.algorithm != S::ALGORITHM
+        || header.adapter2d != S::ADAPTER2D {
+        return Err(ContainerError::Mismatch);
+    }
+
+    let payload_len = usize::try_from(header.payload_len).map_err(|_| ContainerError::Truncated)?;
+    let payload = bytes.get(HEADER_SIZE..).and_then(|rest| rest.get(..payload_len)).ok_or(ContainerError::Truncated)?;
+    if !(payload.as_ptr() as usize).is_multiple_of(PAYLOAD_ALIGN) {
+        return Err(ContainerError::Misaligned);
+    }
+    if checksum(payload) != header.checksum {
+        return Err(ContainerError::Checksum);
+    }
+    Ok((header, payload))
+}
+
+// distributions that can be stored in a container
+pub trait Storable: rkyv::Archive + Sized {
+    type Weight: StorableWeight;
+    const ALGORITHM: Algorithm;
+    // Adapter2D with a table of ALGORITHM per row
+    const ADAPTER2D: bool = false;
+
+    // container version that introduced the current payload layout
+    const LAYOUT_VERSION: u32 = 1;
+
+    // [width, height], with height 1 for 1D
+    fn dimensions(&self) -> [u64; 2];
+
+    // reads the payload of a container older than LAYOUT_VERSION, already checked against the header
+    fn upgrade(version: u32, _payload: &[u8]) -> Result<Self, ContainerError> {
+        Err(ContainerError::Outdated(version))
+    }
+
+    fn to_container(&self) -> Result<AlignedVec<PAYLOAD_ALIGN>, ContainerError>
+        where Self: for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+    {
+        let payload = rkyv::to_bytes::<rancor::Error>(self).map_err(|_| ContainerError::Invalid)?;
+        let header = Header {
+            version: VERSION,
+            weight_type: <Self::Weight as StorableWeight>::TYPE,
+            algorithm: Self::ALGORITHM,
+            adapter2d: Self::ADAPTER2D,
+            dimensions: self.dimensions(),
+            payload_len: payload.len() as u64,
+            checksum: checksum(&payload),
+        };
+
+        let mut bytes = AlignedVec::with_capacity(HEADER_SIZE + payload.len());
+        bytes.extend_from_slice(&header.to_bytes());
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    // validated access in place, e.g. to a memory mapped file, bytes have to be PAYLOAD_ALIGN aligned
+    fn access_container(bytes: &[u8]) -> Result<&Self::Archived, ContainerError>
+        where Self::Archived: Portable + ArchivedValidate + for<'a> CheckBytes<HighValidator<'a, rancor::Error>>,
+    {
+        let (header, payload) = checked_payload::<Self>(bytes)?;
+        if header.version < Self::LAYOUT_VERSION {
+            return Err(ContainerError::Outdated(header.version));
+        }
+
+        access_checked::<Self::Archived>(payload).map_err(|_| ContainerError::Invalid)
+    }
+
+    // copies bytes if they aren't aligned, so any bytes work
+    fn from_container(bytes: &[u8]) -> Result<Self, ContainerError>
+        where Self::Archived: Portable + ArchivedValidate + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> + rkyv::Deserialize<Self, HighDeserializer<rancor::Error>>,
+    {
+        let aligned;
+        let bytes = if (bytes.as_ptr() as usize).is_multiple_of(PAYLOAD_ALIGN) {
+            bytes
+        } else {
+            let mut copy = AlignedVec::<PAYLOAD_ALIGN>::with_capacity(bytes.len());
+            copy.extend_from_slice(bytes);
+            aligned = copy;
+            aligned.as_slice()
+        };
+
+        let (header, payload) = checked_payload::<Self>(bytes)?;
+        let dist = if header.version < Self::LAYOUT_VERSION {
+            Self::upgrade(header.version, payload)?
+        } else {
+            // same checks as access_container, a deserialized table is sampled without any
+            let archived = access_checked::<Self::Archived>(payload).map_err(|_| ContainerError::Invalid)?;
+            rkyv::deserialize::<Self, rancor::Error>(archived).map_err(|_| ContainerError::Invalid)?
+        };
+        if dist.dimensions() != header.dimensions {
+            return Err(ContainerError::Mismatch);
+        }
+        Ok(dist)
+    }
+
+    #[cfg(feature = "std")]
+    fn write_to(&self, writer: &mut impl std::io::Write) -> Result<(), ContainerError>
+        where Self: for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+    {
+        writer.write_all(&self.to_container()?)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn read_from(reader: &mut impl std::io::Read) -> Result<Self, ContainerError>
+        where Self::Archived: Portable + ArchivedValidate + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> + rkyv::Deserialize<Self, HighDeserializer<rancor::Error>>,
+    {
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        let payload_len = usize::try_from(Header::from_bytes(&header)?.payload_len).map_err(|_| ContainerError::Truncated)?;
+
+        let mut bytes = AlignedVec::<PAYLOAD_ALIGN>::with_capacity(HEADER_SIZE + payload_len);
+        bytes.extend_from_slice(&header);
+        bytes.resize(HEADER_SIZE + payload_len, 0);
+        reader.read_exact(&mut bytes[HEADER_SIZE..])?;
+        Self::from_container(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data2d::Data2D;
+    use crate::distribution::{
+        Discrete1D,
+        Discrete1DPdf,
+        Discrete2D,
+        Discrete2DPdf,
+    };
+
+    fn weights_1d() -> [f32; 7] {
+        [1.0, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0]
+    }
+
+    fn weights_2d<W: From<u8> + Clone>() -> Data2D<W> {
+        let mut weights = Data2D::new_same(5, 3, W::from(1));
+        weights[[4, 0]] = W::from(30);
+        weights[[2, 1]] = W::from(0);
+        weights
+    }
+
+    fn roundtrip_1d<D>(dist: &D)
+        where D: Storable + Discrete1DPdf<f32> + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+              D::Archived: Portable + ArchivedValidate + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> + rkyv::Deserialize<D, HighDeserializer<rancor::Error>>,
+              <D as Discrete1D<f32>>::Weight: PartialEq + core::fmt::Debug,
+    {
+        let bytes = dist.to_container().unwrap();
+        let header = Header::from_bytes(&bytes).unwrap();
+        assert_eq!(header.dimensions, [dist.size() as u64, 1]);
+        assert_eq!(bytes.len(), HEADER_SIZE + header.payload_len as usize);
+        compare_1d(dist, &D::from_container(&bytes).unwrap());
+    }
+
+    fn roundtrip_2d<D>(dist: &D)
+        where D: Storable + Discrete2DPdf<f32> + for<'a> rkyv::Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+              D::Archived: Portable + ArchivedValidate + for<'a> CheckBytes<HighValidator<'a, rancor::Error>> + rkyv::Deserialize<D, HighDeserializer<rancor::Error>>,
+              <D as Discrete2D<f32>>::Weight: PartialEq + core::fmt::Debug,
+    {
+        let bytes = dist.to_container().unwrap();
+        assert_eq!(Header::from_bytes(&bytes).unwrap().dimensions, [dist.width() as u64, dist.height() as u64]);
+        compare_2d(dist, &D::from_container(&bytes).unwrap());
+    }
+
+    fn compare_1d<D: Discrete1DPdf<f32>>(expected: &D, dist: &D)
+        where D::Weight: PartialEq + core::fmt::Debug,
+    {
+        assert_eq!(dist.integral(), expected.integral());
+        assert_eq!(dist.size(), expected.size());
+        for idx in 0..dist.size() {
+            assert_eq!(dist.pdf(idx), expected.pdf(idx));
+        }
+        for i in 0..1000 {
+            let u = i as f32 / 1000.0;
+            assert_eq!(dist.sample_remap(u), expected.sample_remap(u));
+        }
+    }
+
+    fn compare_2d<D: Discrete2DPdf<f32>>(expected: &D, dist: &D)
+        where D::Weight: PartialEq + core::fmt::Debug,
+    {
+        assert_eq!(dist.integral(), expected.integral());
+        assert_eq!([dist.width(), dist.height()], [expected.width(), expected.height()]);
+        for j in 0..dist.height() {
+            for i in 0..dist.width() {
+                assert_eq!(dist.pdf([i, j]), expected.pdf([i, j]));
+            }
+        }
+        for j in 0..50 {
+            for i in 0..50 {
+                let uv = [i as f32 / 50.0, j as f32 / 50.0];
+                assert_eq!(dist.sample_remap(uv), expected.sample_remap(uv));
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut inversion = <crate::Inversion1D<f32> as Discrete1D<f32>>::build(&weights_1d());
+        inversion.build_guide::<f32>(4);
+        roundtrip_1d(&inversion);
+        roundtrip_1d(&<crate::EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights_1d()));
+        roundtrip_1d(&<crate::Alias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights_1d()));
+        roundtrip_1d(&<crate::ContinuousAlias1DWithPdf<f32> as Discrete1D<f32>>::build(&weights_1d()));
+        roundtrip_1d(&<crate::Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights_1d()));
+        roundtrip_1d(&<crate::PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&weights_1d()));
+
+        roundtrip_2d(&<crate::Inversion2D<f32> as Discrete2D<f32>>::build(&weights_2d()));
+        roundtrip_2d(&<crate::Inversion2D<u32> as Discrete2D<f32>>::build(&weights_2d()));
+        roundtrip_2d(&<crate::Alias2DWithPdf<f64> as Discrete2D<f32>>::build(&weights_2d()));
+        roundtrip_2d(&<crate::Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights_2d()));
+        roundtrip_2d(&<crate::Bilinear2D<f32> as Discrete2D<f32>>::build(&weights_2d()));
+    }
+
+    #[test]
+    fn header() {
+        let dist = <crate::Alias2D<f64> as Discrete2D<f32>>::build(&weights_2d());
+        let bytes = dist.to_container().unwrap();
+        let header = Header::from_bytes(&bytes).unwrap();
+        assert_eq!(header, Header {
+            version: VERSION,
+            weight_type: WeightType::F64,
+            algorithm: Algorithm::Alias1D,
+            adapter2d: true,
+            dimensions: [5, 3],
+            payload_len: (bytes.len() - HEADER_SIZE) as u64,
+            checksum: checksum(&bytes[HEADER_SIZE..]),
+        });
+        assert_eq!(&header.to_bytes()[..], &bytes[..HEADER_SIZE]);
+    }
+
+    #[test]
+    fn invalid() {
+        type Dist = crate::Inversion2D<f32>;
+        let dist = <Dist as Discrete2D<f32>>::build(&weights_2d());
+        let bytes = dist.to_container().unwrap();
+
+        let corrupted = |offset: usize, value: u8| {
+            let mut corrupt = bytes.clone();
+            corrupt[offset] = value;
+            corrupt
+        };
+
+        assert!(matches!(Dist::access_container(&bytes[..HEADER_SIZE - 1]), Err(ContainerError::Truncated)));
+        assert!(matches!(Dist::access_container(&bytes[..bytes.len() - 1]), Err(ContainerError::Truncated)));
+        assert!(matches!(Dist::access_container(&corrupted(0, b'X')), Err(ContainerError::Magic)));
+        assert!(matches!(Dist::access_container(&corrupted(8, VERSION as u8 + 1)), Err(ContainerError::Version(_))));
+        assert!(matches!(Dist::access_container(&corrupted(8, 0)), Err(ContainerError::Version(0))));
+        assert!(matches!(Dist::from_container(&corrupted(8, 0)), Err(ContainerError::Version(0))));
+        assert!(matches!(Dist::access_container(&corrupted(13, 0xff)), Err(ContainerError::Invalid)));
+        assert!(matches!(Dist::access_container(&corrupted(bytes.len() - 1, 0x55)), Err(ContainerError::Checksum)));
+
+        // different algorithm, weights, or not an Adapter2D
+        assert!(matches!(crate::Alias2D::<f32>::access_container(&bytes), Err(ContainerError::Mismatch)));
+        assert!(matches!(crate::Inversion2D::<f64>::access_container(&bytes), Err(ContainerError::Mismatch)));
+        assert!(matches!(crate::Inversion1D::<f32>::access_container(&bytes), Err(ContainerError::Mismatch)));
+        assert!(matches!(Dist::from_container(&corrupted(16, 4)), Err(ContainerError::Mismatch)));
+
+        // in place access needs alignment, but copying doesn't
+        let mut offset = AlignedVec::<PAYLOAD_ALIGN>::new();
+        offset.push(0);
+        offset.extend_from_slice(&bytes);
+        assert!(matches!(Dist::access_container(&offset[1..]), Err(ContainerError::Misaligned)));
+        compare_2d(&dist, &Dist::from_container(&offset[1..]).unwrap());
+
+        // payload that passes the checksum still has to be valid, cdf length is near the end
+        let mut corrupt = bytes.clone();
+        let len = corrupt.len();
+        corrupt[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        let header = Header { checksum: checksum(&corrupt[HEADER_SIZE..]), ..Header::from_bytes(&corrupt).unwrap() };
+        corrupt[..HEADER_SIZE].copy_from_slice(&header.to_bytes());
+        assert!(matches!(Dist::access_container(&corrupt), Err(ContainerError::Invalid)));
+
+        // well formed, with a correct checksum, but not a valid table
+        let mut broken = <Dist as Discrete2D<f32>>::build(&weights_2d());
+        broken.conditional[0].cdf.reverse();
+        let broken = broken.to_container().unwrap();
+        assert!(matches!(Dist::access_container(&broken), Err(ContainerError::Invalid)));
+        assert!(matches!(Dist::from_container(&broken), Err(ContainerError::Invalid)));
+    }
+
+    #[test]
+    fn archived() {
+        let dist = <crate::Alias2DWithPdf<f32> as Discrete2D<f32>>::build(&weights_2d());
+        let bytes = dist.to_container().unwrap();
+        crate::distribution::test_archived_2d(&dist, crate::Alias2DWithPdf::<f32>::access_container(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io() {
+        let dist = <crate::Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights_2d());
+        let mut file = Vec::new();
+        dist.write_to(&mut file).unwrap();
+        compare_2d(&dist, &crate::Hierarchical2D::<f32>::read_from(&mut &file[..]).unwrap());
+        assert!(matches!(crate::Hierarchical2D::<f32>::read_from(&mut &file[..file.len() - 1]), Err(ContainerError::Truncated)));
+    }
+
+    // files written by each version of the format, which have to stay loadable
+    // regenerate for a new version with `cargo test --all-features -- --ignored write_fixtures`,
+    // keeping the old ones
+    fn fixtures() -> [[(&'static str, &'static [u8]); 6]; VERSION as usize] {
+        [
+            [
+                ("inversion_1d_f32.v1", include_bytes!("../tests/fixtures/inversion_1d_f32.v1")),
+                ("alias_1d_f64.v1", include_bytes!("../tests/fixtures/alias_1d_f64.v1")),
+                ("continuous_alias_2d_f32.v1", include_bytes!("../tests/fixtures/continuous_alias_2d_f32.v1")),
+                ("hierarchical_2d_u32.v1", include_bytes!("../tests/fixtures/hierarchical_2d_u32.v1")),
+                ("piecewise_linear_1d_f32.v1", include_bytes!("../tests/fixtures/piecewise_linear_1d_f32.v1")),
+                ("bilinear_2d_f64.v1", include_bytes!("../tests/fixtures/bilinear_2d_f64.v1")),
+            ],
+            [
+                ("inversion_1d_f32.v2", include_bytes!("../tests/fixtures/inversion_1d_f32.v2")),
+                ("alias_1d_f64.v2", include_bytes!("../tests/fixtures/alias_1d_f64.v2")),
+                ("continuous_alias_2d_f32.v2", include_bytes!("../tests/fixtures/continuous_alias_2d_f32.v2")),
+                ("hierarchical_2d_u32.v2", include_bytes!("../tests/fixtures/hierarchical_2d_u32.v2")),
+                ("piecewise_linear_1d_f32.v2", include_bytes!("../tests/fixtures/piecewise_linear_1d_f32.v2")),
+                ("bilinear_2d_f64.v2", include_bytes!("../tests/fixtures/bilinear_2d_f64.v2")),
+            ],
+            [
+                ("inversion_1d_f32.v3", include_bytes!("../tests/fixtures/inversion_1d_f32.v3")),
+                ("alias_1d_f64.v3", include_bytes!("../tests/fixtures/alias_1d_f64.v3")),
+                ("continuous_alias_2d_f32.v3", include_bytes!("../tests/fixtures/continuous_alias_2d_f32.v3")),
+                ("hierarchical_2d_u32.v3", include_bytes!("../tests/fixtures/hierarchical_2d_u32.v3")),
+                ("piecewise_linear_1d_f32.v3", include_bytes!("../tests/fixtures/piecewise_linear_1d_f32.v3")),
+                ("bilinear_2d_f64.v3", include_bytes!("../tests/fixtures/bilinear_2d_f64.v3")),
+            ],
+        ]
+    }
+
+    fn fixture_inversion_1d() -> crate::Inversion1D<f32> {
+        let mut dist = <crate::Inversion1D<f32> as Discrete1D<f32>>::build(&weights_1d());
+        dist.build_guide::<f32>(4);
+        dist
+    }
+
+    fn fixture_alias_1d() -> crate::Alias1DWithPdf<f64> {
+        <crate::Alias1DWithPdf<f64> as Discrete1D<f32>>::build(&weights_1d().map(f64::from))
+    }
+
+    fn fixture_continuous_alias_2d() -> crate::ContinuousAlias2DWithPdf<f32> {
+        <crate::ContinuousAlias2DWithPdf<f32> as Discrete2D<f32>>::build(&weights_2d())
+    }
+
+    fn fixture_hierarchical_2d() -> crate::Hierarchical2D<u32> {
+        <crate::Hierarchical2D<u32> as Discrete2D<f32>>::build(&weights_2d())
+    }
+
+    fn fixture_piecewise_linear_1d() -> crate::PiecewiseLinear1D<f32> {
+        <crate::PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&weights_1d())
+    }
+
+    fn fixture_bilinear_2d() -> crate::Bilinear2D<f64> {
+        <crate::Bilinear2D<f64> as Discrete2D<f32>>::build(&weights_2d())
+    }
+
+    #[test]
+    fn compat() {
+        for files in fixtures() {
+            let [inversion, alias, continuous_alias, hierarchical, linear, bilinear] = files.map(|(_, bytes)| bytes);
+            compare_1d(&fixture_inversion_1d(), &Storable::from_container(inversion).unwrap());
+            compare_1d(&fixture_alias_1d(), &Storable::from_container(alias).unwrap());
+            compare_2d(&fixture_continuous_alias_2d(), &Storable::from_container(continuous_alias).unwrap());
+            compare_2d(&fixture_hierarchical_2d(), &Storable::from_container(hierarchical).unwrap());
+            compare_1d(&fixture_piecewise_linear_1d(), &Storable::from_container(linear).unwrap());
+            compare_2d(&fixture_bilinear_2d(), &Storable::from_container(bilinear).unwrap());
+        }
+
+        // alias tables were written without a pdf before build kept one, they still load, just not with a pdf
+        let bytes = include_bytes!("../tests/fixtures/alias_1d_f64_no_pdf.v1");
+        let expected = fixture_alias_1d();
+        let dist = crate::Alias1D::<f64>::from_container(bytes).unwrap();
+        assert!(dist.pdf.is_empty());
+        assert_eq!((dist.weight_sum, dist.entries.len()), (expected.table().weight_sum, expected.table().entries.len()));
+        for i in 0..1000 {
+            let u = i as f32 / 1000.0;
+            assert_eq!(dist.sample_remap(u), expected.sample_remap(u));
+        }
+        assert!(matches!(crate::Alias1DWithPdf::<f64>::from_container(bytes), Err(ContainerError::Invalid)));
+    }
+
+    #[test]
+    fn outdated() {
+        // v1 hierarchical is a list of levels, and v2 padded to a square, which only load by upgrading
+        let [v1, v2, v3] = fixtures().map(|files| files[3].1);
+        for (version, bytes) in [(1, v1), (2, v2)] {
+            let mut aligned = AlignedVec::<PAYLOAD_ALIGN>::new();
+            aligned.extend_from_slice(bytes);
+            assert!(matches!(crate::Hierarchical2D::<u32>::access_container(&aligned), Err(ContainerError::Outdated(v)) if v == version));
+            assert!(crate::Hierarchical2D::<u32>::from_container(bytes).is_ok());
+        }
+
+        let mut aligned = AlignedVec::<PAYLOAD_ALIGN>::new();
+        aligned.extend_from_slice(v3);
+        crate::distribution::test_archived_2d(&fixture_hierarchical_2d(), crate::Hierarchical2D::<u32>::access_container(&aligned).unwrap());
+
+        // other layouts haven't changed, so are still accessible in place
+        let mut aligned = AlignedVec::<PAYLOAD_ALIGN>::new();
+        aligned.extend_from_slice(fixtures()[0][0].1);
+        crate::distribution::test_archived_1d(&fixture_inversion_1d(), crate::Inversion1D::<f32>::access_container(&aligned).unwrap());
+    }
+
+    #[test]
+    #[ignore]
+    fn write_fixtures() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            fixture_inversion_1d().to_container().unwrap(),
+            fixture_alias_1d().to_container().unwrap(),
+            fixture_continuous_alias_2d().to_container().unwrap(),
+            fixture_hierarchical_2d().to_container().unwrap(),
+            fixture_piecewise_linear_1d().to_container().unwrap(),
+            fixture_bilinear_2d().to_container().unwrap(),
+        ];
+        for ((name, _), bytes) in fixtures()[VERSION as usize - 1].iter().zip(files) {
+            std::fs::write(dir.join(name), &bytes).unwrap();
+        }
+    }
+}
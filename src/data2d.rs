@@ -6,11 +6,48 @@ use alloc::{
 };
 
 #[derive(Clone)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(try_from = "Data2DFields<T>"))]
 pub struct Data2D<T> {
     buffer: Box<[T]>,
     width: usize,
 }
 
+// as serialized, checked before becoming a Data2D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Data2DFields<T> {
+    buffer: Box<[T]>,
+    width: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<Data2DFields<T>> for Data2D<T> {
+    type Error = &'static str;
+
+    fn try_from(Data2DFields { buffer, width }: Data2DFields<T>) -> Result<Self, Self::Error> {
+        check_buffer(buffer.len(), width)?;
+        Ok(Self { buffer, width })
+    }
+}
+
+// shared by serde and ArchivedData2D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_buffer(len: usize, width: usize) -> Result<(), &'static str> {
+    // zero width means empty buffer, same as Default
+    if len.checked_rem(width).unwrap_or(len) != 0 {
+        return Err("Data2D buffer length isn't a multiple of width");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: rkyv::Archive> crate::distribution::ArchivedValidate for ArchivedData2D<T> {
+    fn validate(&self) -> Result<(), &'static str> {
+        check_buffer(self.buffer.len(), self.width())
+    }
+}
+
 impl<T: core::fmt::Debug> core::fmt::Debug for Data2D<T> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_list().entries(self.iter()).finish()
@@ -59,15 +96,16 @@ impl<T> Data2D<T> {
     }
 
     pub fn height(&self) -> usize {
-        self.buffer.len() / self.width
+        self.buffer.len().checked_div(self.width).unwrap_or(0)
     }
 
+    // zero width means empty buffer, so chunk size doesn't matter there
     pub fn iter(&self) -> core::slice::ChunksExact<T> {
-        self.buffer.chunks_exact(self.width)
+        self.buffer.chunks_exact(self.width.max(1))
     }
 
     pub fn iter_mut(&mut self) -> core::slice::ChunksExactMut<T> {
-        self.buffer.chunks_exact_mut(self.width)
+        self.buffer.chunks_exact_mut(self.width.max(1))
     }
 
     pub fn get(&self, idx: [usize; 2]) -> Option<&T> {
@@ -75,3 +113,42 @@ impl<T> Data2D<T> {
     }
 }
 
+// rows, split across threads
+#[cfg(feature = "rayon")]
+impl<T: Send + Sync> Data2D<T> {
+    pub fn par_iter(&self) -> rayon::slice::ChunksExact<'_, T> {
+        use rayon::slice::ParallelSlice;
+        self.buffer.par_chunks_exact(self.width.max(1))
+    }
+
+    pub fn par_iter_mut(&mut self) -> rayon::slice::ChunksExactMut<'_, T> {
+        use rayon::slice::ParallelSliceMut;
+        self.buffer.par_chunks_exact_mut(self.width.max(1))
+    }
+}
+
+// same accessors on the archived form, for sampling archived distributions
+#[cfg(feature = "rkyv")]
+impl<T: rkyv::Archive> core::ops::Index<[usize; 2]> for ArchivedData2D<T> {
+    type Output = rkyv::Archived<T>;
+
+    fn index(&self, idx: [usize; 2]) -> &Self::Output {
+        &self.buffer[idx[1] * self.width() + idx[0]]
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<T: rkyv::Archive> ArchivedData2D<T> {
+    pub fn width(&self) -> usize {
+        self.width.to_native() as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.buffer.len().checked_div(self.width()).unwrap_or(0)
+    }
+
+    pub fn get(&self, idx: [usize; 2]) -> Option<&rkyv::Archived<T>> {
+        let width = self.width();
+        self.buffer.get().get(idx[1] * width..(idx[1] + 1) * width).and_then(|s| s.get(idx[0]))
+    }
+}
@@ -2,9 +2,53 @@ use crate::data2d::Data2D;
 use num_traits::{
     real::Real,
     Num,
+    Zero,
     AsPrimitive,
 };
 
+// why weights can't be built into a distribution
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    Empty, // no weights, or fewer than needed (two vertices for linear)
+    ZeroIntegral, // all weights are zero
+    InvalidWeight(usize), // weight at (flattened) idx is negative, NaN or infinite
+    TooLarge, // more weights than the implementation can index
+    Overflow, // integral (or a value derived from it) overflows the weight type
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "not enough weights"),
+            Self::ZeroIntegral => write!(f, "all weights are zero"),
+            Self::InvalidWeight(idx) => write!(f, "weight at {} is negative, NaN or infinite", idx),
+            Self::TooLarge => write!(f, "too many weights"),
+            Self::Overflow => write!(f, "integral of weights overflows"),
+        }
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+// random bits, as an alternative to rand [0-1) floats
+// interpreted as fixed point [0-1), so all bits are used, unlike floats
+pub trait RandomBits: Copy {
+    // as 64 bit fixed point
+    fn to_u64(self) -> u64;
+}
+
+impl RandomBits for u32 {
+    fn to_u64(self) -> u64 {
+        (self as u64) << 32
+    }
+}
+
+impl RandomBits for u64 {
+    fn to_u64(self) -> u64 {
+        self
+    }
+}
+
 // 1D piecewise constant distribution
 // sampling functions are discrete
 pub trait Discrete1D<R> {
@@ -13,28 +57,196 @@ pub trait Discrete1D<R> {
     // constructor
     fn build(weights: &[Self::Weight]) -> Self;
 
+    // same as above, but checks weights first rather than panicking or misbehaving
+    fn try_build(weights: &[Self::Weight]) -> Result<Self, BuildError>
+        where Self: Sized,
+              Self::Weight: PartialOrd + Copy,
+    {
+        crate::utils::validate_weights(weights.iter().copied())?;
+        Ok(Self::build(weights))
+    }
+
     // takes in rand [0-1), returns sampled idx
     fn sample(&self, u: R) -> usize;
 
+    // same as above, but takes in random bits
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize;
+
+    // same as sample, but also returns u remapped to [0-1) within the sampled idx
+    // the remapped u is independent of the idx, so can be reused for another decision
+    fn sample_remap(&self, u: R) -> (usize, R);
+
+    // same as sample for each of us, into out, which has to be the same length
+    // some tables sample several at once with SIMD, so this can be faster than a loop
+    fn sample_batch(&self, us: &[R], out: &mut [usize])
+        where R: Copy,
+    {
+        crate::utils::batch(us, out, |u| self.sample(u));
+    }
+
     // sum of all weights
     fn integral(&self) -> Self::Weight;
 
-    // range of sampled idxs, should be len of weights
+    // range of sampled idxs, the len of weights for a step function,
+    // but one less for PiecewiseLinear1D, where weights are vertices and idxs are the segments between them
     fn size(&self) -> usize;
+
+    // all zero weights build fine and have zero integral, which every way of sampling treats the same:
+    // unchecked, it doesn't panic and returns some idx in range, but which is unspecified,
+    // and any remapped u or pdf alongside it is meaningless, possibly NaN
+    // so when that can happen, check with a try_ form, which returns None instead,
+    // or an _or_uniform form, which samples uniformly over all idxs instead
+
+    // None if integral is zero, otherwise same as sample
+    fn try_sample(&self, u: R) -> Option<usize> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample(u))
+        }
+    }
+
+    // same as above, but uniform over all idxs rather than None
+    fn sample_or_uniform(&self, u: R) -> usize
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            crate::utils::scale_unit(u, self.size()).0
+        } else {
+            self.sample(u)
+        }
+    }
+
+    fn try_sample_bits<B: RandomBits>(&self, bits: B) -> Option<usize> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_bits(bits))
+        }
+    }
+
+    fn sample_bits_or_uniform<B: RandomBits>(&self, bits: B) -> usize {
+        if self.integral().is_zero() {
+            crate::utils::scale_bits(bits.to_u64(), self.size()).0
+        } else {
+            self.sample_bits(bits)
+        }
+    }
+
+    fn try_sample_remap(&self, u: R) -> Option<(usize, R)> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_remap(u))
+        }
+    }
+
+    // remapped u is then within the uniformly sampled idx
+    fn sample_remap_or_uniform(&self, u: R) -> (usize, R)
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            crate::utils::scale_unit(u, self.size())
+        } else {
+            self.sample_remap(u)
+        }
+    }
 }
 
 pub trait Discrete1DPdf<R>: Discrete1D<R> {
     // takes in coord, returns unnormalized pdf
     // can normalize by dividing by integral
     fn pdf(&self, u: usize) -> Self::Weight;
+
+    // same as sample, but also returns normalized pdf of sampled idx
+    fn sample_with_pdf(&self, u: R) -> (usize, R);
+
+    // zero integral is the same as for Discrete1D, here the pdf would be 0 / 0
+
+    fn try_sample_with_pdf(&self, u: R) -> Option<(usize, R)> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_with_pdf(u))
+        }
+    }
+
+    // pdf is then that of uniform
+    fn sample_with_pdf_or_uniform(&self, u: R) -> (usize, R)
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+              usize: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            (crate::utils::scale_unit(u, self.size()).0, R::one() / self.size().as_())
+        } else {
+            self.sample_with_pdf(u)
+        }
+    }
 }
 
 pub trait Continuous1D<R>: Discrete1D<R> {
     // takes in rand [0-1), returns sampled [0-1)
     fn sample_continuous(&self, u: R) -> R;
 
+    // same as above, but also returns normalized density at sampled point
+    fn sample_continuous_with_pdf(&self, u: R) -> (R, R);
+
+    // same as sample_continuous for each of us, into out, which has to be the same length
+    fn sample_continuous_batch(&self, us: &[R], out: &mut [R])
+        where R: Copy,
+    {
+        crate::utils::batch(us, out, |u| self.sample_continuous(u));
+    }
+
     // inverse of above
     fn invert_continuous(&self, u: R) -> R;
+
+    // None if integral is zero, otherwise same as sample_continuous
+    fn try_sample_continuous(&self, u: R) -> Option<R> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_continuous(u))
+        }
+    }
+
+    // same as above, but uniform over [0-1) rather than None
+    fn sample_continuous_or_uniform(&self, u: R) -> R
+        where R: Real,
+    {
+        if self.integral().is_zero() {
+            crate::utils::clamp_unit(u)
+        } else {
+            self.sample_continuous(u)
+        }
+    }
+
+    fn try_sample_continuous_with_pdf(&self, u: R) -> Option<(R, R)> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_continuous_with_pdf(u))
+        }
+    }
+
+    // density of uniform is one
+    fn sample_continuous_with_pdf_or_uniform(&self, u: R) -> (R, R)
+        where R: Real,
+    {
+        if self.integral().is_zero() {
+            (crate::utils::clamp_unit(u), R::one())
+        } else {
+            self.sample_continuous_with_pdf(u)
+        }
+    }
+}
+
+pub trait ContinuousPdf1D<R>: Continuous1D<R> {
+    // takes in coord [0-1), returns normalized density of sample_continuous there
+    fn pdf_continuous(&self, x: R) -> R;
 }
 
 // 2D piecewise constant distribution
@@ -44,15 +256,97 @@ pub trait Discrete2D<R> {
     // constructor
     fn build(weights: &Data2D<Self::Weight>) -> Self;
 
+    // same as above, but checks weights first rather than panicking or misbehaving
+    fn try_build(weights: &Data2D<Self::Weight>) -> Result<Self, BuildError>
+        where Self: Sized,
+              Self::Weight: PartialOrd + Copy,
+    {
+        crate::utils::validate_weights(weights.iter().flatten().copied())?;
+        Ok(Self::build(weights))
+    }
+
     // takes in rand [0-1)x[0-1), returns sampled uv coords
     fn sample(&self, uv: [R; 2]) -> [usize; 2];
 
+    // same as above, but takes in random bits
+    fn sample_bits<B: RandomBits>(&self, bits: [B; 2]) -> [usize; 2];
+
+    // same as sample, but also returns uv remapped to [0-1)x[0-1) within the sampled coords
+    fn sample_remap(&self, uv: [R; 2]) -> ([usize; 2], [R; 2]);
+
+    // same as sample for each of uvs, into out, which has to be the same length
+    fn sample_batch(&self, uvs: &[[R; 2]], out: &mut [[usize; 2]])
+        where R: Copy,
+    {
+        crate::utils::batch(uvs, out, |uv| self.sample(uv));
+    }
+
     // sum of all weights
     fn integral(&self) -> Self::Weight;
 
     fn width(&self) -> usize;
     fn height(&self) -> usize;
 
+    // zero integral is the same as for Discrete1D, unchecked it's some coords in range, but check with these
+
+    // None if integral is zero, otherwise same as sample
+    fn try_sample(&self, uv: [R; 2]) -> Option<[usize; 2]> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample(uv))
+        }
+    }
+
+    // same as above, but uniform over all coords rather than None
+    fn sample_or_uniform(&self, [u, v]: [R; 2]) -> [usize; 2]
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            [crate::utils::scale_unit(u, self.width()).0, crate::utils::scale_unit(v, self.height()).0]
+        } else {
+            self.sample([u, v])
+        }
+    }
+
+    fn try_sample_bits<B: RandomBits>(&self, bits: [B; 2]) -> Option<[usize; 2]> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_bits(bits))
+        }
+    }
+
+    fn sample_bits_or_uniform<B: RandomBits>(&self, [u, v]: [B; 2]) -> [usize; 2] {
+        if self.integral().is_zero() {
+            [crate::utils::scale_bits(u.to_u64(), self.width()).0, crate::utils::scale_bits(v.to_u64(), self.height()).0]
+        } else {
+            self.sample_bits([u, v])
+        }
+    }
+
+    fn try_sample_remap(&self, uv: [R; 2]) -> Option<([usize; 2], [R; 2])> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_remap(uv))
+        }
+    }
+
+    // remapped uv is then within the uniformly sampled coords
+    fn sample_remap_or_uniform(&self, [u, v]: [R; 2]) -> ([usize; 2], [R; 2])
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            let [(x, u), (y, v)] = [crate::utils::scale_unit(u, self.width()), crate::utils::scale_unit(v, self.height())];
+            ([x, y], [u, v])
+        } else {
+            self.sample_remap([u, v])
+        }
+    }
+
     // fills demo image with sample_count samples
     fn fill_demo_image(&self, demo: &mut Data2D<[f32; 3]>, rngs: impl Iterator<Item = [R; 2]>) {
         for rng in rngs {
@@ -72,14 +366,157 @@ pub trait Discrete2DPdf<R>: Discrete2D<R> {
     // takes in coord, returns unnormalized pdf
     // can normalize by dividing by integral
     fn pdf(&self, uv: [usize; 2]) -> Self::Weight;
+
+    // same as sample, but also returns normalized pdf of sampled coords
+    fn sample_with_pdf(&self, uv: [R; 2]) -> ([usize; 2], R);
+
+    // same as Discrete1DPdf, for zero integral
+
+    fn try_sample_with_pdf(&self, uv: [R; 2]) -> Option<([usize; 2], R)> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_with_pdf(uv))
+        }
+    }
+
+    fn sample_with_pdf_or_uniform(&self, uv: [R; 2]) -> ([usize; 2], R)
+        where R: Real + AsPrimitive<f64>,
+              f64: AsPrimitive<R>,
+              usize: AsPrimitive<R>,
+    {
+        if self.integral().is_zero() {
+            (self.sample_or_uniform(uv), R::one() / (self.width() * self.height()).as_())
+        } else {
+            self.sample_with_pdf(uv)
+        }
+    }
+}
+
+// 2D distribution that can be updated in place rather than rebuilt
+pub trait Update2D<R>: Discrete2D<R> {
+    // takes in full, already updated weights, only reads [min, max) rect
+    // afterwards, should behave same as if built from weights
+    fn update_rect(&mut self, weights: &Data2D<Self::Weight>, min: [usize; 2], max: [usize; 2]);
+
+    fn update_texel(&mut self, weights: &Data2D<Self::Weight>, idx: [usize; 2]) {
+        self.update_rect(weights, idx, [idx[0] + 1, idx[1] + 1]);
+    }
 }
 
 pub trait Continuous2D<R>: Discrete2D<R> {
     // takes in rand [0-1), returns sampled [0-1)x[0-1)
     fn sample_continuous(&self, uv: [R; 2]) -> [R; 2];
 
+    // same as above, but also returns normalized density at sampled point
+    fn sample_continuous_with_pdf(&self, uv: [R; 2]) -> ([R; 2], R);
+
+    // same as sample_continuous for each of uvs, into out, which has to be the same length
+    fn sample_continuous_batch(&self, uvs: &[[R; 2]], out: &mut [[R; 2]])
+        where R: Copy,
+    {
+        crate::utils::batch(uvs, out, |uv| self.sample_continuous(uv));
+    }
+
     // inverse of above
     fn invert_continuous(&self, uv: [R; 2]) -> [R; 2];
+
+    // None if integral is zero, otherwise same as sample_continuous
+    fn try_sample_continuous(&self, uv: [R; 2]) -> Option<[R; 2]> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_continuous(uv))
+        }
+    }
+
+    // same as above, but uniform over [0-1)x[0-1) rather than None
+    fn sample_continuous_or_uniform(&self, uv: [R; 2]) -> [R; 2]
+        where R: Real,
+    {
+        if self.integral().is_zero() {
+            uv.map(crate::utils::clamp_unit)
+        } else {
+            self.sample_continuous(uv)
+        }
+    }
+
+    fn try_sample_continuous_with_pdf(&self, uv: [R; 2]) -> Option<([R; 2], R)> {
+        if self.integral().is_zero() {
+            None
+        } else {
+            Some(self.sample_continuous_with_pdf(uv))
+        }
+    }
+
+    fn sample_continuous_with_pdf_or_uniform(&self, uv: [R; 2]) -> ([R; 2], R)
+        where R: Real,
+    {
+        if self.integral().is_zero() {
+            (uv.map(crate::utils::clamp_unit), R::one())
+        } else {
+            self.sample_continuous_with_pdf(uv)
+        }
+    }
+}
+
+pub trait ContinuousPdf2D<R>: Continuous2D<R> {
+    // takes in coord [0-1)x[0-1), returns normalized density of sample_continuous there
+    fn pdf_continuous(&self, xy: [R; 2]) -> R;
+}
+
+// sampling directly on rkyv archived distributions, e.g. in a memory mapped file, without deserializing
+// same as Discrete1D, but with no build, as archived data can't be built, only accessed
+#[cfg(feature = "rkyv")]
+pub trait ArchivedDiscrete1D<R> {
+    type Weight;
+
+    fn sample(&self, u: R) -> usize;
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize;
+    fn sample_remap(&self, u: R) -> (usize, R);
+    fn integral(&self) -> Self::Weight;
+    fn size(&self) -> usize;
+}
+
+#[cfg(feature = "rkyv")]
+pub trait ArchivedDiscrete1DPdf<R>: ArchivedDiscrete1D<R> {
+    fn pdf(&self, u: usize) -> Self::Weight;
+    fn sample_with_pdf(&self, u: R) -> (usize, R);
+}
+
+#[cfg(feature = "rkyv")]
+pub trait ArchivedDiscrete2D<R> {
+    type Weight;
+
+    fn sample(&self, uv: [R; 2]) -> [usize; 2];
+    fn sample_bits<B: RandomBits>(&self, bits: [B; 2]) -> [usize; 2];
+    fn sample_remap(&self, uv: [R; 2]) -> ([usize; 2], [R; 2]);
+    fn integral(&self) -> Self::Weight;
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+}
+
+#[cfg(feature = "rkyv")]
+pub trait ArchivedDiscrete2DPdf<R>: ArchivedDiscrete2D<R> {
+    fn pdf(&self, uv: [usize; 2]) -> Self::Weight;
+    fn sample_with_pdf(&self, uv: [R; 2]) -> ([usize; 2], R);
+}
+
+// rkyv::access only checks that archived bytes are laid out right, not that they hold a table build could have made,
+// which sampling relies on to stay in bounds, so these are the same checks as deserializing with serde
+#[cfg(feature = "rkyv")]
+pub trait ArchivedValidate {
+    fn validate(&self) -> Result<(), &'static str>;
+}
+
+// rkyv::access, followed by validate, for untrusted bytes
+#[cfg(feature = "rkyv")]
+pub fn access_checked<T>(bytes: &[u8]) -> Result<&T, &'static str>
+    where T: ArchivedValidate + rkyv::Portable + for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>,
+{
+    let archived = rkyv::access::<T, rkyv::rancor::Error>(bytes).map_err(|_| "archived bytes are malformed")?;
+    archived.validate()?;
+    Ok(archived)
 }
 
 pub fn visualize_warping<D: Continuous2D<R>, R: Real + AsPrimitive<usize> + 'static>(distr: &D, block_count: usize) -> Data2D<[f32; 3]>
@@ -100,85 +537,756 @@ pub fn visualize_warping<D: Continuous2D<R>, R: Real + AsPrimitive<usize> + 'sta
             out[[i, j]] = crate::utils::u64_to_color(tile);
         }
     }
-    out
+    out
+}
+
+#[cfg(test)]
+use {
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    statrs::distribution::{ChiSquared, ContinuousCDF},
+};
+
+#[cfg(test)]
+pub fn chisq_distribution_1d<D: Discrete1D<f64>>(build: impl Fn(&[D::Weight]) -> D, expected: &[D::Weight], sample_count: usize)
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+{
+    chisq_distribution_1d_with(&build(expected), expected, sample_count, |dist, rng| dist.sample(rng.r#gen::<f64>()));
+}
+
+#[cfg(test)]
+pub fn chisq_distribution_1d_bits<D: Discrete1D<f64>, B: RandomBits>(build: impl Fn(&[D::Weight]) -> D, expected: &[D::Weight], sample_count: usize)
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+        rand::distributions::Standard: rand::distributions::Distribution<B>,
+{
+    chisq_distribution_1d_with(&build(expected), expected, sample_count, |dist, rng| dist.sample_bits(rng.r#gen::<B>()));
+}
+
+#[cfg(test)]
+fn chisq_distribution_1d_with<D: Discrete1D<f64>>(dist: &D, expected: &[D::Weight], sample_count: usize, sample: impl Fn(&D, &mut StdRng) -> usize)
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+{
+    let mut observed = vec![0.0f64; expected.len()].into_boxed_slice();
+    let mut hist = vec![0usize; expected.len()].into_boxed_slice();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..sample_count {
+        let idx = sample(dist, &mut rng);
+        hist[idx] += 1;
+    }
+
+    for (weight, obs) in hist.into_iter().zip(observed.iter_mut()) {
+        *obs = ((weight as f64) / (sample_count as f64)) * dist.integral().as_();
+    }
+
+    let mut chsq = 0.0;
+    let dof = expected.len() - 1;
+    for (obs, exp) in observed.into_iter().zip(expected.into_iter()) {
+        let diff = obs - exp.as_();
+        chsq += diff * diff / exp.as_();
+    }
+
+    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq as f64);
+    assert!(pval >= 0.99, "failed chi-squared statistical test, p = {}", pval);
+}
+
+// remapped u should be uniform within each idx, so subdividing each idx by it
+// should give the same weights, just split evenly
+#[cfg(test)]
+pub fn chisq_distribution_1d_remap<D: Discrete1D<f64>>(build: impl Fn(&[D::Weight]) -> D, expected: &[D::Weight], sample_count: usize)
+    where D::Weight: AsPrimitive<f64>,
+{
+    let dist = build(expected);
+    let subdivisions = 4;
+    let mut hist = vec![0usize; expected.len() * subdivisions];
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..sample_count {
+        let (idx, u) = dist.sample_remap(rng.r#gen::<f64>());
+        assert!((0.0..1.0).contains(&u), "remapped {} outside [0-1)", u);
+        hist[idx * subdivisions + (u * subdivisions as f64) as usize] += 1;
+    }
+
+    let integral: f64 = dist.integral().as_();
+    let mut chsq = 0.0;
+    for (i, observed) in hist.into_iter().enumerate() {
+        let weight: f64 = expected[i / subdivisions].as_();
+        let expected = weight / integral / subdivisions as f64 * sample_count as f64;
+        let diff = observed as f64 - expected;
+        chsq += diff * diff / expected;
+    }
+
+    let dof = expected.len() * subdivisions - 1;
+    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq);
+    assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+}
+
+#[cfg(test)]
+pub fn chisq_distribution_2d<D: Discrete2D<f64>>(expected: &Data2D<D::Weight>, sample_count: usize)
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+{
+    chisq_distribution_2d_with::<D>(expected, sample_count, |dist, rng| dist.sample([rng.r#gen::<f64>(), rng.r#gen::<f64>()]));
+}
+
+#[cfg(test)]
+pub fn chisq_distribution_2d_bits<D: Discrete2D<f64>, B: RandomBits>(expected: &Data2D<D::Weight>, sample_count: usize)
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+        rand::distributions::Standard: rand::distributions::Distribution<B>,
+{
+    chisq_distribution_2d_with::<D>(expected, sample_count, |dist, rng| dist.sample_bits([rng.r#gen::<B>(), rng.r#gen::<B>()]));
+}
+
+#[cfg(test)]
+fn chisq_distribution_2d_with<D: Discrete2D<f64>>(expected: &Data2D<D::Weight>, sample_count: usize, sample: impl Fn(&D, &mut StdRng) -> [usize; 2])
+    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
+        f64: AsPrimitive<D::Weight>,
+{
+    let dist = D::build(expected);
+    let mut observed = Data2D::new_same(expected.width(), expected.height(), 0.0f64);
+    let mut hist = Data2D::new_same(expected.width(), expected.height(), 0usize);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..sample_count {
+        let idx = sample(&dist, &mut rng);
+        hist[idx] += 1;
+    }
+
+    for (weight, obs) in hist.iter().flatten().zip(observed.iter_mut().flatten()) {
+        *obs = ((*weight as f64) / (sample_count as f64)) * dist.integral().as_();
+    }
+
+    let mut chsq = 0.0;
+    let dof = expected.width() * expected.height() - 1;
+    for (obs, exp) in observed.iter().flatten().zip(expected.iter().flatten()) {
+        let diff = obs - exp.as_();
+        chsq += diff * diff / exp.as_();
+    }
+
+    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq as f64);
+    assert!(pval >= 0.99, "failed chi-squared statistical test, p = {}", pval);
+}
+
+#[cfg(test)]
+pub fn chisq_distribution_2d_remap<D: Discrete2D<f64>>(expected: &Data2D<D::Weight>, sample_count: usize)
+    where D::Weight: AsPrimitive<f64>,
+{
+    let dist = D::build(expected);
+    let subdivisions = 4;
+    let width = expected.width() * subdivisions;
+    let height = expected.height() * subdivisions;
+    let mut hist = Data2D::new_same(width, height, 0usize);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..sample_count {
+        let (idx, uv) = dist.sample_remap([rng.r#gen::<f64>(), rng.r#gen::<f64>()]);
+        assert!(uv.iter().all(|u| (0.0..1.0).contains(u)), "remapped {:?} outside [0-1)", uv);
+        hist[[
+            idx[0] * subdivisions + (uv[0] * subdivisions as f64) as usize,
+            idx[1] * subdivisions + (uv[1] * subdivisions as f64) as usize,
+        ]] += 1;
+    }
+
+    let integral: f64 = dist.integral().as_();
+    let mut chsq = 0.0;
+    for j in 0..height {
+        for i in 0..width {
+            let weight: f64 = expected[[i / subdivisions, j / subdivisions]].as_();
+            let expected = weight / integral / (subdivisions * subdivisions) as f64 * sample_count as f64;
+            let diff = hist[[i, j]] as f64 - expected;
+            chsq += diff * diff / expected;
+        }
+    }
+
+    let dof = width * height - 1;
+    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq);
+    assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+}
+
+// same as chisq_distribution_1d, but on an already built distribution,
+// and also checks pdf and the pdf returned by sample_with_pdf against expected
+#[cfg(test)]
+pub fn chisq_distribution_1d_pdf<D: Discrete1DPdf<f64>>(dist: &D, expected: &[D::Weight], sample_count: usize)
+    where D::Weight: AsPrimitive<f64>,
+{
+    let integral: f64 = expected.iter().map(|weight| weight.as_()).sum();
+    for (i, weight) in expected.iter().enumerate() {
+        let pdf: f64 = dist.pdf(i).as_();
+        assert!((pdf - weight.as_()).abs() <= integral * 1e-6, "{} pdf not equal to {} expected", pdf, weight.as_());
+    }
+
+    let mut hist = vec![0usize; expected.len()];
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..sample_count {
+        let (idx, pdf) = dist.sample_with_pdf(rng.r#gen::<f64>());
+        let weight: f64 = expected[idx].as_();
+        assert!((pdf - weight / integral).abs() <= 1e-6, "{} pdf not equal to {} expected", pdf, weight / integral);
+        hist[idx] += 1;
+    }
+
+    let mut chsq = 0.0;
+    let mut dof = 0;
+    for (observed, weight) in hist.into_iter().zip(expected.iter()) {
+        let expected = weight.as_() / integral * sample_count as f64;
+        if expected == 0.0 {
+            assert_eq!(observed, 0);
+            continue;
+        }
+        let diff = observed as f64 - expected;
+        chsq += diff * diff / expected;
+        dof += 1;
+    }
+
+    let pval = 1.0 - ChiSquared::new((dof - 1) as f64).unwrap().cdf(chsq);
+    assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+}
+
+#[cfg(test)]
+pub fn chisq_distribution_2d_pdf<D: Discrete2DPdf<f64>>(dist: &D, expected: &Data2D<D::Weight>, sample_count: usize)
+    where D::Weight: AsPrimitive<f64>,
+{
+    let integral: f64 = expected.iter().flatten().map(|weight| weight.as_()).sum();
+    for j in 0..expected.height() {
+        for i in 0..expected.width() {
+            let pdf: f64 = dist.pdf([i, j]).as_();
+            let weight: f64 = expected[[i, j]].as_();
+            assert!((pdf - weight).abs() <= integral * 1e-6, "{} pdf not equal to {} expected", pdf, weight);
+        }
+    }
+
+    let mut hist = Data2D::new_same(expected.width(), expected.height(), 0usize);
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..sample_count {
+        let (idx, pdf) = dist.sample_with_pdf([rng.r#gen::<f64>(), rng.r#gen::<f64>()]);
+        let weight: f64 = expected[idx].as_();
+        assert!((pdf - weight / integral).abs() <= 1e-6, "{} pdf not equal to {} expected", pdf, weight / integral);
+        hist[idx] += 1;
+    }
+
+    let mut chsq = 0.0;
+    let mut dof = 0;
+    for (observed, weight) in hist.iter().flatten().zip(expected.iter().flatten()) {
+        let expected = weight.as_() / integral * sample_count as f64;
+        if expected == 0.0 {
+            assert_eq!(*observed, 0);
+            continue;
+        }
+        let diff = *observed as f64 - expected;
+        chsq += diff * diff / expected;
+        dof += 1;
+    }
+
+    let pval = 1.0 - ChiSquared::new((dof - 1) as f64).unwrap().cdf(chsq);
+    assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+}
+
+// failure modes shared by all implementations
+#[cfg(test)]
+pub fn test_try_build_1d<D: Discrete1D<f32, Weight = f32>>() {
+    assert_eq!(D::try_build(&[]).err(), Some(BuildError::Empty));
+    assert_eq!(D::try_build(&[0.0; 3]).err(), Some(BuildError::ZeroIntegral));
+    assert_eq!(D::try_build(&[1.0, -1.0]).err(), Some(BuildError::InvalidWeight(1)));
+    assert_eq!(D::try_build(&[1.0, 2.0, f32::NAN]).err(), Some(BuildError::InvalidWeight(2)));
+    assert_eq!(D::try_build(&[f32::INFINITY, 1.0]).err(), Some(BuildError::InvalidWeight(0)));
+    assert_eq!(D::try_build(&[f32::MAX, f32::MAX]).err(), Some(BuildError::Overflow));
+
+    let dist = D::try_build(&[0.0, 1.0, 0.0]).unwrap();
+    assert_eq!(dist.sample(0.0), 1);
+    assert_eq!(dist.sample(0.99), 1);
+}
+
+#[cfg(test)]
+pub fn test_try_build_2d<D: Discrete2D<f32, Weight = f32>>() {
+    assert_eq!(D::try_build(&Data2D::default()).err(), Some(BuildError::Empty));
+    assert_eq!(D::try_build(&Data2D::new_same(0, 3, 1.0)).err(), Some(BuildError::Empty));
+    assert_eq!(D::try_build(&Data2D::new_same(3, 0, 1.0)).err(), Some(BuildError::Empty));
+    assert_eq!(D::try_build(&Data2D::new_same(3, 2, 0.0)).err(), Some(BuildError::ZeroIntegral));
+
+    let mut weights = Data2D::new_same(3, 2, 1.0);
+    weights[[1, 1]] = -1.0;
+    assert_eq!(D::try_build(&weights).err(), Some(BuildError::InvalidWeight(4)));
+    weights[[1, 1]] = f32::NAN;
+    assert_eq!(D::try_build(&weights).err(), Some(BuildError::InvalidWeight(4)));
+    weights[[1, 1]] = f32::MAX;
+    weights[[2, 1]] = f32::MAX;
+    assert_eq!(D::try_build(&weights).err(), Some(BuildError::Overflow));
+
+    // a zero row is fine as long as something else isn't
+    let mut weights = Data2D::new_same(3, 3, 0.0);
+    weights[[1, 2]] = 1.0;
+    let dist = D::try_build(&weights).unwrap();
+    assert_eq!(dist.sample([0.0, 0.0])[1], dist.height() - 1);
+    assert_eq!(dist.sample([0.99, 0.99])[1], dist.height() - 1);
+}
+
+// rands that tend to round up or underflow once scaled,
+// 1.0 itself isn't a valid rand, but shouldn't go out of bounds either
+#[cfg(test)]
+pub fn edge_rands<R: Real>() -> [R; 8] {
+    let two = R::one() + R::one();
+    [
+        R::zero(),
+        R::min_positive_value() / (two * two), // denormal
+        R::min_positive_value(),
+        R::epsilon(),
+        R::one() / two,
+        R::one() - R::epsilon(),
+        R::one() - R::epsilon() / two, // largest below 1.0
+        R::one(),
+    ]
+}
+
+// weights with zeros at both ends, so rounding up or down can land on a zero weight
+#[cfg(test)]
+pub fn edge_weights<R: Real + 'static>() -> [Vec<R>; 5]
+    where f64: AsPrimitive<R>,
+{
+    let w = |ws: &[f64]| ws.iter().map(|w| w.as_()).collect::<Vec<R>>();
+    [
+        w(&[1.0]),
+        w(&[1.0, 1.0, 1.0, 1.0, 1.0]),
+        w(&[0.0, 0.0, 1.0, 2.0, 0.0, 0.0]),
+        w(&[3.0, 0.0, 0.0, 0.0, 1e-6]),
+        w(&[1e-6, 0.0, 5.0, 1e-6, 0.0]),
+    ]
+}
+
+// weight of sampled idx, which is a segment between two weights for piecewise linear
+#[cfg(test)]
+fn edge_weight<R: Real>(weights: &[R], size: usize, idx: usize) -> R {
+    if size == weights.len() {
+        weights[idx]
+    } else {
+        weights[idx] + weights[idx + 1]
+    }
+}
+
+#[cfg(test)]
+fn in_unit<R: Real>(x: R) -> bool {
+    x >= R::zero() && x < R::one()
+}
+
+#[cfg(test)]
+pub fn test_edge_cases_1d<R, D: Discrete1D<R, Weight = R>>(build: impl Fn(&[R]) -> D)
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+{
+    for weights in edge_weights::<R>() {
+        // try_build only to skip what the table can't take, such as too few vertices for piecewise linear
+        if let Err(err) = D::try_build(&weights) {
+            assert_eq!(err, BuildError::Empty);
+            continue;
+        }
+        let dist = build(&weights);
+        for u in edge_rands::<R>() {
+            let idx = dist.sample(u);
+            assert!(idx < dist.size() && edge_weight(&weights, dist.size(), idx) > R::zero(), "{:?} sampled {} for {:?}", weights, idx, u);
+
+            let (remap_idx, remap) = dist.sample_remap(u);
+            assert_eq!(remap_idx, idx, "{:?} remap idx for {:?}", weights, u);
+            assert!(in_unit(remap), "{:?} remapped {:?} to {:?}", weights, u, remap);
+        }
+        for idx in [dist.sample_bits(0u32), dist.sample_bits(u32::MAX), dist.sample_bits(0u64), dist.sample_bits(u64::MAX)] {
+            assert!(idx < dist.size() && edge_weight(&weights, dist.size(), idx) > R::zero(), "{:?} sampled {} for bits", weights, idx);
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_edge_cases_continuous_1d<R, D: ContinuousPdf1D<R, Weight = R>>(build: impl Fn(&[R]) -> D)
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+{
+    for weights in edge_weights::<R>() {
+        if let Err(err) = D::try_build(&weights) {
+            assert_eq!(err, BuildError::Empty);
+            continue;
+        }
+        let dist = build(&weights);
+        for u in edge_rands::<R>() {
+            let x = dist.sample_continuous(u);
+            assert!(in_unit(x), "{:?} sampled {:?} for {:?}", weights, x, u);
+
+            // piecewise linear density can be zero where it starts from a zero vertex
+            let (x_pdf, pdf) = dist.sample_continuous_with_pdf(u);
+            assert_eq!(x_pdf, x);
+            assert!(pdf <= R::max_value() && pdf >= R::zero(), "{:?} pdf {:?} for {:?}", weights, pdf, u);
+
+            let inv = dist.invert_continuous(u);
+            assert!(inv >= R::zero() && inv <= R::one(), "{:?} inverted {:?} to {:?}", weights, u, inv);
+
+            let pdf = dist.pdf_continuous(u);
+            assert!(pdf <= R::max_value() && pdf >= R::zero(), "{:?} pdf {:?} at {:?}", weights, pdf, u);
+
+            // a single weight is uniform, so inverting a sample gets back to u
+            if dist.size() == 1 {
+                let round_trip = dist.invert_continuous(x);
+                assert!((round_trip - u).abs() <= R::epsilon() * 4.0.as_(), "{:?} inverted {:?} sampled from {:?} to {:?}", weights, x, u, round_trip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_edge_cases_2d<R, D: Discrete2D<R, Weight = R>>()
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+{
+    for row_weights in edge_weights::<R>() {
+        // same weights along both axes, as a product
+        let n = row_weights.len();
+        let mut weights = Data2D::new_same(n, n, R::zero());
+        for j in 0..n {
+            for i in 0..n {
+                weights[[i, j]] = row_weights[i] * row_weights[j];
+            }
+        }
+        let dist = match D::try_build(&weights) {
+            Err(BuildError::Empty) => continue,
+            dist => dist.unwrap(),
+        };
+        let supported = |[x, y]: [usize; 2]| {
+            x < dist.width() && y < dist.height()
+                && edge_weight(&row_weights, dist.width(), x) > R::zero()
+                && edge_weight(&row_weights, dist.height(), y) > R::zero()
+        };
+        for v in edge_rands::<R>() {
+            for u in edge_rands::<R>() {
+                let idx = dist.sample([u, v]);
+                assert!(supported(idx), "{:?} sampled {:?} for {:?}", row_weights, idx, [u, v]);
+
+                let (remap_idx, remap) = dist.sample_remap([u, v]);
+                assert_eq!(remap_idx, idx, "{:?} remap idx for {:?}", row_weights, [u, v]);
+                assert!(remap.iter().all(|r| in_unit(*r)), "{:?} remapped {:?} to {:?}", row_weights, [u, v], remap);
+            }
+        }
+        for bits in [[0u64, 0], [u64::MAX, u64::MAX], [0, u64::MAX], [u64::MAX, 0]] {
+            let idx = dist.sample_bits(bits);
+            assert!(supported(idx), "{:?} sampled {:?} for bits", row_weights, idx);
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_edge_cases_continuous_2d<R, D: ContinuousPdf2D<R, Weight = R>>()
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+{
+    for row_weights in edge_weights::<R>() {
+        let n = row_weights.len();
+        let mut weights = Data2D::new_same(n, n, R::zero());
+        for j in 0..n {
+            for i in 0..n {
+                weights[[i, j]] = row_weights[i] * row_weights[j];
+            }
+        }
+        let dist = match D::try_build(&weights) {
+            Err(BuildError::Empty) => continue,
+            dist => dist.unwrap(),
+        };
+        for v in edge_rands::<R>() {
+            for u in edge_rands::<R>() {
+                let xy = dist.sample_continuous([u, v]);
+                assert!(xy.iter().all(|x| in_unit(*x)), "{:?} sampled {:?} for {:?}", row_weights, xy, [u, v]);
+
+                let (xy_pdf, pdf) = dist.sample_continuous_with_pdf([u, v]);
+                assert_eq!(xy_pdf, xy);
+                assert!(pdf <= R::max_value() && pdf >= R::zero(), "{:?} pdf {:?} for {:?}", row_weights, pdf, [u, v]);
+
+                let inv = dist.invert_continuous([u, v]);
+                assert!(inv.iter().all(|x| *x >= R::zero() && *x <= R::one()), "{:?} inverted {:?} to {:?}", row_weights, [u, v], inv);
+
+                let pdf = dist.pdf_continuous([u, v]);
+                assert!(pdf <= R::max_value() && pdf >= R::zero(), "{:?} pdf {:?} at {:?}", row_weights, pdf, [u, v]);
+            }
+        }
+    }
+}
+
+// stratified and edge rands, a few past a multiple of the lanes so that the remainder is covered too
+#[cfg(test)]
+pub fn batch_rands<R: Real + 'static>() -> Vec<R>
+    where usize: AsPrimitive<R>,
+{
+    let n = 1001;
+    (0..n).map(|i| i.as_() / n.as_()).chain(edge_rands()).collect()
+}
+
+#[cfg(test)]
+pub fn batch_weights<R: Real + 'static>() -> Vec<Vec<R>>
+    where f64: AsPrimitive<R>,
+{
+    let increasing = (0..1000).map(|i| ((i % 7) * (i % 3)) as f64).map(|w| w.as_()).collect();
+    edge_weights().into_iter().chain([increasing]).collect()
+}
+
+#[cfg(test)]
+pub fn test_sample_batch_1d<R, D: Discrete1D<R, Weight = R>>(build: impl Fn(&[R]) -> D)
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+          usize: AsPrimitive<R>,
+{
+    let us = batch_rands::<R>();
+    for weights in batch_weights::<R>() {
+        if let Err(err) = D::try_build(&weights) {
+            assert_eq!(err, BuildError::Empty);
+            continue;
+        }
+        let dist = build(&weights);
+        let mut out = vec![0; us.len()];
+        dist.sample_batch(&us, &mut out);
+        for (u, idx) in us.iter().zip(out) {
+            assert_eq!(idx, dist.sample(*u), "{:?} batch sampled differently for {:?}", weights, u);
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_sample_continuous_batch_1d<R, D: Continuous1D<R, Weight = R>>(build: impl Fn(&[R]) -> D)
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+          usize: AsPrimitive<R>,
+{
+    let us = batch_rands::<R>();
+    for weights in batch_weights::<R>() {
+        if let Err(err) = D::try_build(&weights) {
+            assert_eq!(err, BuildError::Empty);
+            continue;
+        }
+        let dist = build(&weights);
+        let mut out = vec![R::zero(); us.len()];
+        dist.sample_continuous_batch(&us, &mut out);
+        for (u, x) in us.iter().zip(out) {
+            assert_eq!(x, dist.sample_continuous(*u), "{:?} batch sampled differently for {:?}", weights, u);
+        }
+    }
+}
+
+#[cfg(test)]
+fn batch_uvs<R: Real + 'static>() -> Vec<[R; 2]>
+    where usize: AsPrimitive<R>,
+{
+    let us = batch_rands::<R>();
+    us.iter().zip(us.iter().rev()).map(|(u, v)| [*u, *v]).collect()
+}
+
+#[cfg(test)]
+fn batch_weights_2d<R: Real + 'static>() -> Data2D<R>
+    where f64: AsPrimitive<R>,
+{
+    let mut weights = Data2D::new_same(37, 23, R::one());
+    for j in 0..23 {
+        for i in 0..37 {
+            weights[[i, j]] = (((i % 5) * (j % 4)) as f64).as_();
+        }
+    }
+    weights
 }
 
 #[cfg(test)]
-use {
-    rand::{rngs::StdRng, Rng, SeedableRng},
-    statrs::distribution::{ChiSquared, ContinuousCDF},
-};
+pub fn test_sample_batch_2d<R, D: Discrete2D<R, Weight = R>>()
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+          usize: AsPrimitive<R>,
+{
+    let uvs = batch_uvs::<R>();
+    let dist = D::build(&batch_weights_2d());
+    let mut out = vec![[0; 2]; uvs.len()];
+    dist.sample_batch(&uvs, &mut out);
+    for (uv, idx) in uvs.iter().zip(out) {
+        assert_eq!(idx, dist.sample(*uv), "batch sampled differently for {:?}", uv);
+    }
+}
 
 #[cfg(test)]
-pub fn chisq_distribution_1d<D: Discrete1D<f64>>(expected: &[D::Weight], sample_count: usize)
-    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
-        f64: AsPrimitive<D::Weight>,
+pub fn test_sample_continuous_batch_2d<R, D: Continuous2D<R, Weight = R>>()
+    where R: Real + core::fmt::Debug + 'static,
+          f64: AsPrimitive<R>,
+          usize: AsPrimitive<R>,
 {
-    let dist = D::build(expected);
-    let mut observed = vec![0.0f64; expected.len()].into_boxed_slice();
-    let mut hist = vec![0usize; expected.len()].into_boxed_slice();
-    let mut rng = StdRng::seed_from_u64(0);
+    let uvs = batch_uvs::<R>();
+    let dist = D::build(&batch_weights_2d());
+    let mut out = vec![[R::zero(); 2]; uvs.len()];
+    dist.sample_continuous_batch(&uvs, &mut out);
+    for (uv, xy) in uvs.iter().zip(out) {
+        assert_eq!(xy, dist.sample_continuous(*uv), "batch sampled differently for {:?}", uv);
+    }
+}
 
-    for _ in 0..sample_count {
-        let idx = dist.sample(rng.r#gen::<f64>());
+// all zero weights build with zero integral, checked sampling never gives an arbitrary idx
+#[cfg(test)]
+pub fn test_zero_integral_1d<D: Discrete1DPdf<f32, Weight = f32>>(build: impl Fn(&[f32]) -> D, n: usize) {
+    let dist = build(&vec![0.0; n]);
+    assert_eq!(dist.integral(), 0.0);
+
+    let sample_count = 1000;
+    let mut hist = vec![0; dist.size()];
+    let mut bits_hist = vec![0; dist.size()];
+    for i in 0..sample_count {
+        let u = (i as f32 + 0.5) / sample_count as f32;
+        let bits = (((2 * i + 1) as u128) << 63) / sample_count as u128;
+        let bits = bits as u64;
+
+        // unchecked is still in range
+        assert!(dist.sample(u) < dist.size());
+        assert!(dist.sample_bits(bits) < dist.size());
+        assert!(dist.sample_remap(u).0 < dist.size());
+        assert!(dist.sample_with_pdf(u).0 < dist.size());
+
+        assert_eq!(dist.try_sample(u), None);
+        assert_eq!(dist.try_sample_bits(bits), None);
+        assert_eq!(dist.try_sample_remap(u), None);
+        assert_eq!(dist.try_sample_with_pdf(u), None);
+
+        let idx = dist.sample_or_uniform(u);
         hist[idx] += 1;
+        bits_hist[dist.sample_bits_or_uniform(bits)] += 1;
+        let (remap_idx, remap) = dist.sample_remap_or_uniform(u);
+        assert!(remap_idx == idx && (0.0..1.0).contains(&remap));
+        assert_eq!(dist.sample_with_pdf_or_uniform(u), (idx, 1.0 / dist.size() as f32));
     }
+    assert!(hist.iter().all(|count| *count == sample_count / dist.size()), "fallback not uniform {:?}", hist);
+    assert_eq!(hist, bits_hist);
+}
 
-    for (weight, obs) in hist.into_iter().zip(observed.iter_mut()) {
-        *obs = ((weight as f64) / (sample_count as f64)) * dist.integral().as_();
-    }
+#[cfg(test)]
+pub fn test_zero_integral_2d<D: Discrete2DPdf<f32, Weight = f32>>(width: usize, height: usize) {
+    let dist = D::build(&Data2D::new_same(width, height, 0.0));
+    assert_eq!(dist.integral(), 0.0);
 
-    let mut chsq = 0.0;
-    let dof = expected.len() - 1;
-    for (obs, exp) in observed.into_iter().zip(expected.into_iter()) {
-        let diff = obs - exp.as_();
-        chsq += diff * diff / exp.as_();
-    }
+    let in_range = |[x, y]: [usize; 2]| x < dist.width() && y < dist.height();
+    let sample_count = 100;
+    let mut hist = Data2D::new_same(dist.width(), dist.height(), 0);
+    let mut bits_hist = Data2D::new_same(dist.width(), dist.height(), 0);
+    for j in 0..sample_count {
+        for i in 0..sample_count {
+            let uv = [(i as f32 + 0.5) / sample_count as f32, (j as f32 + 0.5) / sample_count as f32];
+            let bits = [i, j].map(|k| ((((2 * k + 1) as u128) << 63) / sample_count as u128) as u64);
 
-    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq as f64);
-    assert!(pval >= 0.99, "failed chi-squared statistical test, p = {}", pval);
+            assert!(in_range(dist.sample(uv)));
+            assert!(in_range(dist.sample_bits(bits)));
+            assert!(in_range(dist.sample_remap(uv).0));
+            assert!(in_range(dist.sample_with_pdf(uv).0));
+
+            assert_eq!(dist.try_sample(uv), None);
+            assert_eq!(dist.try_sample_bits(bits), None);
+            assert_eq!(dist.try_sample_remap(uv), None);
+            assert_eq!(dist.try_sample_with_pdf(uv), None);
+
+            let idx = dist.sample_or_uniform(uv);
+            hist[idx] += 1;
+            bits_hist[dist.sample_bits_or_uniform(bits)] += 1;
+            let (remap_idx, remap) = dist.sample_remap_or_uniform(uv);
+            assert!(remap_idx == idx && remap.iter().all(|r| (0.0..1.0).contains(r)));
+            assert_eq!(dist.sample_with_pdf_or_uniform(uv), (idx, 1.0 / (dist.width() * dist.height()) as f32));
+        }
+    }
+    let expected = sample_count * sample_count / (dist.width() * dist.height());
+    assert!(hist.iter().flatten().all(|count| *count == expected), "fallback not uniform");
+    assert!(hist.iter().flatten().eq(bits_hist.iter().flatten()), "bits fallback not uniform");
 }
 
 #[cfg(test)]
-pub fn chisq_distribution_2d<D: Discrete2D<f64>>(expected: &Data2D<D::Weight>, sample_count: usize)
-    where D::Weight: std::fmt::Display + AsPrimitive<f64>,
-        f64: AsPrimitive<D::Weight>,
-{
-    let dist = D::build(expected);
-    let mut observed = Data2D::new_same(expected.width(), expected.height(), 0.0f64);
-    let mut hist = Data2D::new_same(expected.width(), expected.height(), 0usize);
-    let mut rng = StdRng::seed_from_u64(0);
+pub fn test_zero_integral_continuous_1d<D: Continuous1D<f32, Weight = f32>>(build: impl Fn(&[f32]) -> D, n: usize) {
+    let dist = build(&vec![0.0; n]);
 
-    for _ in 0..sample_count {
-        let idx = dist.sample([rng.r#gen::<f64>(), rng.r#gen::<f64>()]);
-        hist[idx] += 1;
+    let sample_count = 1000;
+    for i in 0..sample_count {
+        let u = i as f32 / sample_count as f32;
+        assert_eq!(dist.try_sample_continuous(u), None);
+        assert_eq!(dist.try_sample_continuous_with_pdf(u), None);
+        assert_eq!(dist.sample_continuous_or_uniform(u), u);
+        assert_eq!(dist.sample_continuous_with_pdf_or_uniform(u), (u, 1.0));
     }
+    assert!(dist.sample_continuous_or_uniform(1.0) < 1.0);
+}
 
-    for (weight, obs) in hist.iter().flatten().zip(observed.iter_mut().flatten()) {
-        *obs = ((*weight as f64) / (sample_count as f64)) * dist.integral().as_();
+#[cfg(test)]
+pub fn test_zero_integral_continuous_2d<D: Continuous2D<f32, Weight = f32>>(width: usize, height: usize) {
+    let dist = D::build(&Data2D::new_same(width, height, 0.0));
+
+    let sample_count = 100;
+    for j in 0..sample_count {
+        for i in 0..sample_count {
+            let uv = [i as f32 / sample_count as f32, j as f32 / sample_count as f32];
+            assert_eq!(dist.try_sample_continuous(uv), None);
+            assert_eq!(dist.try_sample_continuous_with_pdf(uv), None);
+            assert_eq!(dist.sample_continuous_or_uniform(uv), uv);
+            assert_eq!(dist.sample_continuous_with_pdf_or_uniform(uv), (uv, 1.0));
+        }
     }
+    assert!(dist.sample_continuous_or_uniform([1.0, 1.0]).iter().all(|x| *x < 1.0));
+}
 
-    let mut chsq = 0.0;
-    let dof = expected.width() * expected.height() - 1;
-    for (obs, exp) in observed.iter().flatten().zip(expected.iter().flatten()) {
-        let diff = obs - exp.as_();
-        chsq += diff * diff / exp.as_();
+#[cfg(test)]
+pub fn test_update_2d<D: Update2D<f32, Weight=f32>>(width: usize, height: usize) {
+    let mut weights = Data2D::new_same(width, height, 1.0);
+    let mut dist = D::build(&weights);
+
+    fn updated<D: Update2D<f32, Weight=f32>>(dist: &D, weights: &Data2D<f32>) {
+        let fresh = D::build(weights);
+        assert!(dist.integral() == fresh.integral());
+        let sample_count = 100;
+        for j in 0..sample_count {
+            for i in 0..sample_count {
+                let uv = [i as f32 / sample_count as f32, j as f32 / sample_count as f32];
+                assert_eq!(dist.sample(uv), fresh.sample(uv));
+            }
+        }
     }
 
-    let pval = 1.0 - ChiSquared::new(dof as f64).unwrap().cdf(chsq as f64);
-    assert!(pval >= 0.99, "failed chi-squared statistical test, p = {}", pval);
+    weights[[0, 0]] = 5.0;
+    dist.update_texel(&weights, [0, 0]);
+    updated(&dist, &weights);
+
+    weights[[width - 1, height - 1]] = 0.0;
+    dist.update_texel(&weights, [width - 1, height - 1]);
+    updated(&dist, &weights);
+
+    for j in 1..height / 2 {
+        for i in 2..width - 1 {
+            weights[[i, j]] = (i * j) as f32;
+        }
+    }
+    dist.update_rect(&weights, [2, 1], [width - 1, height / 2]);
+    updated(&dist, &weights);
 }
 
 #[cfg(test)]
-pub fn test_inv_1d<R: Real + 'static, D: Continuous1D<R>>(weights: &[D::Weight], sample_count: usize)
+pub fn test_inv_1d<R: Real + 'static, D: Continuous1D<R>>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize)
     where R: std::fmt::Display,
           usize: AsPrimitive<R>,
           f64: AsPrimitive<R>,
 {
-    let dist = D::build(&weights);
+    test_inv_1d_offset(build, weights, sample_count, 0.0);
+}
+
+// midpoints, as the continuous alias sample jumps at the edge of each entry,
+// where x just below it and some x elsewhere give the same point, and the inverse can only return one
+#[cfg(test)]
+pub fn test_inv_1d_midpoints<R, D>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize)
+    where R: Real + 'static + std::fmt::Display,
+          D: Continuous1D<R>,
+          usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    test_inv_1d_offset(build, weights, sample_count, 0.5);
+}
+
+#[cfg(test)]
+fn test_inv_1d_offset<R, D>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize, offset: f64)
+    where R: Real + 'static + std::fmt::Display,
+          D: Continuous1D<R>,
+          usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    let dist = build(weights);
 
     for i in 0..sample_count {
-        let x = i.as_() / sample_count.as_();
+        let x = (i.as_() + offset.as_()) / sample_count.as_();
         let y = dist.sample_continuous(x);
         let inv = dist.invert_continuous(y);
         assert!((inv - x).abs() < 0.01f64.as_(), "{} original not equal to {} inverse of sample {}", x, inv, y);
@@ -186,11 +1294,11 @@ pub fn test_inv_1d<R: Real + 'static, D: Continuous1D<R>>(weights: &[D::Weight],
 }
 
 #[cfg(test)]
-pub fn test_continuous_discrete_matching_1d<R: Real, D: Continuous1D<R>>(weights: &[D::Weight], sample_count: usize)
+pub fn test_continuous_discrete_matching_1d<R: Real, D: Continuous1D<R>>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize)
     where R: std::fmt::Display + AsPrimitive<usize>,
           usize: AsPrimitive<R>,
 {
-    let dist = D::build(&weights);
+    let dist = build(weights);
 
     for i in 0..sample_count {
         let input = i.as_() / sample_count.as_();
@@ -200,21 +1308,261 @@ pub fn test_continuous_discrete_matching_1d<R: Real, D: Continuous1D<R>>(weights
     }
 }
 
+#[cfg(test)]
+pub fn test_sample_with_pdf_1d<D: Discrete1DPdf<f64>>(weights: &[D::Weight], sample_count: usize)
+    where D::Weight: AsPrimitive<f64>,
+{
+    let dist = D::build(weights);
+
+    for i in 0..sample_count {
+        let u = i as f64 / sample_count as f64;
+        let (idx, pdf) = dist.sample_with_pdf(u);
+        assert_eq!(idx, dist.sample(u));
+        let expected: f64 = dist.pdf(idx).as_() / dist.integral().as_();
+        assert!((pdf - expected).abs() < 1e-12, "{} pdf not equal to {} expected", pdf, expected);
+    }
+}
+
+// sample_bits should switch from one idx to the next at exactly the bits where its mass starts,
+// given masses that are integers, so that the boundary is known exactly
+#[cfg(test)]
+pub fn test_sample_bits_exact_1d<R, D: Discrete1D<R>>(dist: &D, masses: &[u64]) {
+    let total = masses.iter().sum::<u64>() as u128;
+    let mut below = 0;
+    for (idx, mass) in masses.iter().enumerate().skip(1) {
+        below += masses[idx - 1] as u128;
+        let threshold = (below << 64).div_ceil(total) as u64;
+        assert_eq!(dist.sample_bits(threshold - 1), idx - 1, "bits just before {} of {}", below, total);
+        assert_eq!(dist.sample_bits(threshold), idx, "bits at {} of {}", below, total);
+        assert!(*mass > 0);
+    }
+}
+
+// archived distribution should sample exactly the same as the one it was serialized from
+#[cfg(all(test, feature = "rkyv"))]
+pub fn test_archived_1d<D: Discrete1DPdf<f32>, A: ArchivedDiscrete1DPdf<f32, Weight = D::Weight>>(dist: &D, archived: &A)
+    where D::Weight: PartialEq + core::fmt::Debug,
+{
+    assert_eq!(archived.integral(), dist.integral());
+    assert_eq!(archived.size(), dist.size());
+    for idx in 0..dist.size() {
+        assert_eq!(archived.pdf(idx), dist.pdf(idx));
+    }
+
+    let sample_count = 1000;
+    let rands = (0..sample_count).map(|i| i as f32 / sample_count as f32).chain(edge_rands::<f32>());
+    for u in rands {
+        assert_eq!(archived.sample(u), dist.sample(u));
+        assert_eq!(archived.sample_remap(u), dist.sample_remap(u));
+        assert_eq!(archived.sample_with_pdf(u), dist.sample_with_pdf(u));
+    }
+    for bits in [0, 1 << 31, u32::MAX / 3, u32::MAX] {
+        assert_eq!(archived.sample_bits(bits), dist.sample_bits(bits));
+    }
+}
+
+#[cfg(all(test, feature = "rkyv"))]
+pub fn test_archived_2d<D: Discrete2DPdf<f32>, A: ArchivedDiscrete2DPdf<f32, Weight = D::Weight>>(dist: &D, archived: &A)
+    where D::Weight: PartialEq + core::fmt::Debug,
+{
+    assert_eq!(archived.integral(), dist.integral());
+    assert_eq!([archived.width(), archived.height()], [dist.width(), dist.height()]);
+    for j in 0..dist.height() {
+        for i in 0..dist.width() {
+            assert_eq!(archived.pdf([i, j]), dist.pdf([i, j]));
+        }
+    }
+
+    let sample_count = 50;
+    let rands = (0..sample_count).map(|i| i as f32 / sample_count as f32).chain(edge_rands::<f32>()).collect::<Vec<_>>();
+    for &v in rands.iter() {
+        for &u in rands.iter() {
+            assert_eq!(archived.sample([u, v]), dist.sample([u, v]));
+            assert_eq!(archived.sample_remap([u, v]), dist.sample_remap([u, v]));
+            assert_eq!(archived.sample_with_pdf([u, v]), dist.sample_with_pdf([u, v]));
+        }
+    }
+    for bits in [[0, 0], [1 << 31, u32::MAX / 3], [u32::MAX, u32::MAX]] {
+        assert_eq!(archived.sample_bits(bits), dist.sample_bits(bits));
+    }
+}
+
+// deserialized distribution should sample exactly the same as the one it was serialized from
+#[cfg(all(test, feature = "serde"))]
+pub fn test_serde_1d<D: Discrete1DPdf<f32> + serde::Serialize + serde::de::DeserializeOwned>(dist: &D)
+    where D::Weight: PartialEq + core::fmt::Debug,
+{
+    let json = serde_json::to_string(dist).unwrap();
+    let deserialized: D = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(deserialized.integral(), dist.integral());
+    assert_eq!(deserialized.size(), dist.size());
+    for idx in 0..dist.size() {
+        assert_eq!(deserialized.pdf(idx), dist.pdf(idx));
+    }
+
+    let sample_count = 1000;
+    let rands = (0..sample_count).map(|i| i as f32 / sample_count as f32).chain(edge_rands::<f32>());
+    for u in rands {
+        assert_eq!(deserialized.sample_remap(u), dist.sample_remap(u));
+        assert_eq!(deserialized.sample_with_pdf(u), dist.sample_with_pdf(u));
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+pub fn test_serde_2d<D: Discrete2DPdf<f32> + serde::Serialize + serde::de::DeserializeOwned>(dist: &D)
+    where D::Weight: PartialEq + core::fmt::Debug,
+{
+    let json = serde_json::to_string(dist).unwrap();
+    let deserialized: D = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(deserialized.integral(), dist.integral());
+    assert_eq!([deserialized.width(), deserialized.height()], [dist.width(), dist.height()]);
+    for j in 0..dist.height() {
+        for i in 0..dist.width() {
+            assert_eq!(deserialized.pdf([i, j]), dist.pdf([i, j]));
+        }
+    }
+
+    let sample_count = 50;
+    let rands = (0..sample_count).map(|i| i as f32 / sample_count as f32).chain(edge_rands::<f32>()).collect::<Vec<_>>();
+    for &v in rands.iter() {
+        for &u in rands.iter() {
+            assert_eq!(deserialized.sample_remap([u, v]), dist.sample_remap([u, v]));
+            assert_eq!(deserialized.sample_with_pdf([u, v]), dist.sample_with_pdf([u, v]));
+        }
+    }
+}
+
+// archived tables that are well formed, but not what build makes, get past rkyv::access, but not access_checked
+#[cfg(all(test, feature = "rkyv"))]
+pub fn test_archived_invalid<D>(valid: &D, invalid: &D)
+    where D: for<'a> rkyv::Serialize<rkyv::api::high::HighSerializer<rkyv::util::AlignedVec, rkyv::ser::allocator::ArenaHandle<'a>, rkyv::rancor::Error>>,
+          D::Archived: ArchivedValidate + rkyv::Portable + for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>,
+{
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(valid).unwrap();
+    assert!(access_checked::<D::Archived>(&bytes).is_ok());
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(invalid).unwrap();
+    assert!(rkyv::access::<D::Archived, rkyv::rancor::Error>(&bytes).is_ok());
+    assert!(access_checked::<D::Archived>(&bytes).is_err());
+}
+
+// serialized distribution with its json edited by corrupt should be rejected
+#[cfg(all(test, feature = "serde"))]
+pub fn test_serde_invalid<D: serde::Serialize + serde::de::DeserializeOwned>(dist: &D, corrupt: impl FnOnce(&mut serde_json::Value)) {
+    let mut json = serde_json::to_value(dist).unwrap();
+    assert!(serde_json::from_value::<D>(json.clone()).is_ok());
+    corrupt(&mut json);
+    assert!(serde_json::from_value::<D>(json).is_err());
+}
+
+// sampled point should match sample_continuous, and if the density is that of the samples,
+// averaging f / pdf over stratified samples should integrate f
+#[cfg(test)]
+pub fn test_continuous_with_pdf_1d<D: Continuous1D<f64>>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize) {
+    let dist = build(weights);
+    let mut integrals = [0.0; 2];
+
+    for i in 0..sample_count {
+        let u = (i as f64 + 0.5) / sample_count as f64;
+        let (x, pdf) = dist.sample_continuous_with_pdf(u);
+        assert!((x - dist.sample_continuous(u)).abs() < 1e-9, "{} not equal to {} from sample_continuous", x, dist.sample_continuous(u));
+        integrals[0] += 1.0 / pdf / sample_count as f64;
+        integrals[1] += x / pdf / sample_count as f64;
+    }
+
+    assert!((integrals[0] - 1.0).abs() < 0.01, "integral of 1 is {}", integrals[0]);
+    assert!((integrals[1] - 0.5).abs() < 0.01, "integral of x is {}", integrals[1]);
+}
+
+// same as above, with integrals of 1, x, and y
+#[cfg(test)]
+pub fn test_continuous_with_pdf_2d<D: Continuous2D<f64>>(weights: &Data2D<D::Weight>, sample_count: usize) {
+    let dist = D::build(weights);
+    let mut integrals = [0.0; 3];
+
+    for j in 0..sample_count {
+        for i in 0..sample_count {
+            let uv = [(i as f64 + 0.5) / sample_count as f64, (j as f64 + 0.5) / sample_count as f64];
+            let ([x, y], pdf) = dist.sample_continuous_with_pdf(uv);
+            let [expected_x, expected_y] = dist.sample_continuous(uv);
+            assert!((x - expected_x).abs() < 1e-9 && (y - expected_y).abs() < 1e-9, "{:?} not equal to {:?} from sample_continuous", [x, y], [expected_x, expected_y]);
+            let weight = 1.0 / pdf / (sample_count * sample_count) as f64;
+            integrals[0] += weight;
+            integrals[1] += x * weight;
+            integrals[2] += y * weight;
+        }
+    }
+
+    assert!((integrals[0] - 1.0).abs() < 0.01, "integral of 1 is {}", integrals[0]);
+    assert!((integrals[1] - 0.5).abs() < 0.01, "integral of x is {}", integrals[1]);
+    assert!((integrals[2] - 0.5).abs() < 0.01, "integral of y is {}", integrals[2]);
+}
+
+// density at sampled points should be the same as the density from sampling
+#[cfg(test)]
+pub fn test_pdf_continuous_1d<D: ContinuousPdf1D<f64>>(build: impl Fn(&[D::Weight]) -> D, weights: &[D::Weight], sample_count: usize) {
+    let dist = build(weights);
+
+    for i in 0..sample_count {
+        let u = (i as f64 + 0.5) / sample_count as f64;
+        let (x, pdf) = dist.sample_continuous_with_pdf(u);
+        let pdf_continuous = dist.pdf_continuous(x);
+        assert!((pdf_continuous - pdf).abs() <= pdf * 1e-6, "{} density at {} not equal to {} from sampling", pdf_continuous, x, pdf);
+    }
+}
+
+#[cfg(test)]
+pub fn test_pdf_continuous_2d<D: ContinuousPdf2D<f64>>(weights: &Data2D<D::Weight>, sample_count: usize) {
+    let dist = D::build(weights);
+
+    for j in 0..sample_count {
+        for i in 0..sample_count {
+            let uv = [(i as f64 + 0.5) / sample_count as f64, (j as f64 + 0.5) / sample_count as f64];
+            let (xy, pdf) = dist.sample_continuous_with_pdf(uv);
+            let pdf_continuous = dist.pdf_continuous(xy);
+            assert!((pdf_continuous - pdf).abs() <= pdf * 1e-6, "{} density at {:?} not equal to {} from sampling", pdf_continuous, xy, pdf);
+        }
+    }
+}
+
+// function the shared 1D tests build a table of type dist with, for sampling with r
+// build is the trait's own, anything else is found on the type
+#[cfg(test)]
+macro_rules! build_fn {
+    (build, $dist:ty, $r:ty) => {
+        <$dist as crate::distribution::Discrete1D<$r>>::build
+    };
+    ($build:ident, $dist:ty, $r:ty) => {
+        <$dist>::$build
+    };
+}
+#[cfg(test)]
+pub(crate) use build_fn;
+
 #[cfg(test)]
 macro_rules! distribution_1d_tests {
     ($impl:path) => {
+        crate::distribution::distribution_1d_tests!($impl, build);
+    };
+    // same tests, on tables built with another function of the same signature, such as par_build
+    ($impl:path, $build:ident) => {
         mod distribution_1d {
-            use crate::distribution::chisq_distribution_1d;
+            use crate::distribution::{
+                chisq_distribution_1d,
+                chisq_distribution_1d_bits,
+                chisq_distribution_1d_remap,
+            };
             use $impl as Dist;
 
             #[test]
             fn basic() {
-                chisq_distribution_1d::<Dist<usize>>(&[1, 1, 2, 4, 8], 10_000);
+                chisq_distribution_1d(crate::distribution::build_fn!($build, Dist<usize>, f64), &[1, 1, 2, 4, 8], 10_000);
             }
 
             #[test]
             fn uniform() {
-                chisq_distribution_1d::<Dist<f32>>(&[1.0; 10_000], 1_000_000);
+                chisq_distribution_1d(crate::distribution::build_fn!($build, Dist<f32>, f64), &[1.0; 10_000], 1_000_000);
             }
 
             #[test]
@@ -223,7 +1571,56 @@ macro_rules! distribution_1d_tests {
                 for (i, weight) in distr.iter_mut().enumerate() {
                     *weight = (5 * (i + 1)) as f32;
                 }
-                chisq_distribution_1d::<Dist<f32>>(&distr, 100_000);
+                chisq_distribution_1d(crate::distribution::build_fn!($build, Dist<f32>, f64), &distr, 100_000);
+            }
+
+            #[test]
+            fn bits_u32() {
+                chisq_distribution_1d_bits::<_, u32>(crate::distribution::build_fn!($build, Dist<usize>, f64), &[1, 1, 2, 4, 8], 10_000);
+                chisq_distribution_1d_bits::<_, u32>(crate::distribution::build_fn!($build, Dist<f32>, f64), &[0.5, 3.0, 1.0, 0.25, 0.5], 10_000);
+            }
+
+            #[test]
+            fn bits_u64() {
+                chisq_distribution_1d_bits::<_, u64>(crate::distribution::build_fn!($build, Dist<usize>, f64), &[1, 1, 2, 4, 8], 10_000);
+                chisq_distribution_1d_bits::<_, u64>(crate::distribution::build_fn!($build, Dist<f32>, f64), &[1.0; 10_000], 1_000_000);
+            }
+
+            #[test]
+            fn try_build() {
+                use crate::distribution::{Discrete1D, BuildError};
+                crate::distribution::test_try_build_1d::<Dist<f32>>();
+                assert_eq!(<Dist<usize> as Discrete1D<f32>>::try_build(&[usize::MAX, 1]).err(), Some(BuildError::Overflow));
+                assert_eq!(<Dist<i32> as Discrete1D<f32>>::try_build(&[1, -1]).err(), Some(BuildError::InvalidWeight(1)));
+
+                let single = <Dist<usize> as Discrete1D<f32>>::try_build(&[3]).unwrap();
+                assert_eq!(Discrete1D::<f32>::sample(&single, 0.0), 0);
+                assert_eq!(Discrete1D::<f32>::sample(&single, 0.99), 0);
+                assert_eq!(Discrete1D::<f32>::integral(&single), 3);
+            }
+
+            #[test]
+            fn zero_integral() {
+                crate::distribution::test_zero_integral_1d(crate::distribution::build_fn!($build, Dist<f32>, f32), 5);
+                crate::distribution::test_zero_integral_1d(crate::distribution::build_fn!($build, Dist<f32>, f32), 1);
+            }
+
+            #[test]
+            fn edge_cases() {
+                crate::distribution::test_edge_cases_1d(crate::distribution::build_fn!($build, Dist<f32>, f32));
+                crate::distribution::test_edge_cases_1d(crate::distribution::build_fn!($build, Dist<f64>, f64));
+            }
+
+            #[test]
+            fn remap() {
+                chisq_distribution_1d_remap(crate::distribution::build_fn!($build, Dist<usize>, f64), &[1, 1, 2, 4, 8], 100_000);
+                chisq_distribution_1d_remap(crate::distribution::build_fn!($build, Dist<f32>, f64), &[0.5, 3.0, 1.0, 0.25, 0.5, 7.0], 100_000);
+            }
+
+            #[test]
+            fn batch() {
+                crate::distribution::test_sample_batch_1d(crate::distribution::build_fn!($build, Dist<f32>, f32));
+                crate::distribution::test_sample_batch_1d(crate::distribution::build_fn!($build, Dist<f64>, f64));
             }
         }
     }
@@ -235,7 +1632,11 @@ pub(crate) use distribution_1d_tests;
 macro_rules! distribution_2d_tests {
     ($impl:path) => {
         mod distribution_2d {
-            use crate::distribution::chisq_distribution_2d;
+            use crate::distribution::{
+                chisq_distribution_2d,
+                chisq_distribution_2d_bits,
+                chisq_distribution_2d_remap,
+            };
             use $impl as Dist;
 
             #[test]
@@ -267,6 +1668,78 @@ macro_rules! distribution_2d_tests {
                 }
                 chisq_distribution_2d::<Dist<usize>>(&distr, 100_000);
             }
+
+            #[test]
+            fn bits_u32() {
+                let mut distr = crate::data2d::Data2D::new_same(5, 3, 1);
+                distr[[4, 0]] = 3;
+                distr[[2, 1]] = 2;
+                distr[[1, 2]] = 4;
+                distr[[3, 2]] = 1;
+                chisq_distribution_2d_bits::<Dist<usize>, u32>(&distr, 10_000);
+            }
+
+            #[test]
+            fn bits_u64() {
+                chisq_distribution_2d_bits::<Dist<f32>, u64>(&crate::data2d::Data2D::new_same(100, 100, 1.0), 1_000_000);
+            }
+
+            #[test]
+            fn remap() {
+                let mut distr = crate::data2d::Data2D::new_same(5, 3, 1);
+                distr[[4, 0]] = 3;
+                distr[[2, 1]] = 2;
+                distr[[1, 2]] = 4;
+                chisq_distribution_2d_remap::<Dist<usize>>(&distr, 100_000);
+            }
+
+            #[test]
+            fn try_build() {
+                use crate::distribution::Discrete2D;
+                crate::distribution::test_try_build_2d::<Dist<f32>>();
+
+                let single = <Dist<usize> as Discrete2D<f32>>::try_build(&crate::data2d::Data2D::new_same(1, 1, 3)).unwrap();
+                assert_eq!(Discrete2D::<f32>::sample(&single, [0.0, 0.0]), [0, 0]);
+                assert_eq!(Discrete2D::<f32>::sample(&single, [0.99, 0.99]), [0, 0]);
+                assert_eq!(Discrete2D::<f32>::integral(&single), 3);
+            }
+
+            #[test]
+            fn zero_integral() {
+                crate::distribution::test_zero_integral_2d::<Dist<f32>>(5, 4);
+            }
+
+            #[test]
+            fn edge_cases() {
+                crate::distribution::test_edge_cases_2d::<f32, Dist<f32>>();
+                crate::distribution::test_edge_cases_2d::<f64, Dist<f64>>();
+            }
+
+            #[test]
+            fn batch() {
+                crate::distribution::test_sample_batch_2d::<f32, Dist<f32>>();
+                crate::distribution::test_sample_batch_2d::<f64, Dist<f64>>();
+            }
+
+            #[test]
+            fn with_pdf() {
+                use crate::distribution::{Discrete2D, Discrete2DPdf};
+                let mut distr = crate::data2d::Data2D::new_same(5, 3, 1.0);
+                distr[[4, 0]] = 3.0;
+                distr[[2, 1]] = 0.0;
+                distr[[1, 2]] = 4.0;
+                let dist = <Dist<f64> as Discrete2D<f64>>::build(&distr);
+                let integral = Discrete2D::<f64>::integral(&dist);
+                let sample_count = 100;
+                for j in 0..sample_count {
+                    for i in 0..sample_count {
+                        let uv = [i as f64 / sample_count as f64, j as f64 / sample_count as f64];
+                        let (idx, pdf) = Discrete2DPdf::<f64>::sample_with_pdf(&dist, uv);
+                        assert_eq!(idx, Discrete2D::<f64>::sample(&dist, uv));
+                        assert!((pdf - Discrete2DPdf::<f64>::pdf(&dist, idx) / integral).abs() < 1e-12);
+                    }
+                }
+            }
         }
     }
 }
@@ -276,18 +1749,25 @@ pub(crate) use distribution_2d_tests;
 #[cfg(test)]
 macro_rules! continuous_distribution_1d_tests {
     ($impl:path) => {
+        crate::distribution::continuous_distribution_1d_tests!($impl, build);
+    };
+    ($impl:path, $build:ident) => {
+        crate::distribution::continuous_distribution_1d_tests!($impl, $build, test_inv_1d);
+    };
+    ($impl:path, $build:ident, $test_inv:ident) => {
         mod continuous_distribution_1d {
             use crate::distribution::{
-                Discrete1D,
                 Continuous1D,
-                test_inv_1d,
+                $test_inv as test_inv_1d,
                 test_continuous_discrete_matching_1d,
+                test_continuous_with_pdf_1d,
+                test_pdf_continuous_1d,
             };
             use $impl as Dist;
 
             #[test]
             fn surjective() {
-                let dist = <Dist::<f32> as Discrete1D<f32>>::build(&[1.0; 1_000]);
+                let dist = (crate::distribution::build_fn!($build, Dist<f32>, f32))(&[1.0; 1_000]);
                 let sample_count = 1000;
                 let mut values = Vec::with_capacity(sample_count);
                 for i in 0..sample_count {
@@ -312,7 +1792,7 @@ macro_rules! continuous_distribution_1d_tests {
 
             #[test]
             fn injective() {
-                let dist = <Dist::<f64> as Discrete1D<f64>>::build(&[1.0, 1.0, 2.0, 4.0, 8.0]);
+                let dist = (crate::distribution::build_fn!($build, Dist<f64>, f64))(&[1.0, 1.0, 2.0, 4.0, 8.0]);
                 let sample_count = 1000;
                 let mut values = Vec::with_capacity(sample_count);
                 for i in 0..sample_count {
@@ -332,12 +1812,12 @@ macro_rules! continuous_distribution_1d_tests {
 
             #[test]
             fn inverse_uniform() {
-                test_inv_1d::<f64, Dist<f64>>(&[1.0; 1_000], 1000);
+                test_inv_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0; 1_000], 1000);
             }
 
             #[test]
             fn inverse_basic() {
-                test_inv_1d::<f64, Dist<f64>>(&[1.0, 2.0, 4.0, 8.0], 1000);
+                test_inv_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0, 2.0, 4.0, 8.0], 1000);
             }
 
             #[test]
@@ -346,17 +1826,17 @@ macro_rules! continuous_distribution_1d_tests {
                 for (i, weight) in distr.iter_mut().enumerate() {
                     *weight = (5 * (i + 1)) as f64;
                 }
-                test_inv_1d::<f64, Dist<f64>>(&distr, 1000);
+                test_inv_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &distr, 1000);
             }
 
             #[test]
             fn continuous_discrete_matching_uniform() {
-                test_continuous_discrete_matching_1d::<f64, Dist<f64>>(&[1.0; 1_000], 1024);
+                test_continuous_discrete_matching_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0; 1_000], 1024);
             }
 
             #[test]
             fn continuous_discrete_matching_basic() {
-                test_continuous_discrete_matching_1d::<f64, Dist<f64>>(&[1.0, 1.0, 2.0, 4.0, 8.0], 1024);
+                test_continuous_discrete_matching_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0, 1.0, 2.0, 4.0, 8.0], 1024);
             }
 
             #[test]
@@ -365,7 +1845,52 @@ macro_rules! continuous_distribution_1d_tests {
                 for (i, weight) in distr.iter_mut().enumerate() {
                     *weight = (5 * (i + 1)) as f64;
                 }
-                test_continuous_discrete_matching_1d::<f64, Dist<f64>>(&distr, 1024);
+                test_continuous_discrete_matching_1d::<f64, _>(crate::distribution::build_fn!($build, Dist<f64>, f64), &distr, 1024);
+            }
+
+            #[test]
+            fn with_pdf_basic() {
+                test_continuous_with_pdf_1d(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0, 1.0, 2.0, 4.0, 8.0], 10_000);
+            }
+
+            #[test]
+            fn with_pdf_increasing() {
+                let mut distr = [0.0; 100];
+                for (i, weight) in distr.iter_mut().enumerate() {
+                    *weight = (5 * (i + 1)) as f64;
+                }
+                test_continuous_with_pdf_1d(crate::distribution::build_fn!($build, Dist<f64>, f64), &distr, 10_000);
+            }
+
+            #[test]
+            fn zero_integral() {
+                crate::distribution::test_zero_integral_continuous_1d(crate::distribution::build_fn!($build, Dist<f32>, f32), 5);
+            }
+
+            #[test]
+            fn edge_cases() {
+                crate::distribution::test_edge_cases_continuous_1d(crate::distribution::build_fn!($build, Dist<f32>, f32));
+                crate::distribution::test_edge_cases_continuous_1d(crate::distribution::build_fn!($build, Dist<f64>, f64));
+            }
+
+            #[test]
+            fn batch() {
+                crate::distribution::test_sample_continuous_batch_1d(crate::distribution::build_fn!($build, Dist<f32>, f32));
+                crate::distribution::test_sample_continuous_batch_1d(crate::distribution::build_fn!($build, Dist<f64>, f64));
+            }
+
+            #[test]
+            fn pdf_continuous_basic() {
+                test_pdf_continuous_1d(crate::distribution::build_fn!($build, Dist<f64>, f64), &[1.0, 1.0, 2.0, 4.0, 8.0], 10_000);
+            }
+
+            #[test]
+            fn pdf_continuous_increasing() {
+                let mut distr = [0.0; 100];
+                for (i, weight) in distr.iter_mut().enumerate() {
+                    *weight = (5 * (i + 1)) as f64;
+                }
+                test_pdf_continuous_1d(crate::distribution::build_fn!($build, Dist<f64>, f64), &distr, 10_000);
             }
         }
     }
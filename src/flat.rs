@@ -0,0 +1,297 @@
+// flattened layout of 2D distributions, for upload to a GPU buffer as is,
+// along with a reference sampler that reads only that buffer, to validate shader ports against
+//
+// the buffer is an array of 32 bit words, std430 compatible both as `uint words[]`
+// and as `Header header; uint data[];`, weights are stored as f32 bits
+// all offsets are in words from the start of the buffer, header included
+//
+// header, see Header:
+//
+//   0  algorithm, see Algorithm
+//   1  width
+//   2  height
+//   3  number of levels, Hierarchical2D only, 0 otherwise
+//   4  offset of marginal table, or level directory of Hierarchical2D
+//   5  offset of first row table, Adapter2D only
+//   6  words between row tables, Adapter2D only
+//   7  length of the buffer in words
+//
+// tables of Adapter2D, the marginal one has height entries, the rows width entries:
+//
+//   Inversion2D  cdf, entries + 1 words, starting at zero
+//   Alias2D      weight sum, then select and alias of each entry, 1 + 2 * entries words
+//
+// Hierarchical2D level directory has offset, width and height of each level, coarsest first,
+// each level being width * height words of weights, row major
+//
+// the reference sampler does everything in f32, so that a shader can do the same,
+// except that Alias2D splits u into entry and fraction exactly, as utils::scale_unit does, on u's integer mantissa
+// for f32 weights every algorithm samples exactly the same as the table it's exported from,
+// other weights are rounded to f32 on export, so samples may differ very slightly at boundaries between idxs
+
+use crate::utils;
+use num_traits::AsPrimitive;
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    vec::Vec,
+    vec,
+};
+
+pub const HEADER_WORDS: usize = 8;
+
+// tags, values are part of the layout, so must never change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Algorithm {
+    Inversion2D = 1,
+    Alias2D = 2,
+    Hierarchical2D = 3,
+}
+
+impl Algorithm {
+    fn from_tag(tag: u32) -> Option<Self> {
+        [Self::Inversion2D, Self::Alias2D, Self::Hierarchical2D].into_iter().find(|a| *a as u32 == tag)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Header {
+    pub algorithm: u32,
+    pub width: u32,
+    pub height: u32,
+    pub levels: u32,
+    pub marginal: u32,
+    pub conditional: u32,
+    pub row_stride: u32,
+    pub len: u32,
+}
+
+impl Header {
+    pub fn to_words(&self) -> [u32; HEADER_WORDS] {
+        [self.algorithm, self.width, self.height, self.levels, self.marginal, self.conditional, self.row_stride, self.len]
+    }
+
+    pub fn from_words(words: &[u32]) -> Option<Self> {
+        let [algorithm, width, height, levels, marginal, conditional, row_stride, len] = *words.first_chunk()?;
+        Some(Self { algorithm, width, height, levels, marginal, conditional, row_stride, len })
+    }
+}
+
+pub trait ExportFlat {
+    fn export_flat(&self) -> Vec<u32>;
+}
+
+// 1D tables that can be the rows of a flattened Adapter2D
+pub trait FlatTable1D {
+    const ALGORITHM: Algorithm;
+
+    fn flat_size(&self) -> usize;
+    fn write_flat(&self, buffer: &mut Vec<u32>);
+}
+
+// sizes and offsets have to fit in a word
+pub(crate) fn word(n: usize) -> u32 {
+    u32::try_from(n).expect("distribution too large to flatten")
+}
+
+pub(crate) fn weight_word<W: AsPrimitive<f32>>(weight: W) -> u32 {
+    weight.as_().to_bits()
+}
+
+// buffer with room for the header, which is filled in once the tables are written
+pub(crate) fn new_buffer() -> Vec<u32> {
+    vec![0; HEADER_WORDS]
+}
+
+pub(crate) fn finish_buffer(buffer: &mut [u32], header: Header) {
+    let header = Header { len: word(buffer.len()), ..header };
+    buffer[..HEADER_WORDS].copy_from_slice(&header.to_words());
+}
+
+fn load(buffer: &[u32], idx: usize) -> f32 {
+    f32::from_bits(buffer[idx])
+}
+
+// number of leading entries for which pred holds, as with slice::partition_point
+fn partition_point(buffer: &[u32], start: usize, len: usize, pred: impl Fn(f32) -> bool) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if pred(load(buffer, start + mid)) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+fn sample_cdf(buffer: &[u32], start: usize, size: usize, u: f32) -> usize {
+    let integral = load(buffer, start + size);
+    let point = u * integral;
+
+    // u of 1.0, or rounding up, lands past the end, which should still be the last non-zero weight
+    if point >= integral {
+        return partition_point(buffer, start, size + 1, |p| p < integral).saturating_sub(1);
+    }
+    partition_point(buffer, start, size + 1, |p| p <= point) - 1
+}
+
+fn sample_alias(buffer: &[u32], start: usize, size: usize, u: f32) -> usize {
+    let weight_sum = load(buffer, start);
+    let (index, v) = utils::scale_unit(u, size);
+    let v = v * weight_sum;
+    let entry = start + 1 + 2 * index;
+    if load(buffer, entry) <= v {
+        buffer[entry + 1] as usize
+    } else {
+        index
+    }
+}
+
+fn select_remap(weights: [f32; 2], rand: &mut f32) -> usize {
+    // rand can round up to or past 1, which still shouldn't select a zero weight
+    if weights[1] == 0.0 {
+        return 0;
+    }
+    let new_rand = *rand * (weights[0] + weights[1]);
+    if new_rand < weights[0] {
+        *rand = new_rand / weights[0];
+        0
+    } else {
+        *rand = (new_rand - weights[0]) / weights[1];
+        1
+    }
+}
+
+fn sample_hierarchical(buffer: &[u32], header: &Header, [mut u, mut v]: [f32; 2]) -> [usize; 2] {
+    let directory = header.marginal as usize;
+    let level = |i: usize| -> [usize; 3] { [0, 1, 2].map(|j| buffer[directory + 3 * i + j] as usize) };
+    let mut idx = [0; 2];
+
+    for i in 0..header.levels as usize {
+        let [offset, width, height] = level(i);
+        if i > 0 && width > level(i - 1)[1] { idx[0] *= 2 }
+        if i > 0 && height > level(i - 1)[2] { idx[1] *= 2 }
+
+        let get_or_zero = |x: usize, y: usize| if x < width && y < height { load(buffer, offset + y * width + x) } else { 0.0 };
+        let weights = [
+            [get_or_zero(idx[0], idx[1]), get_or_zero(idx[0], idx[1] + 1)],
+            [get_or_zero(idx[0] + 1, idx[1]), get_or_zero(idx[0] + 1, idx[1] + 1)],
+        ];
+
+        let selected_x = select_remap([weights[0][0] + weights[0][1], weights[1][0] + weights[1][1]], &mut u);
+        let selected_y = select_remap(weights[selected_x], &mut v);
+
+        idx[0] += selected_x;
+        idx[1] += selected_y;
+    }
+    idx
+}
+
+// reference sampler, panics if the buffer isn't a flattened distribution
+pub fn sample(buffer: &[u32], [u, v]: [f32; 2]) -> [usize; 2] {
+    let header = Header::from_words(buffer).expect("flat buffer is missing its header");
+    assert_eq!(header.len as usize, buffer.len(), "flat buffer length doesn't match its header");
+    let [width, height] = [header.width as usize, header.height as usize];
+    let row = |y: usize| header.conditional as usize + y * header.row_stride as usize;
+
+    match Algorithm::from_tag(header.algorithm) {
+        Some(Algorithm::Inversion2D) => {
+            let y = sample_cdf(buffer, header.marginal as usize, height, v);
+            [sample_cdf(buffer, row(y), width, u), y]
+        },
+        Some(Algorithm::Alias2D) => {
+            let y = sample_alias(buffer, header.marginal as usize, height, v);
+            [sample_alias(buffer, row(y), width, u), y]
+        },
+        Some(Algorithm::Hierarchical2D) => sample_hierarchical(buffer, &header, [u, v]),
+        None => panic!("unknown flat algorithm {}", header.algorithm),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data2d::Data2D;
+    use crate::distribution::Discrete2D;
+
+    fn weights() -> Data2D<f32> {
+        let mut weights = Data2D::new_same(7, 5, 1.0);
+        weights[[6, 0]] = 30.0;
+        weights[[2, 1]] = 0.0;
+        weights[[0, 3]] = 0.25;
+        for x in 0..7 {
+            weights[[x, 2]] = 0.0;
+        }
+        weights
+    }
+
+    // stratified, on the boundaries between idxs along either axis, where a split in float goes wrong, plus the extremes
+    fn test_flat<D: Discrete2D<f32> + ExportFlat>(dist: &D) {
+        let buffer = dist.export_flat();
+        let n = 97;
+        let cells = dist.width() * dist.height();
+        let mut differ = 0;
+        let points = (0..n).map(|i| (i as f32 + 0.5) / n as f32)
+            .chain((1..cells).map(|i| i as f32 / cells as f32))
+            .chain([0.0, 1.0 - f32::EPSILON / 2.0, 1.0]);
+        for u in points.clone() {
+            for v in points.clone() {
+                let sampled = sample(&buffer, [u, v]);
+                assert!(sampled[0] < dist.width() && sampled[1] < dist.height());
+                differ += (sampled != dist.sample([u, v])) as usize;
+            }
+        }
+        assert_eq!(differ, 0);
+    }
+
+    #[test]
+    fn header() {
+        let dist = <crate::Inversion2D<f32> as Discrete2D<f32>>::build(&weights());
+        let buffer = dist.export_flat();
+        let header = Header::from_words(&buffer).unwrap();
+        assert_eq!(header.to_words(), buffer[..HEADER_WORDS]);
+        assert_eq!(header, Header {
+            algorithm: Algorithm::Inversion2D as u32,
+            width: 7,
+            height: 5,
+            levels: 0,
+            marginal: HEADER_WORDS as u32,
+            conditional: HEADER_WORDS as u32 + 6,
+            row_stride: 8,
+            len: HEADER_WORDS as u32 + 6 + 5 * 8,
+        });
+        assert_eq!(core::mem::size_of::<Header>(), HEADER_WORDS * 4);
+        assert!(Header::from_words(&buffer[..HEADER_WORDS - 1]).is_none());
+    }
+
+    #[test]
+    fn inversion() {
+        test_flat(&<crate::Inversion2D<f32> as Discrete2D<f32>>::build(&weights()));
+    }
+
+    #[test]
+    fn alias() {
+        test_flat(&<crate::Alias2D<f32> as Discrete2D<f32>>::build(&weights()));
+    }
+
+    #[test]
+    fn hierarchical() {
+        test_flat(&<crate::Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights()));
+
+        let mut tall = Data2D::new_same(3, 9, 2.0);
+        tall[[1, 8]] = 0.0;
+        test_flat(&<crate::Hierarchical2D<f32> as Discrete2D<f32>>::build(&tall));
+    }
+
+    #[test]
+    fn integer_weights() {
+        let weights = Data2D::new_same(4, 3, 5u32);
+        let buffer = <crate::Hierarchical2D<u32> as Discrete2D<f32>>::build(&weights).export_flat();
+        assert_eq!(f32::from_bits(*buffer.last().unwrap()), 5.0);
+        assert_eq!(sample(&buffer, [0.0, 0.0]), [0, 0]);
+    }
+}
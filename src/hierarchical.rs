@@ -3,29 +3,71 @@
 use crate::distribution::{
     Continuous1D,
     Continuous2D,
+    ContinuousPdf1D,
+    ContinuousPdf2D,
     Discrete1D,
     Discrete1DPdf,
     Discrete2D,
     Discrete2DPdf,
+    Update2D,
+    RandomBits,
+};
+#[cfg(feature = "rkyv")]
+use crate::distribution::{
+    ArchivedDiscrete1D,
+    ArchivedDiscrete1DPdf,
+    ArchivedDiscrete2D,
+    ArchivedDiscrete2DPdf,
+    ArchivedValidate,
 };
 use crate::data2d::Data2D;
-use crate::utils::lerp;
+#[cfg(feature = "rkyv")]
+use crate::container::{
+    Algorithm,
+    ContainerError,
+    Storable,
+    StorableWeight,
+};
+use crate::flat::{self, ExportFlat};
+use crate::utils::{lerp, clamp_unit_lanes, FixedPoint, Lanes};
+use core::simd::{
+    Simd,
+    Select,
+    cmp::SimdPartialEq,
+    cmp::SimdPartialOrd,
+    num::SimdUint,
+};
 use num_traits::{
     Num,
     real::Real,
-    Zero,
     AsPrimitive,
 };
 
 #[cfg(not(feature = "std"))]
 use alloc::{
     boxed::Box,
+    vec::Vec,
     vec,
 };
 
+// both are stored as one flat implicit tree, with the root, the integral, at node 0
+// Hierarchical1D is a binary heap, with the children of node i at 2i + 1 and 2i + 2
+// Hierarchical2D is stored a level at a time, see Shape2D, where at the top only the longer axis splits,
+// so each node has two children, until the shorter one splits too, and below that each has four
+// the children of [x, y] are [2x, 2y], [2x + 1, 2y], [2x, 2y + 1] and [2x + 1, 2y + 1], in that order,
+// or just the first two along the longer axis
+//
+// leaves are padded with zero weights to a power of two (along each axis separately for 2D),
+// so that every node has all its children, and traversal never goes out of bounds
+// the padding samples exactly as the missing weights did when each level was its own allocation
+
 // returns pdf, selected idx
 // remaps u to [0-1) range
 fn select_remap<N: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static>(weights: [N; 2], rand: &mut R) -> bool {
+    // rand can round up to or past 1, which still shouldn't select a zero weight
+    if weights[1] == N::zero() {
+        return false;
+    }
     let weight_sum = weights[0] + weights[1];
     let weight_1_r = weights[0].as_();
     let weight_2_r = weights[1].as_();
@@ -40,111 +82,582 @@ fn select_remap<N: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static>(weights
     }
 }
 
-fn get_or_zero<Z: Zero + Copy>(v: &[Z], idx: usize) -> Z {
-    v.get(idx).copied().unwrap_or(Z::zero())
+// same as above, but on 64 bit fixed point, with the threshold exactly where the first weight ends
+fn select_remap_bits<N: Num + PartialOrd + Copy>(weights: [N; 2], bits: &mut u64) -> bool {
+    if weights[1] == N::zero() {
+        return false;
+    }
+    let threshold = weights[0].fixed_threshold(weights[0] + weights[1]);
+    if (*bits as u128) < threshold {
+        *bits = (((*bits as u128) << 64) / threshold) as u64;
+        false
+    } else {
+        *bits = (((*bits as u128 - threshold) << 64) / ((1u128 << 64) - threshold)) as u64;
+        true
+    }
+}
+
+// where the first of two weights ends, for inversion
+// all zero has no width in the output, so where doesn't matter, as long as it isn't NaN
+fn split<R: Real>(weights: [R; 2]) -> R {
+    let weight_sum = weights[0] + weights[1];
+    if weight_sum > R::zero() {
+        weights[0] / weight_sum
+    } else {
+        R::zero()
+    }
+}
+
+// leaves per side, always at least two, so that there's at least one level below the root
+fn padded_size(size: usize) -> usize {
+    size.next_power_of_two().max(2)
+}
+
+// interleaves the bits of x and y, x in the even bits
+fn morton([x, y]: [usize; 2]) -> usize {
+    fn spread(v: usize) -> usize {
+        let mut v = v as u64 & 0xffff_ffff;
+        v = (v | (v << 16)) & 0x0000_ffff_0000_ffff;
+        v = (v | (v << 8)) & 0x00ff_00ff_00ff_00ff;
+        v = (v | (v << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+        v as usize
+    }
+    spread(x) | (spread(y) << 1)
+}
+
+// levels of a Hierarchical2D pyramid, each stored after the one above it
+// a level is square tiles with the side of its shorter axis, one after another along the longer,
+// each in Morton order, which keeps the children of a node next to each other,
+// at two or four times its index within the level below
+#[derive(Clone, Copy)]
+struct Shape2D {
+    leaves: [usize; 2],
+    levels: usize,
+}
+
+impl Shape2D {
+    fn new(width: usize, height: usize) -> Self {
+        let leaves = [padded_size(width), padded_size(height)];
+        Self {
+            leaves,
+            levels: leaves[0].max(leaves[1]).ilog2() as usize,
+        }
+    }
+
+    // None if the pyramid wouldn't fit in a usize, for validation
+    #[cfg(any(feature = "serde", feature = "rkyv"))]
+    fn checked(width: usize, height: usize) -> Option<Self> {
+        let [width_leaves, height_leaves] = [width.checked_next_power_of_two()?.max(2), height.checked_next_power_of_two()?.max(2)];
+        // each level above the leaves has at most half the nodes of the one below
+        width_leaves.checked_mul(height_leaves)?.checked_mul(2)?;
+        Some(Self::new(width, height))
+    }
+
+    // levels at the top where only this axis splits, 0 for x, 1 for y
+    fn long(self) -> usize {
+        (self.leaves[1] > self.leaves[0]) as usize
+    }
+
+    // each half the one below, but at least one
+    fn sides(self, level: usize) -> [usize; 2] {
+        self.leaves.map(|side| (side >> (self.levels - level)).max(1))
+    }
+
+    fn len(self, level: usize) -> usize {
+        let [width, height] = self.sides(level);
+        width * height
+    }
+
+    // first node of a level, with the root as level 0
+    fn start(self, level: usize) -> usize {
+        (0..level).map(|level| self.len(level)).sum()
+    }
+
+    // same as start(levels + 1), without the loop, as sampling checks it
+    // the levels where only the longer axis splits double, from one node to one per tile,
+    // and those below quadruple, from one node per tile to the leaves
+    fn nodes(self) -> usize {
+        let long = self.long();
+        let [tiles, tile] = [self.leaves[long] / self.leaves[1 - long], self.leaves[1 - long]];
+        tiles - 1 + self.leaves[0] * self.leaves[1] + tiles * (tile * tile - 1) / 3
+    }
+
+    // of [x, y] within its level
+    fn index(self, level: usize, [x, y]: [usize; 2]) -> usize {
+        let [width, height] = self.sides(level);
+        let tile = width.min(height);
+        (x / tile + y / tile) * tile * tile + morton([x % tile, y % tile])
+    }
+
+    fn node(self, level: usize, idx: [usize; 2]) -> usize {
+        self.start(level) + self.index(level, idx)
+    }
+}
+
+// unpadded leaves, nodes as stored, either as is or archived,
+// so that sampling is shared with the archived tables
+fn leaves_1d<E>(heap: &[E], size: usize) -> &[E] {
+    &heap[heap.len() / 2..][..size]
+}
+
+// leaves are the last level
+fn leaf_2d<W, E: Copy + Into<W>>(pyramid: &[E], shape: Shape2D, idx: [usize; 2]) -> W {
+    pyramid[pyramid.len() - shape.len(shape.levels) + shape.index(shape.levels, idx)].into()
+}
+
+// internal nodes of a heap, the same as len / 2 for any that's valid, but for any length,
+// the last child of the last of them, 2 * internal, is below len, so traversal can skip bounds checks
+fn internal_1d(len: usize) -> usize {
+    len.saturating_sub(1) / 2
+}
+
+fn sample_bits_1d<W, E>(heap: &[E], mut bits: u64) -> usize
+    where W: Num + PartialOrd + Copy,
+          E: Copy + Into<W>,
+{
+    let internal = internal_1d(heap.len());
+    let mut node = 0;
+
+    while node < internal {
+        let child = 2 * node + 1;
+        // in bounds, as node < internal, so child + 1 <= 2 * internal < heap.len()
+        let weights = unsafe { [(*heap.get_unchecked(child)).into(), (*heap.get_unchecked(child + 1)).into()] };
+        node = child + select_remap_bits::<W>(weights, &mut bits) as usize;
+    }
+    node - internal
+}
+
+fn sample_remap_1d<W, R, E>(heap: &[E], mut u: R) -> (usize, R)
+    where W: Num + PartialOrd + AsPrimitive<R>,
+          R: Real + 'static,
+          E: Copy + Into<W>,
+{
+    let internal = internal_1d(heap.len());
+    let mut node = 0;
+
+    while node < internal {
+        let child = 2 * node + 1;
+        // in bounds, same as sample_bits_1d
+        let weights: [W; 2] = unsafe { [(*heap.get_unchecked(child)).into(), (*heap.get_unchecked(child + 1)).into()] };
+        node = child + select_remap(weights, &mut u) as usize;
+    }
+    (node - internal, crate::utils::clamp_unit(u))
 }
 
-fn get_or_zero_2d<Z: Zero + Copy>(v: &Data2D<Z>, idx: [usize; 2]) -> Z {
-    v.get(idx).copied().unwrap_or(Z::zero())
+// same as sample_remap_1d, a lane at a time, without the final clamp
+// all leaves are at the same depth, so lanes stay in step
+fn sample_remap_1d_lanes(heap: &[f32], mut u: Lanes<f32>) -> (Lanes<usize>, Lanes<f32>) {
+    let internal = heap.len() / 2;
+    let mut node = Simd::splat(0);
+
+    for _ in 0..(internal + 1).ilog2() {
+        let child = node * Simd::splat(2) + Simd::splat(1);
+        let weights = [
+            Simd::gather_or_default(heap, child),
+            Simd::gather_or_default(heap, child + Simd::splat(1)),
+        ];
+        // same as select_remap, where a zero second weight keeps rand as is
+        let second_zero = weights[1].simd_eq(Simd::splat(0.0));
+        let new_rand = u * (weights[0] + weights[1]);
+        let below_first = new_rand.simd_lt(weights[0]);
+        u = second_zero.select(u, below_first.select(new_rand / weights[0], (new_rand - weights[0]) / weights[1]));
+        node = child + (second_zero | below_first).cast::<isize>().select(Simd::splat(0), Simd::splat(1));
+    }
+    (node - Simd::splat(internal), u)
+}
+
+// batches of Hierarchical1D, with SIMD for f32, and one at a time otherwise
+trait HeapBatch<R: Copy + 'static>: Sized {
+    fn sample_batch(dist: &Hierarchical1D<Self>, us: &[R], out: &mut [usize]);
+    fn sample_continuous_batch(dist: &Hierarchical1D<Self>, us: &[R], out: &mut [R])
+        where usize: AsPrimitive<R>;
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> HeapBatch<R> for W {
+    default fn sample_batch(dist: &Hierarchical1D<W>, us: &[R], out: &mut [usize]) {
+        crate::utils::batch(us, out, |u| dist.sample(u));
+    }
+
+    default fn sample_continuous_batch(dist: &Hierarchical1D<W>, us: &[R], out: &mut [R])
+        where usize: AsPrimitive<R>,
+    {
+        crate::utils::batch(us, out, |u| dist.sample_continuous(u));
+    }
+}
+
+impl HeapBatch<f32> for f32 {
+    fn sample_batch(dist: &Hierarchical1D<f32>, us: &[f32], out: &mut [usize]) {
+        crate::utils::batch_lanes(us, out,
+            |u| sample_remap_1d_lanes(&dist.heap, Simd::from_array(u)).0.to_array(),
+            |u| dist.sample(u));
+    }
+
+    fn sample_continuous_batch(dist: &Hierarchical1D<f32>, us: &[f32], out: &mut [f32]) {
+        let size = Simd::splat(dist.size as f32);
+        crate::utils::batch_lanes(us, out,
+            |u| {
+                let (idx, u) = sample_remap_1d_lanes(&dist.heap, Simd::from_array(u));
+                clamp_unit_lanes((idx.cast::<f32>() + clamp_unit_lanes(u)) / size).to_array()
+            },
+            |u| dist.sample_continuous(u));
+    }
+}
+
+// root to leaf, with pick choosing between two weights along an axis, 0 for x, 1 for y,
+// where four children split along x first, by the sum of each column, and then y
+fn descend_2d<W, E>(pyramid: &[E], shape: Shape2D, mut pick: impl FnMut(usize, [W; 2]) -> bool) -> [usize; 2]
+    where W: Num + Copy,
+          E: Copy + Into<W>,
+{
+    // every node the traversal reads is below shape.nodes()
+    assert!(pyramid.len() == shape.nodes(), "Hierarchical2D pyramid doesn't match its size");
+    let at = |node: usize| -> W { unsafe { (*pyramid.get_unchecked(node)).into() } };
+    let long = shape.long();
+    let both_levels = shape.leaves[1 - long].ilog2() as usize;
+    let mut start = 0;
+    let mut len = 1;
+    // within its level
+    let mut node = 0;
+    let mut idx = [0; 2];
+
+    for _ in both_levels..shape.levels {
+        let child = start + len + 2 * node;
+        let selected = pick(long, [at(child), at(child + 1)]) as usize;
+
+        node = 2 * node + selected;
+        idx[long] = 2 * idx[long] + selected;
+        start += len;
+        len *= 2;
+    }
+
+    for _ in 0..both_levels {
+        let child = start + len + 4 * node;
+        let weights = [[at(child), at(child + 2)], [at(child + 1), at(child + 3)]];
+
+        let selected_x = pick(0, [weights[0][0] + weights[0][1], weights[1][0] + weights[1][1]]) as usize;
+        let selected_y = pick(1, weights[selected_x]) as usize;
+
+        node = 4 * node + (selected_x | (selected_y << 1));
+        idx = [2 * idx[0] + selected_x, 2 * idx[1] + selected_y];
+        start += len;
+        len *= 4;
+    }
+    idx
+}
+
+fn sample_remap_2d<W, R, E>(pyramid: &[E], shape: Shape2D, mut uv: [R; 2]) -> ([usize; 2], [R; 2])
+    where W: Num + PartialOrd + AsPrimitive<R>,
+          R: Real + 'static,
+          E: Copy + Into<W>,
+{
+    let idx = descend_2d::<W, E>(pyramid, shape, |axis, weights| select_remap(weights, &mut uv[axis]));
+    (idx, uv.map(crate::utils::clamp_unit))
+}
+
+fn sample_bits_2d<W, E>(pyramid: &[E], shape: Shape2D, mut bits: [u64; 2]) -> [usize; 2]
+    where W: Num + PartialOrd + Copy,
+          E: Copy + Into<W>,
+{
+    descend_2d::<W, E>(pyramid, shape, |axis, weights| select_remap_bits(weights, &mut bits[axis]))
 }
 
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Hierarchical1DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + Num + PartialOrd + Copy")))]
 pub struct Hierarchical1D<W> {
-    levels: Box<[Box<[W]>]>,
+    heap: Box<[W]>,
+    size: usize,
+}
+
+// as serialized, checked before becoming a Hierarchical1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Hierarchical1DFields<W> {
+    heap: Box<[W]>,
+    size: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Num + PartialOrd + Copy> TryFrom<Hierarchical1DFields<W>> for Hierarchical1D<W> {
+    type Error = &'static str;
+
+    fn try_from(Hierarchical1DFields { heap, size }: Hierarchical1DFields<W>) -> Result<Self, Self::Error> {
+        check_heap::<W, W>(&heap, size)?;
+        Ok(Self { heap, size })
+    }
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Discrete1D<R> for Hierarchical1D<W> {
+// shared by serde and ArchivedHierarchical1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_heap<W: Num + PartialOrd + Copy, E: Copy + Into<W>>(heap: &[E], size: usize) -> Result<(), &'static str> {
+    // same padding as build, with the tree above the leaves
+    let nodes = size.checked_next_power_of_two()
+        .and_then(|leaves| leaves.max(2).checked_mul(2))
+        .map(|nodes| nodes - 1);
+    if nodes != Some(heap.len()) {
+        return Err("Hierarchical1D heap doesn't match its size");
+    }
+
+    let internal = heap.len() / 2;
+    if !heap[internal..].iter().enumerate().all(|(i, leaf)| valid_leaf::<W>((*leaf).into(), i >= size)) {
+        return Err("Hierarchical1D weights have to be non-negative, and padding zero");
+    }
+    if !(0..internal).all(|node| valid_sum::<W, E>(heap[node], &heap[2 * node + 1..][..2])) {
+        return Err("Hierarchical1D node isn't the sum of its children");
+    }
+    Ok(())
+}
+
+// a weight, or zero if it's padding
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn valid_leaf<W: Num + PartialOrd + Copy>(leaf: W, padding: bool) -> bool {
+    if padding {
+        leaf == W::zero()
+    } else {
+        crate::utils::CheckedWeight::valid(leaf)
+    }
+}
+
+// summed in the same order as build, which also rules out the sum overflowing
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn valid_sum<W: Num + PartialOrd + Copy, E: Copy + Into<W>>(node: E, children: &[E]) -> bool {
+    children[1..].iter().try_fold(children[0].into(), |sum: W, child| crate::utils::CheckedWeight::checked_add(sum, (*child).into())) == Some(node.into())
+}
+
+#[cfg(feature = "serde")]
+impl<W> crate::utils::TableSize for Hierarchical1D<W> {
+    fn table_size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> crate::utils::TableSize for ArchivedHierarchical1D<W> {
+    fn table_size(&self) -> usize {
+        self.size.to_native() as usize
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + Copy + rkyv::Archive> ArchivedValidate for ArchivedHierarchical1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_heap::<W, _>(&self.heap, self.size.to_native() as usize)
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Discrete1D<R> for Hierarchical1D<W> {
     type Weight = W;
 
     fn build(weights: &[W]) -> Self {
-        let level_count = weights.len().next_power_of_two().ilog2() as usize;
-        let mut levels = vec![Default::default(); level_count].into_boxed_slice();
-
-        levels[level_count - 1] = weights.to_vec().into_boxed_slice();
-
-        let mut n = weights.len();
-        let mut prev_level_idx = level_count;
-        while n > 2 {
-            prev_level_idx -= 1;
-            n = n.div_ceil(2);
-            let mut level = vec![W::zero(); n].into_boxed_slice();
-            for (i, l) in level.iter_mut().enumerate() {
-                *l =
-                    get_or_zero(&levels[prev_level_idx], 2 * i + 0) +
-                    get_or_zero(&levels[prev_level_idx], 2 * i + 1);
-            }
-            levels[prev_level_idx - 1] = level;
-        }
+        Self::build_heap(weights)
+    }
 
-        Self {
-            levels,
-        }
+    fn sample(&self, u: R) -> usize {
+        self.sample_remap(u).0
     }
 
-    fn sample(&self, mut u: R) -> usize {
-        let mut idx = 0;
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_1d::<W, _>(&self.heap, bits.to_u64())
+    }
 
-        for level in self.levels.iter() {
-            idx *= 2;
-            let weights = [
-                get_or_zero(level, idx + 0),
-                get_or_zero(level, idx + 1),
-            ];
-            idx = idx + select_remap(weights, &mut u) as usize;
-        }
-        idx
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_1d::<W, R, _>(&self.heap, u)
+    }
+
+    fn sample_batch(&self, us: &[R], out: &mut [usize]) {
+        <W as HeapBatch<R>>::sample_batch(self, us, out);
     }
 
     fn integral(&self) -> W {
-        let first = self.levels.first().unwrap();
-        first[0] + first[1]
+        self.heap[0]
     }
 
     fn size(&self) -> usize {
-        self.levels.last().unwrap().len()
+        self.size
     }
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Discrete1DPdf<R> for Hierarchical1D<W> {
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Discrete1DPdf<R> for Hierarchical1D<W> {
     fn pdf(&self, u: usize) -> W {
-        self.levels.first().unwrap()[u]
+        leaves_1d(&self.heap, self.size)[u]
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let idx = self.sample(u);
+        (idx, leaves_1d(&self.heap, self.size)[idx].as_() / self.heap[0].as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<u64> + 'static> ArchivedDiscrete1D<R> for ArchivedHierarchical1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: R) -> usize {
+        self.sample_remap(u).0
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_1d::<W, _>(&self.heap, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_1d::<W, R, _>(&self.heap, u)
+    }
+
+    fn integral(&self) -> W {
+        self.heap[0].into()
+    }
+
+    fn size(&self) -> usize {
+        self.size.to_native() as usize
     }
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Continuous1D<R> for Hierarchical1D<W> where usize: AsPrimitive<R>,
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<u64> + 'static> ArchivedDiscrete1DPdf<R> for ArchivedHierarchical1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
 {
-    fn sample_continuous(&self, mut u: R) -> R {
-        let mut idx = 0;
-
-        for level in self.levels.iter() {
-            idx *= 2;
-            let weights = [
-                get_or_zero(level, idx + 0),
-                get_or_zero(level, idx + 1),
-            ];
-            idx = idx + select_remap(weights, &mut u) as usize;
+    fn pdf(&self, u: usize) -> W {
+        leaves_1d(&self.heap, self.size())[u].into()
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let idx = self.sample(u);
+        (idx, self.pdf(idx).as_() / self.integral().as_())
+    }
+}
+
+// layout of both before container version 2, with each level in its own allocation, coarsest first
+// only read to upgrade old containers, which are rebuilt from the leaves
+#[cfg(feature = "rkyv")]
+#[derive(rkyv::Archive, rkyv::Deserialize)]
+pub struct Levels<L> {
+    levels: Box<[L]>,
+}
+
+#[cfg(feature = "rkyv")]
+fn read_levels<L: rkyv::Archive>(payload: &[u8]) -> Result<L, ContainerError>
+    where rkyv::Archived<Levels<L>>: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+              + rkyv::Deserialize<Levels<L>, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+{
+    let levels = rkyv::from_bytes::<Levels<L>, rkyv::rancor::Error>(payload).map_err(|_| ContainerError::Invalid)?;
+    levels.levels.into_vec().pop().ok_or(ContainerError::Invalid)
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight + Num + Copy> Storable for Hierarchical1D<W>
+    where rkyv::Archived<Levels<Box<[W]>>>: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+              + rkyv::Deserialize<Levels<Box<[W]>>, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+{
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Hierarchical1D;
+    const LAYOUT_VERSION: u32 = 2;
+
+    fn dimensions(&self) -> [u64; 2] {
+        [self.size as u64, 1]
+    }
+
+    fn upgrade(_version: u32, payload: &[u8]) -> Result<Self, ContainerError> {
+        Ok(Self::build_heap(&read_levels::<Box<[W]>>(payload)?))
+    }
+}
+
+impl<W: Num + Copy> Hierarchical1D<W> {
+    fn build_heap(weights: &[W]) -> Self {
+        let leaves = padded_size(weights.len());
+        let mut heap = vec![W::zero(); 2 * leaves - 1].into_boxed_slice();
+        heap[leaves - 1..][..weights.len()].copy_from_slice(weights);
+        for node in (0..leaves - 1).rev() {
+            heap[node] = heap[2 * node + 1] + heap[2 * node + 2];
+        }
+
+        Self {
+            heap,
+            size: weights.len(),
+        }
+    }
+
+    // updates a single weight and its ancestors, O(log n)
+    pub fn set_weight(&mut self, idx: usize, weight: W) {
+        assert!(idx < self.size, "weight index out of range");
+        let mut node = self.heap.len() / 2 + idx;
+        self.heap[node] = weight;
+
+        while node > 0 {
+            node = (node - 1) / 2;
+            self.update_sum(node);
+        }
+    }
+
+    // updates many weights at once, only recomputing each shared ancestor once
+    pub fn set_weights(&mut self, updates: impl IntoIterator<Item = (usize, W)>) {
+        let internal = self.heap.len() / 2;
+        let mut dirty = updates.into_iter().map(|(idx, weight)| {
+            assert!(idx < self.size, "weight index out of range");
+            self.heap[internal + idx] = weight;
+            internal + idx
+        }).collect::<Vec<usize>>();
+
+        // all leaves are at the same depth, and halving keeps this sorted, so only need to dedup afterwards
+        dirty.sort_unstable();
+        while dirty.first().is_some_and(|node| *node > 0) {
+            for node in dirty.iter_mut() {
+                *node = (*node - 1) / 2;
+            }
+            dirty.dedup();
+            for &node in dirty.iter() {
+                self.update_sum(node);
+            }
         }
-        (idx.as_() + u) / self.size().as_()
+    }
+
+    fn update_sum(&mut self, node: usize) {
+        self.heap[node] = self.heap[2 * node + 1] + self.heap[2 * node + 2];
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Continuous1D<R> for Hierarchical1D<W> where usize: AsPrimitive<R>,
+{
+    fn sample_continuous(&self, u: R) -> R {
+        let (idx, du) = self.sample_remap(u);
+        crate::utils::clamp_unit((idx.as_() + du) / self.size().as_())
+    }
+
+    fn sample_continuous_batch(&self, us: &[R], out: &mut [R]) {
+        <W as HeapBatch<R>>::sample_continuous_batch(self, us, out);
+    }
+
+    fn sample_continuous_with_pdf(&self, u: R) -> (R, R) {
+        let (idx, du) = self.sample_remap(u);
+        let pdf = leaves_1d(&self.heap, self.size)[idx].as_() / self.integral().as_() * self.size().as_();
+        (crate::utils::clamp_unit((idx.as_() + du) / self.size().as_()), pdf)
     }
 
     fn invert_continuous(&self, u: R) -> R {
         let mut out = [R::zero(), R::one()];
         let mut bounds = [
             R::zero(),
-            self.size().next_power_of_two().as_() / self.size().as_(),
+            padded_size(self.size()).as_() / self.size().as_(),
         ];
-        let mut idx = 0;
+        let internal = self.heap.len() / 2;
+        let mut node = 0;
 
-        for level in self.levels.iter() {
-            idx *= 2;
+        while node < internal {
+            let child = 2 * node + 1;
 
             let bounds_mid = (bounds[0] + bounds[1]) / 2.as_();
 
             let more = u < bounds_mid;
-            let weights = [get_or_zero(level, idx + 0).as_(), get_or_zero(level, idx + 1).as_()];
-            out[more as usize] = lerp(weights[0] / (weights[0] + weights[1]), out[0], out[1]);
+            let weights: [R; 2] = [self.heap[child].as_(), self.heap[child + 1].as_()];
+            out[more as usize] = lerp(split(weights), out[0], out[1]);
             bounds[more as usize] = bounds_mid;
-            idx += (!more) as usize;
+            node = child + (!more) as usize;
         }
 
         let delta = (u - bounds[0]) / (bounds[1] - bounds[0]);
@@ -152,122 +665,386 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Continuous1D<R> fo
     }
 }
 
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + AsPrimitive<usize> + 'static> ContinuousPdf1D<R> for Hierarchical1D<W>
+    where usize: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, x: R) -> R {
+        let idx = <R as AsPrimitive<usize>>::as_(x * self.size().as_()).min(self.size() - 1);
+        leaves_1d(&self.heap, self.size)[idx].as_() / self.integral().as_() * self.size().as_()
+    }
+}
+
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Hierarchical2DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + Num + PartialOrd + Copy")))]
 pub struct Hierarchical2D<W> {
-    levels: Box<[Data2D<W>]>,
+    pyramid: Box<[W]>,
+    width: usize,
+    height: usize,
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Discrete2D<R> for Hierarchical2D<W> {
-    type Weight = W;
+// as serialized, checked before becoming a Hierarchical2D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Hierarchical2DFields<W> {
+    pyramid: Box<[W]>,
+    width: usize,
+    height: usize,
+}
 
-    fn build(weights: &Data2D<W>) -> Self {
-        let max_size = weights.width().max(weights.height());
-        let level_count = max_size.next_power_of_two().ilog2() as usize;
-        let mut levels = vec![Default::default(); level_count].into_boxed_slice();
-
-        levels[level_count - 1] = weights.clone();
-
-        let mut nx = weights.width();
-        let mut ny = weights.height();
-        let mut prev_level_idx = level_count;
-        while nx > 2 || ny > 2 {
-            prev_level_idx -= 1;
-            nx = nx.div_ceil(2);
-            ny = ny.div_ceil(2);
-            let mut level = Data2D::new_same(nx, ny, W::zero());
-            for y in 0..ny {
-                for x in 0..nx {
-                    level[[x, y]] = 
-                        get_or_zero_2d(&levels[prev_level_idx], [2 * x + 0, 2 * y + 0]) +
-                        get_or_zero_2d(&levels[prev_level_idx], [2 * x + 1, 2 * y + 0]) +
-                        get_or_zero_2d(&levels[prev_level_idx], [2 * x + 0, 2 * y + 1]) +
-                        get_or_zero_2d(&levels[prev_level_idx], [2 * x + 1, 2 * y + 1]);
-                }
+#[cfg(feature = "serde")]
+impl<W: Num + PartialOrd + Copy> TryFrom<Hierarchical2DFields<W>> for Hierarchical2D<W> {
+    type Error = &'static str;
+
+    fn try_from(Hierarchical2DFields { pyramid, width, height }: Hierarchical2DFields<W>) -> Result<Self, Self::Error> {
+        check_pyramid::<W, W>(&pyramid, width, height)?;
+        Ok(Self { pyramid, width, height })
+    }
+}
+
+// shared by serde and ArchivedHierarchical2D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_pyramid<W: Num + PartialOrd + Copy, E: Copy + Into<W>>(pyramid: &[E], width: usize, height: usize) -> Result<(), &'static str> {
+    // same padding as build
+    let shape = Shape2D::checked(width, height).filter(|shape| shape.nodes() == pyramid.len())
+        .ok_or("Hierarchical2D pyramid doesn't match its size")?;
+
+    let leaves = &pyramid[shape.start(shape.levels)..];
+    for y in 0..shape.leaves[1] {
+        for x in 0..shape.leaves[0] {
+            let leaf = leaves[shape.index(shape.levels, [x, y])].into();
+            if !valid_leaf::<W>(leaf, x >= width || y >= height) {
+                return Err("Hierarchical2D weights have to be non-negative, and padding zero");
             }
-            levels[prev_level_idx - 1] = level;
         }
-
-        Self {
-            levels,
+    }
+    for level in 0..shape.levels {
+        let [start, children] = [shape.start(level), shape.start(level + 1)];
+        let arity = shape.len(level + 1) / shape.len(level);
+        if !(0..shape.len(level)).all(|i| valid_sum::<W, E>(pyramid[start + i], &pyramid[children + arity * i..][..arity])) {
+            return Err("Hierarchical2D node isn't the sum of its children");
         }
     }
+    Ok(())
+}
 
-    fn sample(&self, [mut u, mut v]: [R; 2]) -> [usize; 2] {
-        let mut idx = [0; 2];
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + Copy + rkyv::Archive> ArchivedValidate for ArchivedHierarchical2D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_pyramid::<W, _>(&self.pyramid, self.width.to_native() as usize, self.height.to_native() as usize)
+    }
+}
 
-        for (i, level) in self.levels.iter().enumerate() {
-            if i > 0 && self.levels[i].width() > self.levels[i - 1].width() { idx[0] *= 2 }
-            if i > 0 && self.levels[i].height() > self.levels[i - 1].height() { idx[1] *= 2 }
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Discrete2D<R> for Hierarchical2D<W> {
+    type Weight = W;
 
-            let weights = [
-                [get_or_zero_2d(level, [idx[0] + 0, idx[1] + 0]), get_or_zero_2d(level, [idx[0] + 0, idx[1] + 1])],
-                [get_or_zero_2d(level, [idx[0] + 1, idx[1] + 0]), get_or_zero_2d(level, [idx[0] + 1, idx[1] + 1])],
-            ];
+    fn build(weights: &Data2D<W>) -> Self {
+        Self::build_pyramid(weights, reduce_level)
+    }
 
-            let weights_x = [weights[0][0] + weights[0][1], weights[1][0] + weights[1][1]];
-            let selected_x = select_remap(weights_x, &mut u) as usize;
+    fn sample(&self, uv: [R; 2]) -> [usize; 2] {
+        self.sample_remap(uv).0
+    }
 
-            let weights_y = [weights[selected_x][0], weights[selected_x][1]];
-            let selected_y = select_remap(weights_y, &mut v) as usize;
+    fn sample_remap(&self, uv: [R; 2]) -> ([usize; 2], [R; 2]) {
+        sample_remap_2d::<W, R, _>(&self.pyramid, self.shape(), uv)
+    }
 
-            idx[0] += selected_x;
-            idx[1] += selected_y;
-        }
-        idx
+    fn sample_bits<B: RandomBits>(&self, bits: [B; 2]) -> [usize; 2] {
+        sample_bits_2d::<W, _>(&self.pyramid, self.shape(), bits.map(|b| b.to_u64()))
     }
 
     fn integral(&self) -> W {
-        let mut sum = W::zero();
-        for l in self.levels.first().unwrap().iter().flatten() {
-            sum = sum + *l;
-        }
-        sum
+        self.pyramid[0]
     }
 
     fn width(&self) -> usize {
-        self.levels.last().unwrap().width()
+        self.width
     }
 
     fn height(&self) -> usize {
-        self.levels.last().unwrap().height()
+        self.height
     }
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Discrete2DPdf<R> for Hierarchical2D<W> {
-    fn pdf(&self, [u, v]: [usize; 2]) -> W {
-        self.levels.first().unwrap()[[u, v]]
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Discrete2DPdf<R> for Hierarchical2D<W> {
+    fn pdf(&self, uv: [usize; 2]) -> W {
+        leaf_2d(&self.pyramid, self.shape(), uv)
+    }
+
+    fn sample_with_pdf(&self, uv: [R; 2]) -> ([usize; 2], R) {
+        let idx = self.sample(uv);
+        (idx, leaf_2d::<W, _>(&self.pyramid, self.shape(), idx).as_() / self.pyramid[0].as_())
     }
 }
 
-impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Continuous2D<R> for Hierarchical2D<W>
-    where usize: AsPrimitive<R>,
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<u64> + 'static> ArchivedDiscrete2D<R> for ArchivedHierarchical2D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
 {
-    fn sample_continuous(&self, [mut u, mut v]: [R; 2]) -> [R; 2] {
-        let mut idx = [0; 2];
+    type Weight = W;
+
+    fn sample(&self, uv: [R; 2]) -> [usize; 2] {
+        self.sample_remap(uv).0
+    }
+
+    fn sample_remap(&self, uv: [R; 2]) -> ([usize; 2], [R; 2]) {
+        sample_remap_2d::<W, R, _>(&self.pyramid, self.shape(), uv)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: [B; 2]) -> [usize; 2] {
+        sample_bits_2d::<W, _>(&self.pyramid, self.shape(), bits.map(|b| b.to_u64()))
+    }
+
+    fn integral(&self) -> W {
+        self.pyramid[0].into()
+    }
+
+    fn width(&self) -> usize {
+        self.width.to_native() as usize
+    }
 
-        for (i, level) in self.levels.iter().enumerate() {
-            if i > 0 && self.levels[i].width() > self.levels[i - 1].width() { idx[0] *= 2 }
-            if i > 0 && self.levels[i].height() > self.levels[i - 1].height() { idx[1] *= 2 }
+    fn height(&self) -> usize {
+        self.height.to_native() as usize
+    }
+}
 
-            let weights = [
-                [get_or_zero_2d(level, [idx[0] + 0, idx[1] + 0]), get_or_zero_2d(level, [idx[0] + 0, idx[1] + 1])],
-                [get_or_zero_2d(level, [idx[0] + 1, idx[1] + 0]), get_or_zero_2d(level, [idx[0] + 1, idx[1] + 1])],
-            ];
+#[cfg(feature = "rkyv")]
+impl<W: Num + PartialOrd + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<u64> + 'static> ArchivedDiscrete2DPdf<R> for ArchivedHierarchical2D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn pdf(&self, uv: [usize; 2]) -> W {
+        leaf_2d(&self.pyramid, self.shape(), uv)
+    }
 
-            let weights_x = [weights[0][0] + weights[0][1], weights[1][0] + weights[1][1]];
-            let selected_x = select_remap(weights_x, &mut u) as usize;
+    fn sample_with_pdf(&self, uv: [R; 2]) -> ([usize; 2], R) {
+        let idx = self.sample(uv);
+        (idx, self.pdf(idx).as_() / self.integral().as_())
+    }
+}
 
-            let weights_y = [weights[selected_x][0], weights[selected_x][1]];
-            let selected_y = select_remap(weights_y, &mut v) as usize;
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> ArchivedHierarchical2D<W> {
+    fn shape(&self) -> Shape2D {
+        Shape2D::new(self.width.to_native() as usize, self.height.to_native() as usize)
+    }
+}
 
-            idx[0] += selected_x;
-            idx[1] += selected_y;
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight + Num + Copy> Storable for Hierarchical2D<W>
+    where rkyv::Archived<Levels<Data2D<W>>>: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+              + rkyv::Deserialize<Levels<Data2D<W>>, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+          rkyv::Archived<SquarePyramid<W>>: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+              + rkyv::Deserialize<SquarePyramid<W>, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+{
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Hierarchical2D;
+    const LAYOUT_VERSION: u32 = 3;
+
+    fn dimensions(&self) -> [u64; 2] {
+        [self.width as u64, self.height as u64]
+    }
+
+    fn upgrade(version: u32, payload: &[u8]) -> Result<Self, ContainerError> {
+        let leaves = if version == 1 {
+            read_levels::<Data2D<W>>(payload)?
+        } else {
+            read_square_pyramid(payload)?
+        };
+        Ok(Self::build_pyramid(&leaves, reduce_level))
+    }
+}
+
+// layout of Hierarchical2D in container version 2, with the leaves padded to a square, a 4-ary heap
+// only read to upgrade old containers, which are rebuilt from the leaves
+#[cfg(feature = "rkyv")]
+#[derive(rkyv::Archive, rkyv::Deserialize)]
+pub struct SquarePyramid<W> {
+    pyramid: Box<[W]>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "rkyv")]
+fn read_square_pyramid<W: rkyv::Archive + Num + Copy>(payload: &[u8]) -> Result<Data2D<W>, ContainerError>
+    where rkyv::Archived<SquarePyramid<W>>: for<'a> rkyv::bytecheck::CheckBytes<rkyv::api::high::HighValidator<'a, rkyv::rancor::Error>>
+              + rkyv::Deserialize<SquarePyramid<W>, rkyv::api::high::HighDeserializer<rkyv::rancor::Error>>,
+{
+    let SquarePyramid { pyramid, width, height } = rkyv::from_bytes::<SquarePyramid<W>, rkyv::rancor::Error>(payload)
+        .map_err(|_| ContainerError::Invalid)?;
+    let nodes = width.max(height).checked_next_power_of_two()
+        .and_then(|side| side.max(2).checked_mul(side.max(2)))
+        .and_then(|leaves| leaves.checked_mul(4))
+        .map(|nodes| (nodes - 1) / 3);
+    if nodes != Some(pyramid.len()) {
+        return Err(ContainerError::Invalid);
+    }
+
+    // the leaves start a quarter of the way in
+    let mut leaves = Data2D::new_same(width, height, W::zero());
+    for y in 0..height {
+        for x in 0..width {
+            leaves[[x, y]] = pyramid[pyramid.len() / 4 + morton([x, y])];
         }
-        let idx_normalized = [
-            (idx[0].as_() + u) / self.width().as_(),
-            (idx[1].as_() + v) / self.height().as_(),
-        ];
-        idx_normalized
+    }
+    Ok(leaves)
+}
+
+// flat export keeps each level unpadded and row major, same as they were stored before
+impl<W: AsPrimitive<f32>> ExportFlat for Hierarchical2D<W> {
+    fn export_flat(&self) -> Vec<u32> {
+        let mut buffer = flat::new_buffer();
+        let shape = self.shape();
+        let levels = shape.levels;
+        let directory = buffer.len();
+        buffer.resize(directory + 3 * levels, 0);
+
+        for i in 0..levels {
+            let [width, height] = self.level_size(i + 1);
+            let offset = flat::word(buffer.len());
+            buffer[directory + 3 * i..][..3].copy_from_slice(&[offset, flat::word(width), flat::word(height)]);
+            for y in 0..height {
+                for x in 0..width {
+                    buffer.push(flat::weight_word(self.pyramid[shape.node(i + 1, [x, y])]));
+                }
+            }
+        }
+
+        flat::finish_buffer(&mut buffer, flat::Header {
+            algorithm: flat::Algorithm::Hierarchical2D as u32,
+            width: flat::word(self.width),
+            height: flat::word(self.height),
+            levels: flat::word(levels),
+            marginal: flat::word(directory),
+            conditional: 0,
+            row_stride: 0,
+            len: 0,
+        });
+        buffer
+    }
+}
+
+// in the same order as a 2x2 of rows, as that's how levels used to be reduced
+fn sum_children<W: Num + Copy>(children: &[W]) -> W {
+    children[1..].iter().fold(children[0], |sum, weight| sum + *weight)
+}
+
+// each weight of a level the sum of its two or four children, which come one after another in the level below
+fn reduce_level<W: Num + Copy>(level: &mut [W], children: &[W]) {
+    let arity = children.len() / level.len();
+    for (weight, children) in level.iter_mut().zip(children.chunks_exact(arity)) {
+        *weight = sum_children(children);
+    }
+}
+
+impl<W> Hierarchical2D<W> {
+    fn shape(&self) -> Shape2D {
+        Shape2D::new(self.width, self.height)
+    }
+
+    // unpadded size of a level, each half of the one below, rounded up
+    fn level_size(&self, level: usize) -> [usize; 2] {
+        let scale = 1 << (self.shape().levels - level);
+        [self.width.div_ceil(scale), self.height.div_ceil(scale)]
+    }
+}
+
+impl<W: Num + Copy> Hierarchical2D<W> {
+    // leaves go in at the bottom, then each level is filled in from the one below it by reduce,
+    // which gets a level and the level of its children
+    fn build_pyramid(weights: &Data2D<W>, reduce: impl Fn(&mut [W], &[W])) -> Self {
+        let shape = Shape2D::new(weights.width(), weights.height());
+        let mut pyramid = vec![W::zero(); shape.nodes()].into_boxed_slice();
+
+        for y in 0..weights.height() {
+            for x in 0..weights.width() {
+                pyramid[shape.node(shape.levels, [x, y])] = weights[[x, y]];
+            }
+        }
+
+        for level in (0..shape.levels).rev() {
+            let (upper, lower) = pyramid.split_at_mut(shape.start(level + 1));
+            reduce(&mut upper[shape.start(level)..], &lower[..shape.len(level + 1)]);
+        }
+
+        Self {
+            pyramid,
+            width: weights.width(),
+            height: weights.height(),
+        }
+    }
+
+    // same as build, with each level reduced across threads, results are identical
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &Data2D<W>) -> Self
+        where W: Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        Self::build_pyramid(weights, |level, children| {
+            let arity = children.len() / level.len();
+            level.par_iter_mut().zip(children.par_chunks_exact(arity)).for_each(|(weight, children)| *weight = sum_children(children));
+        })
+    }
+
+    // updates a single weight and its ancestors, O(log n)
+    pub fn set_weight(&mut self, idx: [usize; 2], weight: W) {
+        assert!(idx[0] < self.width && idx[1] < self.height, "weight index out of range");
+        let shape = self.shape();
+        self.pyramid[shape.node(shape.levels, idx)] = weight;
+
+        for level in (0..shape.levels).rev() {
+            let scale = shape.levels - level;
+            self.update_sum(shape, level, [idx[0] >> scale, idx[1] >> scale]);
+        }
+    }
+
+    fn update_sum(&mut self, shape: Shape2D, level: usize, idx: [usize; 2]) {
+        let arity = shape.len(level + 1) / shape.len(level);
+        let child = shape.start(level + 1) + arity * shape.index(level, idx);
+        self.pyramid[shape.node(level, idx)] = sum_children(&self.pyramid[child..child + arity]);
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Update2D<R> for Hierarchical2D<W> {
+    fn update_rect(&mut self, weights: &Data2D<W>, mut min: [usize; 2], mut max: [usize; 2]) {
+        let shape = self.shape();
+        for y in min[1]..max[1] {
+            for x in min[0]..max[0] {
+                self.pyramid[shape.node(shape.levels, [x, y])] = weights[[x, y]];
+            }
+        }
+
+        for level in (0..shape.levels).rev() {
+            min = [min[0] / 2, min[1] / 2];
+            max = [max[0].div_ceil(2), max[1].div_ceil(2)];
+            for y in min[1]..max[1] {
+                for x in min[0]..max[0] {
+                    self.update_sum(shape, level, [x, y]);
+                }
+            }
+        }
+    }
+}
+
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + 'static> Continuous2D<R> for Hierarchical2D<W>
+    where usize: AsPrimitive<R>,
+{
+    fn sample_continuous(&self, uv: [R; 2]) -> [R; 2] {
+        let (idx, [du, dv]) = self.sample_remap(uv);
+        [
+            crate::utils::clamp_unit((idx[0].as_() + du) / self.width().as_()),
+            crate::utils::clamp_unit((idx[1].as_() + dv) / self.height().as_()),
+        ]
+    }
+
+    fn sample_continuous_with_pdf(&self, uv: [R; 2]) -> ([R; 2], R) {
+        let (idx, [du, dv]) = self.sample_remap(uv);
+        let pdf = leaf_2d::<W, _>(&self.pyramid, self.shape(), idx).as_() / self.integral().as_() * (self.width() * self.height()).as_();
+        let xy = [(idx[0].as_() + du) / self.width().as_(), (idx[1].as_() + dv) / self.height().as_()];
+        (xy.map(crate::utils::clamp_unit), pdf)
     }
 
     fn invert_continuous(&self, [u, v]: [R; 2]) -> [R; 2] {
@@ -281,34 +1058,39 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Continuous2D<R> fo
             R::zero(),
             self.height().next_power_of_two().as_() / self.height().as_(),
         ];
+        let shape = self.shape();
         let mut idx = [0; 2];
 
-        for (i, level) in self.levels.iter().enumerate() {
-            if i > 0 && self.levels[i].width() > self.levels[i - 1].width() { idx[0] *= 2 }
-            if i > 0 && self.levels[i].height() > self.levels[i - 1].height() { idx[1] *= 2 }
+        for level in 1..=shape.levels {
+            idx = [2 * idx[0], 2 * idx[1]];
+            // a dimension that's down to a single weight has nothing to split, even though it's padded,
+            // and might not have a second row or column at all
+            let [width, height] = self.level_size(level);
+            let at = |idx: [usize; 2]| -> W { self.pyramid[shape.node(level, idx)] };
+            let column = |x: usize| -> W {
+                if height > 1 {
+                    at([x, idx[1]]) + at([x, idx[1] + 1])
+                } else {
+                    at([x, idx[1]])
+                }
+            };
 
-            if level.width() > 1 {
+            if width > 1 {
                 let bounds_mid = (bounds_u[0] + bounds_u[1]) / 2.as_();
 
-                let weights = [
-                    (get_or_zero_2d(level, [idx[0] + 0, idx[1] + 0]) + get_or_zero_2d(level, [idx[0] + 0, idx[1] + 1])).as_(),
-                    (get_or_zero_2d(level, [idx[0] + 1, idx[1] + 0]) + get_or_zero_2d(level, [idx[0] + 1, idx[1] + 1])).as_(),
-                ];
+                let weights = [column(idx[0]).as_(), column(idx[0] + 1).as_()];
                 let more = u < bounds_mid;
-                out_u[more as usize] = lerp(weights[0] / (weights[0] + weights[1]), out_u[0], out_u[1]);
+                out_u[more as usize] = lerp(split(weights), out_u[0], out_u[1]);
                 bounds_u[more as usize] = bounds_mid;
                 idx[0] += (!more) as usize;
             }
 
-            if level.height() > 1 {
+            if height > 1 {
                 let bounds_mid = (bounds_v[0] + bounds_v[1]) / 2.as_();
 
                 let more = v < bounds_mid;
-                let weights = [
-                    get_or_zero_2d(level, [idx[0] + 0, idx[1] + 0]).as_(),
-                    get_or_zero_2d(level, [idx[0] + 0, idx[1] + 1]).as_(),
-                ];
-                out_v[more as usize] = lerp(weights[0] / (weights[0] + weights[1]), out_v[0], out_v[1]);
+                let weights = [at(idx).as_(), at([idx[0], idx[1] + 1]).as_()];
+                out_v[more as usize] = lerp(split(weights), out_v[0], out_v[1]);
                 bounds_v[more as usize] = bounds_mid;
                 idx[1] += (!more) as usize;
             }
@@ -320,6 +1102,18 @@ impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + 'static> Continuous2D<R> fo
     }
 }
 
+impl<W: Num + PartialOrd + AsPrimitive<R>, R: Real + AsPrimitive<u64> + AsPrimitive<usize> + 'static> ContinuousPdf2D<R> for Hierarchical2D<W>
+    where usize: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, [x, y]: [R; 2]) -> R {
+        let idx = [
+            <R as AsPrimitive<usize>>::as_(x * self.width().as_()).min(self.width() - 1),
+            <R as AsPrimitive<usize>>::as_(y * self.height().as_()).min(self.height() - 1),
+        ];
+        leaf_2d::<W, _>(&self.pyramid, self.shape(), idx).as_() / self.integral().as_() * (self.width() * self.height()).as_()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::distribution::distribution_1d_tests;
@@ -329,6 +1123,289 @@ mod tests {
     distribution_1d_tests!(crate::hierarchical::Hierarchical1D);
     continuous_distribution_1d_tests!(crate::hierarchical::Hierarchical1D);
 
+    // each level splits exactly, and with these, so does the remap in between
+    #[test]
+    fn sample_bits_exact() {
+        use crate::distribution::Discrete1D;
+        let dist = <crate::hierarchical::Hierarchical1D<f32> as Discrete1D<f32>>::build(&[1.0, 2.0, 3.0]);
+        crate::distribution::test_sample_bits_exact_1d::<f32, _>(&dist, &[1, 2, 3]);
+    }
+
+    // pdf is the leaf weight, not one from a coarser level, which it used to read from
+    #[test]
+    fn pdf_1d() {
+        use crate::distribution::{Discrete1D, Discrete1DPdf};
+        use crate::hierarchical::Hierarchical1D;
+
+        for size in [1, 2, 3, 5, 8, 13] {
+            let weights = (0..size).map(|i| (i * 3 % 7) as f32 + 0.5).collect::<Vec<_>>();
+            let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights);
+            for (i, weight) in weights.iter().enumerate() {
+                assert_eq!(Discrete1DPdf::<f32>::pdf(&dist, i), *weight);
+            }
+        }
+    }
+
+    mod update_1d {
+        use crate::distribution::{
+            Discrete1D,
+            Discrete1DPdf,
+        };
+        use crate::hierarchical::Hierarchical1D;
+
+        fn assert_same(updated: &Hierarchical1D<f32>, weights: &[f32]) {
+            let fresh = <Hierarchical1D<f32> as Discrete1D<f32>>::build(weights);
+            assert_eq!(updated.heap, fresh.heap);
+            assert_eq!(Discrete1D::<f32>::integral(updated), Discrete1D::<f32>::integral(&fresh));
+            for (i, weight) in weights.iter().enumerate() {
+                assert_eq!(Discrete1DPdf::<f32>::pdf(updated, i), *weight);
+            }
+            let sample_count = 1000;
+            for i in 0..sample_count {
+                let u = i as f32 / sample_count as f32;
+                assert_eq!(Discrete1D::<f32>::sample(updated, u), Discrete1D::<f32>::sample(&fresh, u));
+            }
+        }
+
+        #[test]
+        fn set_weight() {
+            let mut weights = [1.0; 13];
+            let mut dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights);
+            for (i, weight) in [(0, 5.0), (12, 0.0), (7, 3.0), (7, 0.5), (3, 8.0)] {
+                dist.set_weight(i, weight);
+                weights[i] = weight;
+                assert_same(&dist, &weights);
+            }
+        }
+
+        #[test]
+        fn set_weights() {
+            let mut weights = [1.0; 100];
+            let mut dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&weights);
+            let updates = [(0, 5.0), (99, 0.0), (50, 3.0), (51, 2.0), (50, 0.5), (98, 7.0)];
+            dist.set_weights(updates);
+            for (i, weight) in updates {
+                weights[i] = weight;
+            }
+            assert_same(&dist, &weights);
+        }
+    }
+
     distribution_2d_tests!(crate::hierarchical::Hierarchical2D);
-}
 
+    #[test]
+    fn sample_with_pdf() {
+        crate::distribution::test_sample_with_pdf_1d::<crate::hierarchical::Hierarchical1D<f64>>(&[1.0, 0.0, 2.0, 4.0, 8.0], 1000);
+    }
+
+    #[test]
+    fn chisq_pdf() {
+        use crate::distribution::{Discrete1D, Discrete2D};
+        use crate::hierarchical::{Hierarchical1D, Hierarchical2D};
+        let weights = [1, 0, 2, 4, 8, 100];
+        crate::distribution::chisq_distribution_1d_pdf(&<Hierarchical1D<usize> as Discrete1D<f64>>::build(&weights), &weights, 100_000);
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1);
+        weights[[4, 0]] = 30;
+        weights[[2, 1]] = 0;
+        crate::distribution::chisq_distribution_2d_pdf(&<Hierarchical2D<usize> as Discrete2D<f64>>::build(&weights), &weights, 100_000);
+    }
+
+    #[test]
+    fn continuous_with_pdf_2d() {
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 4.0;
+        crate::distribution::test_continuous_with_pdf_2d::<crate::hierarchical::Hierarchical2D<f64>>(&weights, 300);
+        crate::distribution::test_pdf_continuous_2d::<crate::hierarchical::Hierarchical2D<f64>>(&weights, 300);
+    }
+
+    #[test]
+    fn zero_integral_2d() {
+        crate::distribution::test_zero_integral_continuous_2d::<crate::hierarchical::Hierarchical2D<f32>>(5, 4);
+    }
+
+    #[test]
+    fn edge_cases_2d() {
+        crate::distribution::test_edge_cases_continuous_2d::<f32, crate::hierarchical::Hierarchical2D<f32>>();
+        crate::distribution::test_edge_cases_continuous_2d::<f64, crate::hierarchical::Hierarchical2D<f64>>();
+    }
+
+    #[test]
+    fn update_2d() {
+        crate::distribution::test_update_2d::<crate::hierarchical::Hierarchical2D<f32>>(17, 10);
+        crate::distribution::test_update_2d::<crate::hierarchical::Hierarchical2D<f32>>(8, 8);
+    }
+
+    // padded along each axis on its own, so a long thin table isn't padded to the square of its length
+    #[test]
+    fn padding_2d() {
+        use crate::distribution::Discrete2D;
+        use crate::hierarchical::Hierarchical2D;
+
+        for [width, height] in [[1, 1], [1000, 1], [3, 300], [5, 3]] {
+            let weights = crate::data2d::Data2D::new_same(width, height, 1.0f32);
+            let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+            let leaves = width.next_power_of_two().max(2) * height.next_power_of_two().max(2);
+            assert!(dist.pyramid.len() < 2 * leaves);
+            let shape = dist.shape();
+            assert_eq!(shape.nodes(), shape.start(shape.levels + 1));
+            assert_eq!(Discrete2D::<f32>::integral(&dist), (width * height) as f32);
+        }
+    }
+
+    #[test]
+    fn morton() {
+        assert_eq!([[0, 0], [1, 0], [0, 1], [1, 1], [2, 0], [3, 1], [0, 2], [3, 3]].map(super::morton), [0, 1, 2, 3, 4, 7, 8, 15]);
+        assert_eq!(super::morton([usize::MAX >> 32, 0]), 0x5555_5555_5555_5555);
+    }
+
+    // same as pdf_1d, which Hierarchical2D got wrong the same way
+    #[test]
+    fn pdf_2d() {
+        use crate::distribution::{Discrete2D, Discrete2DPdf};
+        use crate::hierarchical::Hierarchical2D;
+
+        for [width, height] in [[1, 1], [2, 1], [5, 3], [3, 17], [8, 8]] {
+            let mut weights = crate::data2d::Data2D::new_same(width, height, 0.0f32);
+            for y in 0..height {
+                for x in 0..width {
+                    weights[[x, y]] = ((x * 5 + y * 3) % 7) as f32 + 0.5;
+                }
+            }
+            let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(Discrete2DPdf::<f32>::pdf(&dist, [x, y]), weights[[x, y]]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn set_weight_2d() {
+        use crate::distribution::{Discrete2D, Discrete2DPdf};
+        use crate::hierarchical::Hierarchical2D;
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0);
+        let mut dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        dist.set_weight([4, 2], 3.0);
+        weights[[4, 2]] = 3.0;
+        let fresh = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        assert_eq!(dist.pyramid, fresh.pyramid);
+        assert_eq!(Discrete2DPdf::<f32>::pdf(&dist, [4, 2]), 3.0);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use crate::distribution::{Discrete1D, Discrete2D};
+        use crate::hierarchical::{
+            Hierarchical1D,
+            Hierarchical2D,
+            ArchivedHierarchical1D,
+            ArchivedHierarchical2D,
+        };
+        use rkyv::rancor::Error;
+
+        let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0]);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, crate::distribution::access_checked::<ArchivedHierarchical1D<f32>>(&bytes).unwrap());
+
+        let mut weights = crate::data2d::Data2D::new_same(5, 3, 1.0f32);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 0.0;
+        let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_2d(&dist, crate::distribution::access_checked::<ArchivedHierarchical2D<f32>>(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_invalid() {
+        use crate::distribution::{Discrete1D, Discrete2D, test_archived_invalid};
+        use crate::hierarchical::{Hierarchical1D, Hierarchical2D};
+
+        let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 2.0, 4.0, 8.0]);
+        test_archived_invalid(&dist, &Hierarchical1D { heap: dist.heap.clone(), size: 9 });
+        // the last weight becomes padding
+        test_archived_invalid(&dist, &Hierarchical1D { heap: dist.heap.clone(), size: 4 });
+        let mut heap = dist.heap.clone();
+        heap[0] = 100.0;
+        test_archived_invalid(&dist, &Hierarchical1D { heap, size: 5 });
+
+        let weights = crate::data2d::Data2D::new_same(6, 3, 1.0f32);
+        let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        test_archived_invalid(&dist, &Hierarchical2D { pyramid: dist.pyramid[1..].into(), width: 6, height: 3 });
+        test_archived_invalid(&dist, &Hierarchical2D { pyramid: dist.pyramid.clone(), width: 5, height: 3 });
+        let mut pyramid = dist.pyramid.clone();
+        pyramid[1] = 100.0;
+        test_archived_invalid(&dist, &Hierarchical2D { pyramid, width: 6, height: 3 });
+    }
+
+    // sampling skips bounds checks, which an archived table that wasn't validated can't get around
+    #[cfg(feature = "rkyv")]
+    #[test]
+    #[should_panic(expected = "pyramid doesn't match its size")]
+    fn archived_unvalidated() {
+        use crate::distribution::{ArchivedDiscrete2D, Discrete2D};
+        use crate::hierarchical::{Hierarchical2D, ArchivedHierarchical2D};
+        use rkyv::rancor::Error;
+
+        let weights = crate::data2d::Data2D::new_same(6, 3, 1.0f32);
+        let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&Hierarchical2D { pyramid: dist.pyramid[..9].into(), width: 6, height: 3 }).unwrap();
+        let archived = rkyv::access::<ArchivedHierarchical2D<f32>, Error>(&bytes).unwrap();
+        ArchivedDiscrete2D::<f32>::sample(archived, [0.5, 0.5]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::distribution::{Discrete1D, Discrete2D, test_serde_1d, test_serde_2d, test_serde_invalid};
+        use crate::hierarchical::{Hierarchical1D, Hierarchical2D};
+
+        let dist = <Hierarchical1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0]);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| { json["heap"].as_array_mut().unwrap().push(1.0.into()); });
+        test_serde_invalid(&dist, |json| json["size"] = 9.into());
+        test_serde_invalid(&dist, |json| json["heap"] = serde_json::json!([]));
+        // the last weight becomes padding
+        test_serde_invalid(&dist, |json| json["size"] = 6.into());
+        test_serde_invalid(&dist, |json| json["heap"][0] = 100.0.into());
+        test_serde_invalid(&dist, |json| json["heap"][7] = (-1.0).into());
+
+        let mut weights = crate::data2d::Data2D::new_same(6, 3, 1.0f32);
+        weights[[4, 0]] = 3.0;
+        weights[[1, 2]] = 0.0;
+        let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+        test_serde_2d(&dist);
+        // 6x3 pads to 8x4, as does 5x4, but not 9x3
+        test_serde_invalid(&dist, |json| json["width"] = 9.into());
+        test_serde_invalid(&dist, |json| { json["pyramid"].as_array_mut().unwrap().pop(); });
+        test_serde_invalid(&dist, |json| json["pyramid"] = serde_json::json!([]));
+        // the last column becomes padding
+        test_serde_invalid(&dist, |json| json["width"] = 5.into());
+        test_serde_invalid(&dist, |json| json["pyramid"][0] = 100.0.into());
+        test_serde_invalid(&dist, |json| json["pyramid"][1] = 100.0.into());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_build() {
+        use crate::distribution::Discrete2D;
+        use crate::hierarchical::Hierarchical2D;
+
+        for [width, height] in [[1, 1], [5, 3], [3, 17], [300, 200]] {
+            let mut weights = crate::data2d::Data2D::new_same(width, height, 0.0f32);
+            for y in 0..height {
+                for x in 0..width {
+                    weights[[x, y]] = ((x * 7919 + y * 104729) % 13) as f32 * 0.1;
+                }
+            }
+            let dist = <Hierarchical2D<f32> as Discrete2D<f32>>::build(&weights);
+            let par = Hierarchical2D::par_build(&weights);
+            assert_eq!(par.pyramid, dist.pyramid);
+        }
+    }
+}
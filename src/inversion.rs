@@ -2,6 +2,34 @@ use crate::distribution::{
     Discrete1D,
     Discrete1DPdf,
     Continuous1D,
+    ContinuousPdf1D,
+    RandomBits,
+};
+#[cfg(feature = "rkyv")]
+use crate::distribution::{
+    ArchivedDiscrete1D,
+    ArchivedDiscrete1DPdf,
+    ArchivedValidate,
+};
+#[cfg(feature = "rkyv")]
+use crate::container::{
+    Algorithm,
+    Storable,
+    StorableWeight,
+};
+use crate::flat::{self, FlatTable1D};
+use crate::packed2d::{
+    PackedLayout,
+    PackedRow,
+    PackedRowPdf,
+    PackedRowContinuous,
+};
+use crate::utils::{self, FixedPoint, Lanes};
+use core::simd::{
+    Simd,
+    Select,
+    cmp::SimdPartialOrd,
+    num::SimdUint,
 };
 use num_traits::{
     Num,
@@ -9,16 +37,279 @@ use num_traits::{
     AsPrimitive,
 };
 #[cfg(not(feature = "std"))]
-use alloc::boxed::Box;
+use alloc::{
+    boxed::Box,
+    vec::Vec,
+    vec,
+};
 
 pub type Inversion2D<R> = crate::Adapter2D<Inversion1D<R>>;
+pub type EytzingerInversion2D<R> = crate::Adapter2D<EytzingerInversion1D<R>>;
+pub type PackedInversion2D<R> = crate::Packed2D<Inversion1D<R>>;
 
 #[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Inversion1DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + num_traits::Zero + PartialOrd + Copy")))]
 pub struct Inversion1D<W> {
     pub cdf: Box<[W]>,
+    pub guide: Box<[u32]>, // optional, empty if not built
+}
+
+// as serialized, checked before becoming an Inversion1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Inversion1DFields<W> {
+    cdf: Box<[W]>,
+    guide: Box<[u32]>,
+}
+
+#[cfg(feature = "serde")]
+impl<W: num_traits::Zero + PartialOrd + Copy> TryFrom<Inversion1DFields<W>> for Inversion1D<W> {
+    type Error = &'static str;
+
+    fn try_from(Inversion1DFields { cdf, guide }: Inversion1DFields<W>) -> Result<Self, Self::Error> {
+        check_cdf::<W, _, _>(&cdf, &guide)?;
+        Ok(Self { cdf, guide })
+    }
 }
 
-impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> Discrete1D<R> for Inversion1D<W> {
+// shared by serde and ArchivedInversion1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_cdf<W, E, G>(cdf: &[E], guide: &[G]) -> Result<(), &'static str>
+    where W: num_traits::Zero + PartialOrd,
+          E: Copy + Into<W>,
+          G: Copy + Into<u32>,
+{
+    if !utils::valid_cdf::<W, E>(cdf) {
+        return Err("Inversion1D cdf is empty, negative or decreasing");
+    }
+    // each bucket is a range of the cdf, so has to be within it, and not go backwards
+    if guide.len() == 1
+        || guide.iter().any(|g| (*g).into() as usize >= cdf.len())
+        || guide.windows(2).any(|pair| pair[0].into() > pair[1].into()) {
+        return Err("Inversion1D guide is out of range of the cdf");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl<W> utils::TableSize for Inversion1D<W> {
+    fn table_size(&self) -> usize {
+        self.cdf.len() - 1
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> utils::TableSize for ArchivedInversion1D<W> {
+    fn table_size(&self) -> usize {
+        self.cdf.len() - 1
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive + num_traits::Zero + PartialOrd> ArchivedValidate for ArchivedInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_cdf::<W, _, _>(&self.cdf, &self.guide)
+    }
+}
+
+// sampling is shared with ArchivedInversion1D, where cdf is Archived<W> rather than W
+fn sample_cdf<W, R, E, G>(cdf: &[E], guide: &[G], u: R) -> usize
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static,
+          E: Copy + Into<W>,
+          G: Copy + Into<u32>,
+          usize: AsPrimitive<R>,
+{
+    let at = |i: usize| -> W { cdf[i].into() };
+    let integral = at(cdf.len() - 1);
+    let point = (u * integral.as_()).as_();
+
+    // u of 1.0, or rounding up, lands past the end, which should still be the last non-zero weight
+    if point >= integral {
+        return cdf.partition_point(|p| Into::<W>::into(*p) < integral).saturating_sub(1);
+    }
+
+    if !guide.is_empty() {
+        let guide_size = guide.len() - 1;
+        let bucket = <R as AsPrimitive<usize>>::as_(u * guide_size.as_()).min(guide_size - 1);
+        let lo = Into::<u32>::into(guide[bucket]) as usize;
+        let hi = Into::<u32>::into(guide[bucket + 1]) as usize + 1;
+
+        // cutpoints are computed with the same rounding as point, so this should basically always hold,
+        // but check anyway so that results are always identical to the full search
+        if at(lo) <= point && (hi == cdf.len() || at(hi) > point) {
+            return lo + cdf[lo + 1..hi].partition_point(|p| Into::<W>::into(*p) <= point);
+        }
+    }
+
+    let offset = cdf.partition_point(|p| Into::<W>::into(*p) <= point) - 1;
+    offset
+}
+
+// sample_cdf on a 64 bit fixed point u, comparing it against the cdf exactly
+fn sample_bits_cdf<W, E, G>(cdf: &[E], guide: &[G], bits: u64) -> usize
+    where W: Num + PartialOrd + Copy,
+          E: Copy + Into<W>,
+          G: Copy + Into<u32>,
+{
+    let integral: W = cdf[cdf.len() - 1].into();
+    if integral.is_zero() {
+        return 0;
+    }
+    // entries at or below the point, that is, threshold of at most bits
+    let below = |p: &E| Into::<W>::into(*p).fixed_threshold(integral) <= bits as u128;
+
+    if !guide.is_empty() {
+        let guide_size = guide.len() - 1;
+        let bucket = utils::scale_bits(bits, guide_size).0;
+        let lo = Into::<u32>::into(guide[bucket]) as usize;
+        let hi = Into::<u32>::into(guide[bucket + 1]) as usize + 1;
+
+        // cutpoints are from floats, so check that the bucket holds the point
+        if below(&cdf[lo]) && (hi == cdf.len() || !below(&cdf[hi])) {
+            return lo + cdf[lo + 1..hi].partition_point(below);
+        }
+    }
+
+    cdf.partition_point(below) - 1
+}
+
+fn sample_remap_cdf<W, R, E, G>(cdf: &[E], guide: &[G], u: R) -> (usize, R)
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static,
+          E: Copy + Into<W>,
+          G: Copy + Into<u32>,
+          usize: AsPrimitive<R>,
+{
+    let at = |i: usize| -> R { Into::<W>::into(cdf[i]).as_() };
+    let offset = sample_cdf::<W, R, E, G>(cdf, guide, u);
+    let du = (u * at(cdf.len() - 1) - at(offset)) / (at(offset + 1) - at(offset));
+    (offset, utils::clamp_unit(du))
+}
+
+// continuous sampling on the cdf alone, so that it's shared with packed rows
+fn sample_continuous_cdf<W, R, G>(cdf: &[W], guide: &[G], u: R) -> R
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static,
+          G: Copy + Into<u32>,
+          usize: AsPrimitive<R>,
+{
+    let size = cdf.len() - 1;
+    let (offset, du) = sample_remap_cdf::<W, R, W, G>(cdf, guide, u);
+    // du just below 1 can round up to the next offset
+    utils::clamp_unit((offset.as_() + du) / size.as_())
+}
+
+fn sample_continuous_with_pdf_cdf<W, R, G>(cdf: &[W], guide: &[G], u: R) -> (R, R)
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static,
+          G: Copy + Into<u32>,
+          usize: AsPrimitive<R>,
+{
+    let size = cdf.len() - 1;
+    let (offset, du) = sample_remap_cdf::<W, R, W, G>(cdf, guide, u);
+    let pdf = (cdf[offset + 1] - cdf[offset]).as_() / cdf[size].as_() * size.as_();
+    (utils::clamp_unit((offset.as_() + du) / size.as_()), pdf)
+}
+
+fn invert_continuous_cdf<W, R>(cdf: &[W], u: R) -> R
+    where W: Num + AsPrimitive<R>,
+          R: Real + AsPrimitive<usize> + 'static,
+          usize: AsPrimitive<R>,
+{
+    let size = cdf.len() - 1;
+    let scaled: R = size.as_() * u;
+    let idx: usize = <R as AsPrimitive<usize>>::as_(scaled).min(size - 1);
+    let delta = scaled - idx.as_();
+    crate::utils::lerp(delta, cdf[idx].as_(), cdf[idx + 1].as_()) / cdf[size].as_()
+}
+
+fn pdf_continuous_cdf<W, R>(cdf: &[W], x: R) -> R
+    where W: Num + AsPrimitive<R>,
+          R: Real + AsPrimitive<usize> + 'static,
+          usize: AsPrimitive<R>,
+{
+    let size = cdf.len() - 1;
+    let idx = <R as AsPrimitive<usize>>::as_(x * size.as_()).min(size - 1);
+    (cdf[idx + 1] - cdf[idx]).as_() / cdf[size].as_() * size.as_()
+}
+
+// same as sample_cdf, a lane at a time, with a branchless binary search so that lanes stay in step
+// the guide isn't used, as it always gives the same result as the full search
+fn sample_cdf_lanes(cdf: &[f32], guide: &[u32], u: Lanes<f32>) -> Lanes<usize> {
+    let integral = cdf[cdf.len() - 1];
+    let point = u * Simd::splat(integral);
+
+    let mut offset = Simd::splat(0);
+    let mut len = cdf.len();
+    while len > 1 {
+        let half = len / 2;
+        let mid = offset + Simd::splat(half);
+        offset = Simd::gather_or_default(cdf, mid).simd_le(point).cast::<isize>().select(mid, offset);
+        len -= half;
+    }
+
+    // u of 1.0, or rounding up, is rare enough to leave to sample_cdf
+    let past = point.simd_ge(Simd::splat(integral));
+    if past.any() {
+        let mut offset = offset.to_array();
+        for (lane, offset) in offset.iter_mut().enumerate().filter(|(lane, _)| past.test(*lane)) {
+            *offset = sample_cdf::<f32, f32, _, _>(cdf, guide, u[lane]);
+        }
+        return Simd::from_array(offset);
+    }
+    offset
+}
+
+// same as sample_continuous, a lane at a time
+fn sample_continuous_cdf_lanes(cdf: &[f32], guide: &[u32], u: Lanes<f32>) -> Lanes<f32> {
+    let offset = sample_cdf_lanes(cdf, guide, u);
+    let start = Simd::gather_or_default(cdf, offset);
+    let end = Simd::gather_or_default(cdf, offset + Simd::splat(1));
+    let du = utils::clamp_unit_lanes((u * Simd::splat(cdf[cdf.len() - 1]) - start) / (end - start));
+    utils::clamp_unit_lanes((offset.cast::<f32>() + du) / Simd::splat((cdf.len() - 1) as f32))
+}
+
+// batches of Inversion1D, with SIMD for f32, and one at a time otherwise
+trait CdfBatch<R>: Sized {
+    fn sample_batch(dist: &Inversion1D<Self>, us: &[R], out: &mut [usize]);
+    fn sample_continuous_batch(dist: &Inversion1D<Self>, us: &[R], out: &mut [R]);
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> CdfBatch<R> for W
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    default fn sample_batch(dist: &Inversion1D<W>, us: &[R], out: &mut [usize]) {
+        utils::batch(us, out, |u| dist.sample(u));
+    }
+
+    default fn sample_continuous_batch(dist: &Inversion1D<W>, us: &[R], out: &mut [R]) {
+        utils::batch(us, out, |u| dist.sample_continuous(u));
+    }
+}
+
+impl CdfBatch<f32> for f32 {
+    fn sample_batch(dist: &Inversion1D<f32>, us: &[f32], out: &mut [usize]) {
+        utils::batch_lanes(us, out,
+            |u| sample_cdf_lanes(&dist.cdf, &dist.guide, Simd::from_array(u)).to_array(),
+            |u| dist.sample(u));
+    }
+
+    fn sample_continuous_batch(dist: &Inversion1D<f32>, us: &[f32], out: &mut [f32]) {
+        utils::batch_lanes(us, out,
+            |u| sample_continuous_cdf_lanes(&dist.cdf, &dist.guide, Simd::from_array(u)).to_array(),
+            |u| dist.sample_continuous(u));
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Discrete1D<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
     type Weight = W;
 
     fn build(weights: &[W]) -> Self {
@@ -30,13 +321,24 @@ impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> D
 
         Self {
             cdf,
+            guide: Box::default(),
         }
     }
 
     fn sample(&self, u: R) -> usize {
-        let point = (u * self.integral().as_()).as_();
-        let offset = self.cdf.partition_point(|p| *p <= point) - 1;
-        offset
+        sample_cdf::<W, R, _, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_cdf::<W, _, _>(&self.cdf, &self.guide, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_cdf::<W, R, _, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn sample_batch(&self, us: &[R], out: &mut [usize]) {
+        <W as CdfBatch<R>>::sample_batch(self, us, out);
     }
 
     fn integral(&self) -> W {
@@ -48,26 +350,508 @@ impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> D
     }
 }
 
-impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> Discrete1DPdf<R> for Inversion1D<W> {
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Discrete1DPdf<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
     fn pdf(&self, u: usize) -> W {
         self.cdf[u + 1] - self.cdf[u]
     }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let offset = self.sample(u);
+        (offset, (self.cdf[offset + 1] - self.cdf[offset]).as_() / self.integral().as_())
+    }
 }
 
 impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Continuous1D<R> for Inversion1D<W>
     where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
 {
     fn sample_continuous(&self, u: R) -> R {
+        sample_continuous_cdf::<W, R, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn sample_continuous_with_pdf(&self, u: R) -> (R, R) {
+        sample_continuous_with_pdf_cdf::<W, R, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn sample_continuous_batch(&self, us: &[R], out: &mut [R]) {
+        <W as CdfBatch<R>>::sample_continuous_batch(self, us, out);
+    }
+
+    fn invert_continuous(&self, u: R) -> R {
+        invert_continuous_cdf::<W, R>(&self.cdf, u)
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ContinuousPdf1D<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, x: R) -> R {
+        pdf_continuous_cdf::<W, R>(&self.cdf, x)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + AsPrimitive<R> + PartialOrd + rkyv::Archive, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ArchivedDiscrete1D<R> for ArchivedInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: R) -> usize {
+        sample_cdf::<W, R, _, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_cdf::<W, _, _>(&self.cdf, &self.guide, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_cdf::<W, R, _, _>(&self.cdf, &self.guide, u)
+    }
+
+    fn integral(&self) -> W {
+        (*self.cdf.last().unwrap()).into()
+    }
+
+    fn size(&self) -> usize {
+        self.cdf.len() - 1
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + AsPrimitive<R> + PartialOrd + rkyv::Archive, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ArchivedDiscrete1DPdf<R> for ArchivedInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.cdf[u + 1].into() - self.cdf[u].into()
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
         let offset = self.sample(u);
-        let du = (u * self.integral().as_() - self.cdf[offset].as_()) / (self.cdf[offset + 1].as_() - self.cdf[offset].as_());
-        (offset.as_() + du) / self.size().as_()
+        (offset, self.pdf(offset).as_() / self.integral().as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for Inversion1D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Inversion1D;
+
+    fn dimensions(&self) -> [u64; 2] {
+        [self.cdf.len() as u64 - 1, 1]
+    }
+}
+
+// guide isn't flattened, it only speeds up the search
+impl<W: AsPrimitive<f32>> FlatTable1D for Inversion1D<W> {
+    const ALGORITHM: flat::Algorithm = flat::Algorithm::Inversion2D;
+
+    fn flat_size(&self) -> usize {
+        self.cdf.len() - 1
+    }
+
+    fn write_flat(&self, buffer: &mut Vec<u32>) {
+        buffer.extend(self.cdf.iter().map(|&p| flat::weight_word(p)));
+    }
+}
+
+// a row is just the cdf, guide isn't packed, it only speeds up the search
+impl<W: Copy> PackedLayout for Inversion1D<W> {
+    type Entry = W;
+
+    fn stride(width: usize) -> usize {
+        width + 1
+    }
+
+    fn pack(&self, entries: &mut Vec<W>) {
+        entries.extend_from_slice(&self.cdf);
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> PackedRow<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn row_integral(row: &[W]) -> W {
+        row[row.len() - 1]
+    }
+
+    fn sample_row(row: &[W], u: R) -> usize {
+        sample_cdf::<W, R, W, u32>(row, &[], u)
+    }
+
+    fn sample_bits_row(row: &[W], bits: u64) -> usize {
+        sample_bits_cdf::<W, W, u32>(row, &[], bits)
+    }
+
+    fn sample_remap_row(row: &[W], u: R) -> (usize, R) {
+        sample_remap_cdf::<W, R, W, u32>(row, &[], u)
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> PackedRowPdf<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf_row(row: &[W], idx: usize) -> W {
+        row[idx + 1] - row[idx]
+    }
+
+    fn sample_with_pdf_row(row: &[W], u: R) -> (usize, R) {
+        let offset = <Self as PackedRow<R>>::sample_row(row, u);
+        (offset, (row[offset + 1] - row[offset]).as_() / row[row.len() - 1].as_())
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> PackedRowContinuous<R> for Inversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn sample_continuous_row(row: &[W], u: R) -> R {
+        sample_continuous_cdf::<W, R, u32>(row, &[], u)
+    }
+
+    fn sample_continuous_with_pdf_row(row: &[W], u: R) -> (R, R) {
+        sample_continuous_with_pdf_cdf::<W, R, u32>(row, &[], u)
+    }
+
+    fn invert_continuous_row(row: &[W], u: R) -> R {
+        invert_continuous_cdf::<W, R>(row, u)
+    }
+
+    fn pdf_continuous_row(row: &[W], x: R) -> R {
+        pdf_continuous_cdf::<W, R>(row, x)
+    }
+}
+
+impl<W: Num + PartialOrd + Copy> Inversion1D<W> {
+    // same as build, with the prefix sum split across threads into blocks, each offset by the sums before it
+    // only for integer weights, where the blocked sum is exact, so the cdf is identical to build's
+    #[cfg(feature = "rayon")]
+    pub fn par_build(weights: &[W]) -> Self
+        where W: num_traits::PrimInt + Send + Sync,
+    {
+        let mut cdf = core::iter::once(W::zero()).chain(weights.iter().cloned()).collect::<Box<[W]>>();
+        utils::par_prefix_sum(&mut cdf);
+
+        Self {
+            cdf,
+            guide: Box::default(),
+        }
+    }
+
+    // Chen and Asau's cutpoint method
+    // splits [0-1) into guide_size buckets, recording the range of the cdf each one may land in,
+    // so that sampling only needs to search within that range
+    // guide_size around the number of weights is a reasonable default
+    pub fn build_guide<R: Real + AsPrimitive<W> + 'static>(&mut self, guide_size: usize)
+        where W: AsPrimitive<R>,
+              usize: AsPrimitive<R>,
+    {
+        assert!(guide_size > 0, "guide table must have at least one bucket");
+        assert!(self.cdf.len() < u32::MAX as usize, "guide table doesn't work for distributions with more than or equal to u32::MAX elements");
+
+        let integral: R = self.cdf.last().unwrap().as_();
+        let mut guide = Vec::with_capacity(guide_size + 1);
+        let mut idx = 0;
+        for bucket in 0..=guide_size {
+            // same as the point sample computes for the smallest u in this bucket
+            let cutpoint: W = ((bucket.as_() / guide_size.as_()) * integral).as_();
+            while idx + 1 < self.cdf.len() && self.cdf[idx + 1] <= cutpoint {
+                idx += 1;
+            }
+            guide.push(idx as u32);
+        }
+
+        self.guide = guide.into_boxed_slice();
+    }
+}
+
+// same as Inversion1D, but with the cdf stored in Eytzinger (breadth-first) order,
+// which is much more cache friendly to binary search for large distributions
+// https://arxiv.org/abs/1509.05053
+//
+// padded to a perfect tree with copies of the last cdf entry, so that
+// the position a search ends at directly gives the number of entries <= point,
+// and the position of any cdf entry can be computed in O(1)
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "EytzingerInversion1DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + num_traits::Zero + PartialOrd + Copy")))]
+pub struct EytzingerInversion1D<W> {
+    pub tree: Box<[W]>, // 1-indexed, first element unused
+    pub size: usize,
+}
+
+// as serialized, checked before becoming an EytzingerInversion1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct EytzingerInversion1DFields<W> {
+    tree: Box<[W]>,
+    size: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<W: num_traits::Zero + PartialOrd + Copy> TryFrom<EytzingerInversion1DFields<W>> for EytzingerInversion1D<W> {
+    type Error = &'static str;
+
+    fn try_from(EytzingerInversion1DFields { tree, size }: EytzingerInversion1DFields<W>) -> Result<Self, Self::Error> {
+        check_tree::<W, _>(&tree, size)?;
+        Ok(Self { tree, size })
+    }
+}
+
+// shared by serde and ArchivedEytzingerInversion1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_tree<W: num_traits::Zero + PartialOrd + Copy, E: Copy + Into<W>>(tree: &[E], size: usize) -> Result<(), &'static str> {
+    if size.checked_add(2).and_then(usize::checked_next_power_of_two) != Some(tree.len()) {
+        return Err("EytzingerInversion1D tree length doesn't match its size");
+    }
+    // in cdf order, then padding, which has to compare the same as the last entry
+    let cdf = (0..tree.len() - 1).map(|idx| tree[tree_position(tree.len(), idx)].into()).collect::<Vec<W>>();
+    if !utils::valid_cdf::<W, W>(&cdf[..=size]) || cdf[size + 1..].iter().any(|p| *p != cdf[size]) {
+        return Err("EytzingerInversion1D cdf is negative or decreasing");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl<W> utils::TableSize for EytzingerInversion1D<W> {
+    fn table_size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive> utils::TableSize for ArchivedEytzingerInversion1D<W> {
+    fn table_size(&self) -> usize {
+        self.size.to_native() as usize
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: rkyv::Archive + num_traits::Zero + PartialOrd + Copy> ArchivedValidate for ArchivedEytzingerInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_tree::<W, _>(&self.tree, self.size.to_native() as usize)
+    }
+}
+
+impl<W> EytzingerInversion1D<W> {
+    // position in tree of cdf[idx]
+    fn position(&self, idx: usize) -> usize {
+        tree_position(self.tree.len(), idx)
+    }
+}
+
+// below are shared with ArchivedEytzingerInversion1D, where tree is Archived<W> rather than W
+
+fn tree_position(tree_len: usize, idx: usize) -> usize {
+    let height = tree_len.ilog2();
+    let trailing = (idx + 1).trailing_zeros();
+    (1 << (height - 1 - trailing)) + ((idx + 1) >> (trailing + 1))
+}
+
+// number of cdf entries pred holds for, pred should be true for some prefix of the cdf
+fn tree_search<W, E: Copy + Into<W>>(tree: &[E], size: usize, pred: impl Fn(W) -> bool) -> usize {
+    // branchless, always goes all the way down
+    let mut k = 1;
+    while k < tree.len() {
+        k = 2 * k + pred(tree[k].into()) as usize;
+    }
+
+    // padding compares the same as the last entry, so clamping gives the same as partition_point
+    (k - tree.len()).min(size + 1)
+}
+
+fn sample_tree<W, R, E>(tree: &[E], size: usize, u: R) -> usize
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + 'static,
+          E: Copy + Into<W>,
+{
+    let integral: W = tree[tree_position(tree.len(), size)].into();
+    let point = (u * integral.as_()).as_();
+
+    // same as Inversion1D, past the end should still be the last non-zero weight
+    if point >= integral {
+        return tree_search(tree, size, |p| p < integral).saturating_sub(1);
+    }
+
+    tree_search(tree, size, |p| p <= point) - 1
+}
+
+fn sample_bits_tree<W, E>(tree: &[E], size: usize, bits: u64) -> usize
+    where W: Num + PartialOrd + Copy,
+          E: Copy + Into<W>,
+{
+    let integral: W = tree[tree_position(tree.len(), size)].into();
+    if integral.is_zero() {
+        return 0;
+    }
+    tree_search(tree, size, |p: W| p.fixed_threshold(integral) <= bits as u128) - 1
+}
+
+fn sample_remap_tree<W, R, E>(tree: &[E], size: usize, u: R) -> (usize, R)
+    where W: Num + AsPrimitive<R> + PartialOrd,
+          R: Real + AsPrimitive<W> + 'static,
+          E: Copy + Into<W>,
+{
+    let at = |idx: usize| -> R { Into::<W>::into(tree[tree_position(tree.len(), idx)]).as_() };
+    let offset = sample_tree::<W, R, E>(tree, size, u);
+    let [lo, hi] = [at(offset), at(offset + 1)];
+    (offset, utils::clamp_unit((u * at(size) - lo) / (hi - lo)))
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for EytzingerInversion1D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::EytzingerInversion1D;
+
+    fn dimensions(&self) -> [u64; 2] {
+        [self.size as u64, 1]
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> Discrete1D<R> for EytzingerInversion1D<W>
+    where f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn build(weights: &[W]) -> Self {
+        let mut cdf = core::iter::once(W::zero()).chain(weights.iter().cloned()).collect::<Box<[W]>>();
+
+        for i in 1..cdf.len() {
+            cdf[i] = cdf[i - 1] + cdf[i];
+        }
+
+        let mut out = Self {
+            tree: vec![*cdf.last().unwrap(); (cdf.len() + 1).next_power_of_two()].into_boxed_slice(),
+            size: weights.len(),
+        };
+
+        for (idx, c) in cdf.iter().enumerate() {
+            let position = out.position(idx);
+            out.tree[position] = *c;
+        }
+
+        out
+    }
+
+    fn sample(&self, u: R) -> usize {
+        sample_tree::<W, R, _>(&self.tree, self.size, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_tree::<W, _>(&self.tree, self.size, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_tree::<W, R, _>(&self.tree, self.size, u)
+    }
+
+    fn integral(&self) -> W {
+        self.tree[self.position(self.size)]
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + AsPrimitive<R> + PartialOrd + rkyv::Archive, R: Real + AsPrimitive<W> + 'static> ArchivedDiscrete1D<R> for ArchivedEytzingerInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: R) -> usize {
+        sample_tree::<W, R, _>(&self.tree, self.size(), u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_tree::<W, _>(&self.tree, self.size(), bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_tree::<W, R, _>(&self.tree, self.size(), u)
+    }
+
+    fn integral(&self) -> W {
+        self.tree[tree_position(self.tree.len(), self.size())].into()
+    }
+
+    fn size(&self) -> usize {
+        self.size.to_native() as usize
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Num + AsPrimitive<R> + PartialOrd + rkyv::Archive, R: Real + AsPrimitive<W> + 'static> ArchivedDiscrete1DPdf<R> for ArchivedEytzingerInversion1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf(&self, u: usize) -> W {
+        let position = |idx| tree_position(self.tree.len(), idx);
+        self.tree[position(u + 1)].into() - self.tree[position(u)].into()
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let offset = self.sample(u);
+        (offset, self.pdf(offset).as_() / self.integral().as_())
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + 'static> Discrete1DPdf<R> for EytzingerInversion1D<W>
+    where f64: AsPrimitive<R>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.tree[self.position(u + 1)] - self.tree[self.position(u)]
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let offset = self.sample(u);
+        (offset, (self.tree[self.position(offset + 1)] - self.tree[self.position(offset)]).as_() / self.integral().as_())
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Continuous1D<R> for EytzingerInversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn sample_continuous(&self, u: R) -> R {
+        let (offset, du) = self.sample_remap(u);
+        utils::clamp_unit((offset.as_() + du) / self.size.as_())
+    }
+
+    fn sample_continuous_with_pdf(&self, u: R) -> (R, R) {
+        let (offset, du) = self.sample_remap(u);
+        let pdf = (self.tree[self.position(offset + 1)] - self.tree[self.position(offset)]).as_() / self.integral().as_() * self.size.as_();
+        (utils::clamp_unit((offset.as_() + du) / self.size.as_()), pdf)
     }
 
     fn invert_continuous(&self, u: R) -> R {
-        let scaled: R = self.size().as_() * u;
-        let idx: usize = scaled.as_();
+        let scaled: R = self.size.as_() * u;
+        let idx: usize = <R as AsPrimitive<usize>>::as_(scaled).min(self.size - 1);
         let delta = scaled - idx.as_();
-        crate::utils::lerp(delta, self.cdf[idx].as_(), self.cdf[idx + 1].as_()) / self.integral().as_()
+        crate::utils::lerp(delta, self.tree[self.position(idx)].as_(), self.tree[self.position(idx + 1)].as_()) / self.integral().as_()
+    }
+}
+
+impl<W: Num + AsPrimitive<R> + PartialOrd, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ContinuousPdf1D<R> for EytzingerInversion1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, x: R) -> R {
+        let idx = <R as AsPrimitive<usize>>::as_(x * self.size.as_()).min(self.size - 1);
+        (self.tree[self.position(idx + 1)] - self.tree[self.position(idx)]).as_() / self.integral().as_() * self.size.as_()
     }
 }
 
@@ -78,5 +862,232 @@ mod tests {
 
     distribution_1d_tests!(crate::inversion::Inversion1D);
     continuous_distribution_1d_tests!(crate::inversion::Inversion1D);
-}
 
+    #[test]
+    fn sample_with_pdf() {
+        crate::distribution::test_sample_with_pdf_1d::<crate::inversion::Inversion1D<f64>>(&[1.0, 0.0, 2.0, 4.0, 8.0], 1000);
+        crate::distribution::test_sample_with_pdf_1d::<crate::inversion::EytzingerInversion1D<f64>>(&[1.0, 0.0, 2.0, 4.0, 8.0], 1000);
+    }
+
+    // with a float u, 1/3 can't be told apart from the f32 just below it
+    #[test]
+    fn sample_bits_exact() {
+        use crate::distribution::Discrete1D;
+        use crate::inversion::{Inversion1D, EytzingerInversion1D};
+        let weights = [1.0, 1.0, 1.0, 3.0, 5.0];
+        let masses = [1, 1, 1, 3, 5];
+        crate::distribution::test_sample_bits_exact_1d::<f32, _>(&<Inversion1D<f32> as Discrete1D<f32>>::build(&weights), &masses);
+        crate::distribution::test_sample_bits_exact_1d::<f32, _>(&<EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights), &masses);
+
+        let mut guided = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        guided.build_guide::<f32>(4);
+        crate::distribution::test_sample_bits_exact_1d::<f32, _>(&guided, &masses);
+    }
+
+    #[test]
+    fn chisq_pdf() {
+        use crate::distribution::Discrete1D;
+        use crate::inversion::{Inversion1D, EytzingerInversion1D};
+        let weights = [1, 0, 2, 4, 8, 100];
+        crate::distribution::chisq_distribution_1d_pdf(&<Inversion1D<usize> as Discrete1D<f64>>::build(&weights), &weights, 100_000);
+        crate::distribution::chisq_distribution_1d_pdf(&<EytzingerInversion1D<usize> as Discrete1D<f64>>::build(&weights), &weights, 100_000);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use crate::distribution::Discrete1D;
+        use crate::inversion::{
+            Inversion1D,
+            ArchivedInversion1D,
+            EytzingerInversion1D,
+            ArchivedEytzingerInversion1D,
+        };
+        use rkyv::rancor::Error;
+
+        let weights = [1.0f32, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0];
+        let mut dist = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, crate::distribution::access_checked::<ArchivedInversion1D<f32>>(&bytes).unwrap());
+
+        dist.build_guide::<f32>(4);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, crate::distribution::access_checked::<ArchivedInversion1D<f32>>(&bytes).unwrap());
+
+        let dist = <EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, crate::distribution::access_checked::<ArchivedEytzingerInversion1D<f32>>(&bytes).unwrap());
+    }
+
+    // untrusted bytes are checked by access_checked, both their layout and the tables they hold,
+    // rather than read out of bounds while sampling
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_invalid() {
+        use crate::distribution::{Discrete1D, access_checked, test_archived_invalid};
+        use crate::inversion::{Inversion1D, ArchivedInversion1D, EytzingerInversion1D};
+        use rkyv::rancor::Error;
+
+        let dist = <Inversion1D<f32> as Discrete1D<f32>>::build(&[1.0, 2.0, 3.0]);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        assert!(access_checked::<ArchivedInversion1D<f32>>(&bytes).is_ok());
+        assert!(access_checked::<ArchivedInversion1D<f32>>(&bytes[..bytes.len() / 2]).is_err());
+
+        // root is at the end, cdf is a relative pointer then length, followed by guide
+        let mut corrupt = bytes.clone();
+        let len = corrupt.len();
+        corrupt[len - 12..len - 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(access_checked::<ArchivedInversion1D<f32>>(&corrupt).is_err());
+
+        // well formed, but decreasing cdf, or a guide past its end
+        test_archived_invalid(&dist, &Inversion1D { cdf: [0.0, 3.0, 1.0, 6.0].into(), guide: Box::default() });
+        test_archived_invalid(&dist, &Inversion1D { cdf: dist.cdf.clone(), guide: [0, 2, 9].into() });
+
+        let dist = <EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&[1.0, 2.0, 3.0]);
+        test_archived_invalid(&dist, &EytzingerInversion1D { tree: dist.tree.clone(), size: 9 });
+        let mut tree = dist.tree.clone();
+        tree[1] = -1.0;
+        test_archived_invalid(&dist, &EytzingerInversion1D { tree, size: dist.size });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::distribution::{Discrete1D, test_serde_1d, test_serde_invalid};
+        use crate::inversion::{Inversion1D, EytzingerInversion1D};
+
+        let weights = [1.0f32, 0.0, 2.0, 4.0, 8.0, 0.0, 3.0];
+        let mut dist = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| json["cdf"][3] = 0.5.into());
+        test_serde_invalid(&dist, |json| json["cdf"] = serde_json::json!([]));
+
+        dist.build_guide::<f32>(4);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| json["guide"][2] = 8.into());
+        test_serde_invalid(&dist, |json| json["guide"][2] = 0.into());
+
+        let dist = <EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| json["size"] = 100.into());
+        test_serde_invalid(&dist, |json| json["tree"][1] = (-1.0).into());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_build() {
+        use crate::distribution::Discrete1D;
+        use crate::inversion::Inversion1D;
+        use crate::utils::PREFIX_BLOCK;
+
+        for size in [1, 7, PREFIX_BLOCK - 1, PREFIX_BLOCK, 3 * PREFIX_BLOCK + 5] {
+            let weights = (0..size).map(|i| ((i * 7919) % 13) as u64).collect::<Vec<_>>();
+            let dist = <Inversion1D<u64> as Discrete1D<f32>>::build(&weights);
+            assert_eq!(Inversion1D::par_build(&weights).cdf, dist.cdf);
+
+            let weights = weights.iter().map(|&w| w as u32).collect::<Vec<_>>();
+            let dist = <Inversion1D<u32> as Discrete1D<f32>>::build(&weights);
+            assert_eq!(Inversion1D::par_build(&weights).cdf, dist.cdf);
+        }
+    }
+
+    mod eytzinger {
+        use crate::distribution::distribution_1d_tests;
+        use crate::distribution::continuous_distribution_1d_tests;
+        use crate::distribution::{
+            Discrete1D,
+            Discrete1DPdf,
+        };
+        use crate::inversion::{
+            Inversion1D,
+            EytzingerInversion1D,
+        };
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        distribution_1d_tests!(crate::inversion::EytzingerInversion1D);
+        continuous_distribution_1d_tests!(crate::inversion::EytzingerInversion1D);
+
+        #[test]
+        fn matching_linear() {
+            let mut rng = StdRng::seed_from_u64(0);
+            for size in [1, 2, 3, 6, 7, 8, 100, 1023, 1024, 5000] {
+                let weights = (0..size).map(|i| ((i * 7919) % 13) as f32).collect::<Vec<_>>();
+                let linear = <Inversion1D<f32> as Discrete1D<f32>>::build(&weights);
+                let eytzinger = <EytzingerInversion1D<f32> as Discrete1D<f32>>::build(&weights);
+                assert_eq!(Discrete1D::<f32>::integral(&linear), Discrete1D::<f32>::integral(&eytzinger));
+                for i in 0..size {
+                    assert_eq!(Discrete1DPdf::<f32>::pdf(&linear, i), Discrete1DPdf::<f32>::pdf(&eytzinger, i));
+                }
+                for _ in 0..10_000 {
+                    let u = rng.r#gen::<f32>();
+                    assert_eq!(Discrete1D::<f32>::sample(&linear, u), Discrete1D::<f32>::sample(&eytzinger, u));
+                }
+                for u in [0.0, 1.0 - f32::EPSILON / 2.0, 1.0] {
+                    assert_eq!(Discrete1D::<f32>::sample(&linear, u), Discrete1D::<f32>::sample(&eytzinger, u));
+                }
+            }
+        }
+    }
+
+    mod guide {
+        use crate::distribution::{
+            Discrete1D,
+            Continuous1D,
+        };
+        use crate::inversion::Inversion1D;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        fn test_guide_matching<W: num_traits::Num + PartialOrd + Copy + num_traits::AsPrimitive<f32> + 'static>(weights: &[W], guide_size: usize)
+            where f32: num_traits::AsPrimitive<W>,
+        {
+            let plain = <Inversion1D<W> as Discrete1D<f32>>::build(weights);
+            let mut guided = <Inversion1D<W> as Discrete1D<f32>>::build(weights);
+            guided.build_guide::<f32>(guide_size);
+
+            let mut rng = StdRng::seed_from_u64(0);
+            let coherent = (0..10_000).map(|i| i as f32 / 10_000.0);
+            let incoherent = (0..10_000).map(|_| rng.r#gen::<f32>());
+            let edges = [0.0, f32::MIN_POSITIVE, 0.5, 1.0 - f32::EPSILON / 2.0];
+            for u in coherent.chain(incoherent).chain(edges) {
+                assert_eq!(Discrete1D::<f32>::sample(&plain, u), Discrete1D::<f32>::sample(&guided, u));
+            }
+        }
+
+        #[test]
+        fn matching_basic() {
+            test_guide_matching::<usize>(&[1, 1, 2, 4, 8], 5);
+            test_guide_matching::<usize>(&[1, 1, 2, 4, 8], 1);
+            test_guide_matching::<usize>(&[0, 3, 0, 0, 8, 0], 64);
+        }
+
+        #[test]
+        fn matching_increasing() {
+            let mut distr = [0.0f32; 1000];
+            for (i, weight) in distr.iter_mut().enumerate() {
+                *weight = (5 * (i + 1)) as f32;
+            }
+            test_guide_matching(&distr, 1000);
+            test_guide_matching(&distr, 37);
+        }
+
+        #[test]
+        fn matching_spiky() {
+            let mut distr = [0.001f32; 4096];
+            distr[17] = 1000.0;
+            distr[4000] = 50.0;
+            test_guide_matching(&distr, 4096);
+        }
+
+        #[test]
+        fn matching_continuous() {
+            let weights = [1.0f64, 1.0, 2.0, 4.0, 8.0];
+            let plain = <Inversion1D<f64> as Discrete1D<f64>>::build(&weights);
+            let mut guided = <Inversion1D<f64> as Discrete1D<f64>>::build(&weights);
+            guided.build_guide::<f64>(8);
+            for i in 0..1000 {
+                let u = i as f64 / 1000.0;
+                assert_eq!(plain.sample_continuous(u).to_bits(), guided.sample_continuous(u).to_bits());
+            }
+        }
+    }
+}
@@ -4,6 +4,9 @@
 #![feature(int_roundings)]
 #![feature(min_specialization)]
 
+// for batched sampling
+#![feature(portable_simd)]
+
 // for fast math (SIMD float sum)
 #![allow(internal_features)]
 #![feature(core_intrinsics)]
@@ -15,22 +18,69 @@ mod utils;
 
 pub mod distribution;
 
+#[cfg(feature = "rkyv")]
+pub mod container;
+
+pub mod flat;
+
 mod data2d;
 pub use data2d::Data2D;
+#[cfg(feature = "rkyv")]
+pub use data2d::ArchivedData2D;
 
 mod inversion;
 pub use inversion::Inversion1D;
 pub use inversion::Inversion2D;
+pub use inversion::EytzingerInversion1D;
+pub use inversion::EytzingerInversion2D;
+pub use inversion::PackedInversion2D;
+#[cfg(feature = "rkyv")]
+pub use inversion::ArchivedInversion1D;
+#[cfg(feature = "rkyv")]
+pub use inversion::ArchivedEytzingerInversion1D;
 
 mod alias;
 pub use alias::Alias1D;
 pub use alias::Alias2D;
 pub use alias::ContinuousAlias1D;
 pub use alias::ContinuousAlias2D;
+pub use alias::PackedAlias2D;
+pub use alias::Alias1DWithPdf;
+pub use alias::Alias2DWithPdf;
+pub use alias::PackedAlias2DWithPdf;
+pub use alias::ContinuousAlias1DWithPdf;
+pub use alias::ContinuousAlias2DWithPdf;
+#[cfg(feature = "rkyv")]
+pub use alias::ArchivedAlias1D;
+#[cfg(feature = "rkyv")]
+pub use alias::ArchivedContinuousAlias1D;
+#[cfg(feature = "rkyv")]
+pub use alias::ArchivedAlias1DWithPdf;
+#[cfg(feature = "rkyv")]
+pub use alias::ArchivedContinuousAlias1DWithPdf;
 
 mod adapter2d;
 pub use adapter2d::Adapter2D;
+#[cfg(feature = "rkyv")]
+pub use adapter2d::ArchivedAdapter2D;
+
+mod packed2d;
+pub use packed2d::Packed2D;
+pub use packed2d::PackedLayout;
+pub use packed2d::PackedRow;
+pub use packed2d::PackedRowPdf;
+pub use packed2d::PackedRowContinuous;
 
 mod hierarchical;
 pub use hierarchical::Hierarchical1D;
 pub use hierarchical::Hierarchical2D;
+#[cfg(feature = "rkyv")]
+pub use hierarchical::ArchivedHierarchical1D;
+#[cfg(feature = "rkyv")]
+pub use hierarchical::ArchivedHierarchical2D;
+
+mod linear;
+pub use linear::PiecewiseLinear1D;
+pub use linear::Bilinear2D;
+#[cfg(feature = "rkyv")]
+pub use linear::ArchivedPiecewiseLinear1D;
@@ -0,0 +1,958 @@
+use crate::distribution::{
+    Discrete1D,
+    Discrete1DPdf,
+    Discrete2D,
+    Discrete2DPdf,
+    Continuous1D,
+    Continuous2D,
+    ContinuousPdf1D,
+    ContinuousPdf2D,
+    RandomBits,
+    BuildError,
+};
+#[cfg(feature = "rkyv")]
+use crate::distribution::{
+    ArchivedDiscrete1D,
+    ArchivedDiscrete1DPdf,
+    ArchivedValidate,
+};
+#[cfg(feature = "rkyv")]
+use crate::container::{
+    Algorithm,
+    Storable,
+    StorableWeight,
+};
+use crate::data2d::Data2D;
+use crate::utils::{
+    self,
+    lerp,
+    FixedPoint,
+};
+use num_traits::{
+    real::Real,
+    AsPrimitive,
+};
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    boxed::Box,
+    vec::Vec,
+    vec,
+};
+
+// samples t in [0-1) proportional to lerp(t, a, b)
+// from pbrt, more numerically stable than solving the quadratic directly
+pub(crate) fn sample_linear<R: Real>(u: R, [a, b]: [R; 2]) -> R {
+    if u == R::zero() && a == R::zero() {
+        return R::zero();
+    }
+    let x = u * (a + b) / (a + lerp(u, a * a, b * b).sqrt());
+    x.min(R::one() - R::epsilon() / (R::one() + R::one()))
+}
+
+// inverse of above
+pub(crate) fn invert_linear<R: Real>(t: R, [a, b]: [R; 2]) -> R {
+    if a + b == R::zero() {
+        return t;
+    }
+    t * (a * (R::one() + R::one() - t) + b * t) / (a + b)
+}
+
+// 1D piecewise linear distribution
+// weights are values at evenly spaced vertices, so n weights make n - 1 segments,
+// and the density is the linear interpolation between them
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PiecewiseLinear1DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + Real")))]
+pub struct PiecewiseLinear1D<W> {
+    pub values: Box<[W]>,
+    pub cdf: Box<[W]>, // running sum of segment (trapezoid) areas
+}
+
+// as serialized, checked before becoming a PiecewiseLinear1D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct PiecewiseLinear1DFields<W> {
+    values: Box<[W]>,
+    cdf: Box<[W]>,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> TryFrom<PiecewiseLinear1DFields<W>> for PiecewiseLinear1D<W> {
+    type Error = &'static str;
+
+    fn try_from(PiecewiseLinear1DFields { values, cdf }: PiecewiseLinear1DFields<W>) -> Result<Self, Self::Error> {
+        check_segments::<W, _>(values.len(), &cdf)?;
+        Ok(Self { values, cdf })
+    }
+}
+
+// shared by serde and ArchivedPiecewiseLinear1D
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_segments<W: Real, E: Copy + Into<W>>(vertices: usize, cdf: &[E]) -> Result<(), &'static str> {
+    if vertices < 2 || vertices != cdf.len() {
+        return Err("PiecewiseLinear1D needs a cdf entry for each of at least two vertices");
+    }
+    if !utils::valid_cdf::<W, E>(cdf) {
+        return Err("PiecewiseLinear1D cdf is negative or decreasing");
+    }
+    Ok(())
+}
+
+impl<W> PiecewiseLinear1D<W> {
+    // between each pair of vertices, what size() returns, as the weights are one more
+    pub fn segments(&self) -> usize {
+        self.cdf.len() - 1
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W> utils::TableSize for PiecewiseLinear1D<W> {
+    fn table_size(&self) -> usize {
+        self.segments()
+    }
+}
+
+// sampling is shared with ArchivedPiecewiseLinear1D, where cdf is Archived<W> rather than W
+fn sample_segment<W, R, E>(cdf: &[E], u: R) -> usize
+    where W: Real + AsPrimitive<R>,
+          R: Real + AsPrimitive<W> + 'static,
+          E: Copy + Into<W>,
+{
+    let integral: W = (*cdf.last().unwrap()).into();
+    let point = (u * integral.as_()).as_();
+
+    // past the end should still be the last non-zero segment
+    if point >= integral {
+        return cdf.partition_point(|p| Into::<W>::into(*p) < integral).saturating_sub(1);
+    }
+
+    cdf.partition_point(|p| Into::<W>::into(*p) <= point) - 1
+}
+
+// sample_segment on a 64 bit fixed point u, comparing it against the cdf exactly
+fn sample_bits_segment<W, E>(cdf: &[E], bits: u64) -> usize
+    where W: Real,
+          E: Copy + Into<W>,
+{
+    let integral: W = (*cdf.last().unwrap()).into();
+    if integral.is_zero() {
+        return 0;
+    }
+    cdf.partition_point(|p| Into::<W>::into(*p).fixed_threshold(integral) <= bits as u128) - 1
+}
+
+fn sample_remap_segment<W, R, E>(cdf: &[E], u: R) -> (usize, R)
+    where W: Real + AsPrimitive<R>,
+          R: Real + AsPrimitive<W> + 'static,
+          E: Copy + Into<W>,
+{
+    let at = |i: usize| -> R { Into::<W>::into(cdf[i]).as_() };
+    let offset = sample_segment::<W, R, E>(cdf, u);
+    let du = (u * at(cdf.len() - 1) - at(offset)) / (at(offset + 1) - at(offset));
+    (offset, utils::clamp_unit(du))
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + 'static> Discrete1D<R> for PiecewiseLinear1D<W>
+    where f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn build(weights: &[W]) -> Self {
+        assert!(weights.len() >= 2, "PiecewiseLinear1D needs at least two vertices");
+
+        let mut cdf = vec![W::zero(); weights.len()].into_boxed_slice();
+
+        for i in 1..cdf.len() {
+            cdf[i] = cdf[i - 1] + (weights[i - 1] + weights[i]) / (W::one() + W::one());
+        }
+
+        Self {
+            values: weights.into(),
+            cdf,
+        }
+    }
+
+    fn try_build(weights: &[W]) -> Result<Self, BuildError> {
+        utils::validate_weights(weights.iter().copied())?;
+        if weights.len() < 2 {
+            return Err(BuildError::Empty);
+        }
+        Ok(<Self as Discrete1D<R>>::build(weights))
+    }
+
+    // returns sampled segment
+    fn sample(&self, u: R) -> usize {
+        sample_segment::<W, R, _>(&self.cdf, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_segment::<W, _>(&self.cdf, bits.to_u64())
+    }
+
+    // remapped u is where in the segment's area it lands
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_segment::<W, R, _>(&self.cdf, u)
+    }
+
+    fn integral(&self) -> W {
+        *self.cdf.last().unwrap()
+    }
+
+    // sampled idxs are segments, so this is one less than the number of weights
+    fn size(&self) -> usize {
+        self.segments()
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + 'static> Discrete1DPdf<R> for PiecewiseLinear1D<W>
+    where f64: AsPrimitive<R>,
+{
+    // area of the segment
+    fn pdf(&self, u: usize) -> W {
+        self.cdf[u + 1] - self.cdf[u]
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let offset = self.sample(u);
+        (offset, (self.cdf[offset + 1] - self.cdf[offset]).as_() / self.integral().as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<W> + 'static> ArchivedDiscrete1D<R> for ArchivedPiecewiseLinear1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn sample(&self, u: R) -> usize {
+        sample_segment::<W, R, _>(&self.cdf, u)
+    }
+
+    fn sample_bits<B: RandomBits>(&self, bits: B) -> usize {
+        sample_bits_segment::<W, _>(&self.cdf, bits.to_u64())
+    }
+
+    fn sample_remap(&self, u: R) -> (usize, R) {
+        sample_remap_segment::<W, R, _>(&self.cdf, u)
+    }
+
+    fn integral(&self) -> W {
+        (*self.cdf.last().unwrap()).into()
+    }
+
+    fn size(&self) -> usize {
+        self.cdf.len() - 1
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + AsPrimitive<R> + rkyv::Archive, R: Real + AsPrimitive<W> + 'static> ArchivedDiscrete1DPdf<R> for ArchivedPiecewiseLinear1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf(&self, u: usize) -> W {
+        self.cdf[u + 1].into() - self.cdf[u].into()
+    }
+
+    fn sample_with_pdf(&self, u: R) -> (usize, R) {
+        let offset = self.sample(u);
+        (offset, self.pdf(offset).as_() / self.integral().as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> ArchivedValidate for ArchivedPiecewiseLinear1D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        check_segments::<W, _>(self.values.len(), &self.cdf)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for PiecewiseLinear1D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::PiecewiseLinear1D;
+
+    // in segments, same as size
+    fn dimensions(&self) -> [u64; 2] {
+        [self.segments() as u64, 1]
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Continuous1D<R> for PiecewiseLinear1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn sample_continuous(&self, u: R) -> R {
+        self.sample_continuous_with_pdf(u).0
+    }
+
+    fn sample_continuous_with_pdf(&self, u: R) -> (R, R) {
+        let (offset, du) = self.sample_remap(u);
+        let values = [self.values[offset].as_(), self.values[offset + 1].as_()];
+        let t = sample_linear(du, values);
+        let pdf = lerp(t, values[0], values[1]) * self.size().as_() / self.integral().as_();
+        (utils::clamp_unit((offset.as_() + t) / self.size().as_()), pdf)
+    }
+
+    fn invert_continuous(&self, u: R) -> R {
+        let scaled: R = self.size().as_() * u;
+        let idx: usize = <R as AsPrimitive<usize>>::as_(scaled).min(self.size() - 1);
+        let t = invert_linear(scaled - idx.as_(), [self.values[idx].as_(), self.values[idx + 1].as_()]);
+        lerp(t, self.cdf[idx].as_(), self.cdf[idx + 1].as_()) / self.integral().as_()
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ContinuousPdf1D<R> for PiecewiseLinear1D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, u: R) -> R {
+        let size = self.size();
+        let scaled: R = size.as_() * u;
+        let idx: usize = <R as AsPrimitive<usize>>::as_(scaled).min(size - 1);
+        let value = lerp(scaled - idx.as_(), self.values[idx].as_(), self.values[idx + 1].as_());
+        value * size.as_() / self.integral().as_()
+    }
+}
+
+// 2D bilinear distribution
+// weights are values at vertices of a grid, so a width x height grid makes
+// (width - 1) x (height - 1) cells, and the density is the bilinear interpolation between them
+//
+// as the integral of each row of vertices is linear in y, the marginal is itself piecewise linear,
+// and as the cdf of a row is linear in its values, the conditional cdf at any y is
+// just a lerp of the cdfs of the rows of vertices above and below
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Bilinear2DFields<W>", bound(deserialize = "W: serde::Deserialize<'de> + Real")))]
+pub struct Bilinear2D<W> {
+    pub marginal: PiecewiseLinear1D<W>,
+    pub conditional: Box<[PiecewiseLinear1D<W>]>, // one per row of vertices
+}
+
+// as serialized, checked before becoming a Bilinear2D
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound(deserialize = "W: serde::Deserialize<'de> + Real"))]
+struct Bilinear2DFields<W> {
+    marginal: PiecewiseLinear1D<W>,
+    conditional: Box<[PiecewiseLinear1D<W>]>,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Real> TryFrom<Bilinear2DFields<W>> for Bilinear2D<W> {
+    type Error = &'static str;
+
+    fn try_from(Bilinear2DFields { marginal, conditional }: Bilinear2DFields<W>) -> Result<Self, Self::Error> {
+        check_grid(marginal.values.len(), conditional.iter().map(|row| row.values.len()))?;
+        Ok(Self { marginal, conditional })
+    }
+}
+
+// shared by serde and ArchivedBilinear2D, the tables themselves are checked on their own
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+fn check_grid(marginal: usize, mut rows: impl ExactSizeIterator<Item = usize>) -> Result<(), &'static str> {
+    // marginal has a vertex per row of vertices
+    if marginal != rows.len() {
+        return Err("Bilinear2D marginal doesn't match its rows");
+    }
+    let first = rows.next();
+    if rows.any(|row| Some(row) != first) {
+        return Err("Bilinear2D rows differ in width");
+    }
+    Ok(())
+}
+
+// first idx in [0, len) for which pred is false
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let mut lo = 0;
+    let mut hi = len;
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Discrete2D<R> for Bilinear2D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    type Weight = W;
+
+    fn build(weights: &Data2D<W>) -> Self {
+        assert!(weights.height() >= 2, "Bilinear2D needs at least two rows of vertices");
+
+        let mut conditional = Vec::with_capacity(weights.height());
+        let mut marginal_weights = Vec::with_capacity(weights.height());
+
+        for row in weights.iter() {
+            let table = <PiecewiseLinear1D<W> as Discrete1D<R>>::build(row);
+            marginal_weights.push(<PiecewiseLinear1D<W> as Discrete1D<R>>::integral(&table));
+            conditional.push(table);
+        }
+
+        let marginal = <PiecewiseLinear1D<W> as Discrete1D<R>>::build(&marginal_weights);
+
+        Self {
+            marginal,
+            conditional: conditional.into_boxed_slice(),
+        }
+    }
+
+    fn try_build(weights: &Data2D<W>) -> Result<Self, BuildError> {
+        utils::validate_weights(weights.iter().flatten().copied())?;
+        if weights.width() < 2 || weights.height() < 2 {
+            return Err(BuildError::Empty);
+        }
+        Ok(<Self as Discrete2D<R>>::build(weights))
+    }
+
+    // returns sampled cell
+    fn sample(&self, uv: [R; 2]) -> [usize; 2] {
+        self.sample_cell(uv).0
+    }
+
+    fn sample_bits<B: RandomBits>(&self, [u, v]: [B; 2]) -> [usize; 2] {
+        // a cell's mass is the sum of its segments in the rows above and below, so as with the marginal,
+        // the sum of the two rows' cdfs is a cdf over the cells in row j
+        let j = sample_bits_segment::<W, _>(&self.marginal.cdf, v.to_u64());
+        let [lo, hi] = [&self.conditional[j], &self.conditional[j + 1]];
+        let total = *lo.cdf.last().unwrap() + *hi.cdf.last().unwrap();
+        if total.is_zero() {
+            return [0, j];
+        }
+        let width = self.conditional[0].segments();
+        let i = partition_point(width + 1, |k| (lo.cdf[k] + hi.cdf[k]).fixed_threshold(total) <= u.to_u64() as u128) - 1;
+        [i, j]
+    }
+
+    // remapped uv is where in the cell's density the continuous sample lands,
+    // with v from the cell's marginal in y, and u from its conditional in x given y
+    fn sample_remap(&self, uv: [R; 2]) -> ([usize; 2], [R; 2]) {
+        let ([i, j], [t_x, t_y], _) = self.sample_cell(uv);
+
+        let (lo, hi, t) = self.rows_at(j, t_y);
+        let values = [
+            lerp(t, lo.values[i].as_(), hi.values[i].as_()),
+            lerp(t, lo.values[i + 1].as_(), hi.values[i + 1].as_()),
+        ];
+        let u = invert_linear(t_x, values);
+
+        let column: [R; 2] = [(lo.values[i] + lo.values[i + 1]).as_(), (hi.values[i] + hi.values[i + 1]).as_()];
+        let v = invert_linear(t_y, column);
+
+        ([i, j], [utils::clamp_unit(u), utils::clamp_unit(v)])
+    }
+
+    fn integral(&self) -> W {
+        <PiecewiseLinear1D<W> as Discrete1D<R>>::integral(&self.marginal)
+    }
+
+    fn width(&self) -> usize {
+        self.conditional[0].segments()
+    }
+
+    fn height(&self) -> usize {
+        self.conditional.len() - 1
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Discrete2DPdf<R> for Bilinear2D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    // area of the cell
+    fn pdf(&self, [u, v]: [usize; 2]) -> W {
+        let [lo, hi] = [&self.conditional[v], &self.conditional[v + 1]];
+        let four = W::one() + W::one() + W::one() + W::one();
+        (lo.values[u] + lo.values[u + 1] + hi.values[u] + hi.values[u + 1]) / four
+    }
+
+    fn sample_with_pdf(&self, uv: [R; 2]) -> ([usize; 2], R) {
+        let idx = self.sample(uv);
+        let pdf: R = <Self as Discrete2DPdf<R>>::pdf(self, idx).as_();
+        (idx, pdf / self.integral().as_())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: Real + rkyv::Archive> ArchivedValidate for ArchivedBilinear2D<W>
+    where rkyv::Archived<W>: Copy + Into<W>,
+{
+    fn validate(&self) -> Result<(), &'static str> {
+        self.marginal.validate()?;
+        self.conditional.iter().try_for_each(ArchivedValidate::validate)?;
+        check_grid(self.marginal.values.len(), self.conditional.iter().map(|row| row.values.len()))
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<W: StorableWeight> Storable for Bilinear2D<W> {
+    type Weight = W;
+    const ALGORITHM: Algorithm = Algorithm::Bilinear2D;
+
+    // in cells, same as width and height
+    fn dimensions(&self) -> [u64; 2] {
+        [self.conditional[0].segments() as u64, self.conditional.len() as u64 - 1]
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> Continuous2D<R> for Bilinear2D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn sample_continuous(&self, uv: [R; 2]) -> [R; 2] {
+        self.sample_continuous_with_pdf(uv).0
+    }
+
+    fn sample_continuous_with_pdf(&self, uv: [R; 2]) -> ([R; 2], R) {
+        let ([i, j], [t_x, t_y], pdf) = self.sample_cell(uv);
+        let x = utils::clamp_unit((i.as_() + t_x) / self.width().as_());
+        let y = utils::clamp_unit((j.as_() + t_y) / self.height().as_());
+
+        ([x, y], pdf)
+    }
+
+    fn invert_continuous(&self, [x, y]: [R; 2]) -> [R; 2] {
+        let v = self.marginal.invert_continuous(y);
+        let (lo, hi, t) = self.rows(y);
+        let cdf = |i: usize| lerp(t, lo.cdf[i].as_(), hi.cdf[i].as_());
+
+        let width = self.width();
+        // zero rows never get sampled, so have nothing to invert
+        if cdf(width) == R::zero() {
+            return [x, v];
+        }
+
+        let scaled = x * width.as_();
+        let idx: usize = <R as AsPrimitive<usize>>::as_(scaled).min(width - 1);
+        let values = [
+            lerp(t, lo.values[idx].as_(), hi.values[idx].as_()),
+            lerp(t, lo.values[idx + 1].as_(), hi.values[idx + 1].as_()),
+        ];
+        let du = invert_linear(scaled - idx.as_(), values);
+        let u = lerp(du, cdf(idx), cdf(idx + 1)) / cdf(width);
+
+        [u, v]
+    }
+}
+
+impl<W: Real + AsPrimitive<R>, R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static> ContinuousPdf2D<R> for Bilinear2D<W>
+    where usize: AsPrimitive<R>,
+          f64: AsPrimitive<R>,
+{
+    fn pdf_continuous(&self, [x, y]: [R; 2]) -> R {
+        let width = self.width();
+        let height = self.height();
+
+        let scaled_x = x * width.as_();
+        let i: usize = <R as AsPrimitive<usize>>::as_(scaled_x).min(width - 1);
+        let s = scaled_x - i.as_();
+
+        let scaled_y = y * height.as_();
+        let j: usize = <R as AsPrimitive<usize>>::as_(scaled_y).min(height - 1);
+        let t = scaled_y - j.as_();
+
+        let [lo, hi] = [&self.conditional[j].values, &self.conditional[j + 1].values];
+        let value = lerp(t, lerp(s, lo[i].as_(), lo[i + 1].as_()), lerp(s, hi[i].as_(), hi[i + 1].as_()));
+        value * (width * height).as_() / self.integral().as_()
+    }
+}
+
+impl<W: Real> Bilinear2D<W> {
+    // which two rows of vertices y falls between, and where between them
+    fn rows<R: Real + AsPrimitive<usize>>(&self, y: R) -> (&PiecewiseLinear1D<W>, &PiecewiseLinear1D<W>, R)
+        where W: AsPrimitive<R>,
+              usize: AsPrimitive<R>,
+    {
+        let height = self.conditional.len() - 1;
+        let scaled = y * height.as_();
+        let j: usize = <R as AsPrimitive<usize>>::as_(scaled).min(height - 1);
+        self.rows_at(j, scaled - j.as_())
+    }
+
+    // same as above, but for a known row of cells j, and t within it
+    fn rows_at<R: Real + 'static>(&self, j: usize, mut t: R) -> (&PiecewiseLinear1D<W>, &PiecewiseLinear1D<W>, R)
+        where W: AsPrimitive<R>,
+    {
+        let [lo, hi] = [&self.conditional[j], &self.conditional[j + 1]];
+
+        // a zero row can only be hit exactly on its edge, where the other row is just as good
+        let integrals: [R; 2] = [lo.cdf.last().unwrap().as_(), hi.cdf.last().unwrap().as_()];
+        if lerp(t, integrals[0], integrals[1]) == R::zero() {
+            t = if integrals[0] == R::zero() { R::one() } else { R::zero() };
+        }
+
+        (lo, hi, t)
+    }
+
+    // returns sampled cell, where in it, and normalized density there
+    // goes by the cell sampled rather than rounding the continuous sample,
+    // which could land on the edge of a neighbouring zero cell
+    fn sample_cell<R: Real + AsPrimitive<W> + AsPrimitive<usize> + 'static>(&self, [u, v]: [R; 2]) -> ([usize; 2], [R; 2], R)
+        where W: AsPrimitive<R>,
+              usize: AsPrimitive<R>,
+              f64: AsPrimitive<R>,
+    {
+        let (j, dv) = <PiecewiseLinear1D<W> as Discrete1D<R>>::sample_remap(&self.marginal, v);
+        let t_y = sample_linear(dv, [self.marginal.values[j].as_(), self.marginal.values[j + 1].as_()]);
+        let (lo, hi, t) = self.rows_at(j, t_y);
+        let cdf = |i: usize| lerp(t, lo.cdf[i].as_(), hi.cdf[i].as_());
+
+        let width = self.conditional[0].segments();
+        let total = cdf(width);
+        let point = u * total;
+        // same as PiecewiseLinear1D, past the end should still be the last non-zero segment
+        let offset = if point >= total {
+            partition_point(width + 1, |i| cdf(i) < total).saturating_sub(1)
+        } else {
+            partition_point(width + 1, |i| cdf(i) <= point) - 1
+        }.min(width - 1);
+
+        let du = (point - cdf(offset)) / (cdf(offset + 1) - cdf(offset));
+        let values = [
+            lerp(t, lo.values[offset].as_(), hi.values[offset].as_()),
+            lerp(t, lo.values[offset + 1].as_(), hi.values[offset + 1].as_()),
+        ];
+        let t_x = sample_linear(du, values);
+
+        let height = self.conditional.len() - 1;
+        let integral: R = self.marginal.cdf.last().unwrap().as_();
+        let pdf = lerp(t_x, values[0], values[1]) * (width * height).as_() / integral;
+
+        ([offset, j], [t_x, t_y], pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distribution::{
+        Discrete1D,
+        Discrete1DPdf,
+        Discrete2D,
+        Discrete2DPdf,
+        Continuous1D,
+        Continuous2D,
+        ContinuousPdf1D,
+        ContinuousPdf2D,
+        continuous_distribution_1d_tests,
+    };
+    use crate::data2d::Data2D;
+    use super::{
+        PiecewiseLinear1D,
+        Bilinear2D,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use statrs::distribution::{ChiSquared, ContinuousCDF};
+
+    continuous_distribution_1d_tests!(crate::linear::PiecewiseLinear1D);
+
+    #[test]
+    fn pdf_matches_samples() {
+        let dist = <PiecewiseLinear1D<f64> as Discrete1D<f64>>::build(&[0.0, 1.0, 3.0, 2.0, 0.5]);
+
+        // histogram bins subdivide segments, so density is linear within each
+        // and the trapezoid rule gives exact expected counts
+        let bin_count = 64;
+        let sample_count = 1_000_000;
+        let mut hist = vec![0usize; bin_count];
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..sample_count {
+            let x = dist.sample_continuous(rng.r#gen::<f64>());
+            hist[(x * bin_count as f64) as usize] += 1;
+        }
+
+        let mut chsq = 0.0;
+        for (i, observed) in hist.into_iter().enumerate() {
+            let lo = i as f64 / bin_count as f64;
+            let hi = (i + 1) as f64 / bin_count as f64;
+            let expected = (dist.pdf_continuous(lo) + dist.pdf_continuous(hi - 1e-12)) / 2.0 / bin_count as f64 * sample_count as f64;
+            let diff = observed as f64 - expected;
+            chsq += diff * diff / expected;
+        }
+        let pval = 1.0 - ChiSquared::new((bin_count - 1) as f64).unwrap().cdf(chsq);
+        assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+    }
+
+    #[test]
+    fn pdf_matches_interpolant() {
+        let weights = [1.0, 4.0, 2.0];
+        let dist = <PiecewiseLinear1D<f64> as Discrete1D<f64>>::build(&weights);
+        let integral = <PiecewiseLinear1D<f64> as Discrete1D<f64>>::integral(&dist) / 2.0;
+        assert!((dist.pdf_continuous(0.0) - 1.0 / integral).abs() < 1e-12);
+        assert!((dist.pdf_continuous(0.25) - 2.5 / integral).abs() < 1e-12);
+        assert!((dist.pdf_continuous(0.5) - 4.0 / integral).abs() < 1e-12);
+        assert!((dist.pdf_continuous(0.75) - 3.0 / integral).abs() < 1e-12);
+    }
+
+    #[test]
+    fn size_is_segments() {
+        let dist = <PiecewiseLinear1D<f64> as Discrete1D<f64>>::build(&[0.0, 1.0, 3.0, 2.0, 0.5]);
+        assert_eq!(Discrete1D::<f64>::size(&dist), 4);
+        assert_eq!(dist.segments(), 4);
+        assert_eq!(Discrete1D::<f64>::sample(&dist, 1.0), 3);
+    }
+
+    // segments of equal mass, split at exactly a third
+    #[test]
+    fn sample_bits_exact() {
+        let dist = <PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&[1.0, 1.0, 1.0, 1.0]);
+        crate::distribution::test_sample_bits_exact_1d::<f32, _>(&dist, &[1, 1, 1]);
+    }
+
+    #[test]
+    fn zero_integral() {
+        crate::distribution::test_zero_integral_1d(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::build, 5);
+        crate::distribution::test_zero_integral_2d::<Bilinear2D<f32>>(5, 3);
+        crate::distribution::test_zero_integral_continuous_2d::<Bilinear2D<f32>>(5, 3);
+    }
+
+    #[test]
+    fn edge_cases() {
+        use crate::distribution::{test_edge_cases_1d, test_edge_cases_2d, test_edge_cases_continuous_2d};
+        test_edge_cases_1d(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::build);
+        test_edge_cases_1d(<PiecewiseLinear1D<f64> as Discrete1D<f64>>::build);
+        test_edge_cases_2d::<f32, Bilinear2D<f32>>();
+        test_edge_cases_2d::<f64, Bilinear2D<f64>>();
+        test_edge_cases_continuous_2d::<f32, Bilinear2D<f32>>();
+        test_edge_cases_continuous_2d::<f64, Bilinear2D<f64>>();
+    }
+
+    #[test]
+    fn try_build() {
+        use crate::distribution::BuildError;
+
+        // weights are vertices here, so the shared 1d success case doesn't apply
+        assert_eq!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[0.0; 3]).err(), Some(BuildError::ZeroIntegral));
+        assert_eq!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[1.0, -1.0]).err(), Some(BuildError::InvalidWeight(1)));
+        assert_eq!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[1.0, f32::NAN]).err(), Some(BuildError::InvalidWeight(1)));
+        assert_eq!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[f32::MAX, f32::MAX]).err(), Some(BuildError::Overflow));
+        assert!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[0.0, 1.0, 0.0]).is_ok());
+
+        crate::distribution::test_try_build_2d::<Bilinear2D<f32>>();
+
+        // needs at least two vertices in each dimension
+        assert_eq!(<PiecewiseLinear1D<f32> as Discrete1D<f32>>::try_build(&[1.0]).err(), Some(BuildError::Empty));
+        assert_eq!(<Bilinear2D<f32> as Discrete2D<f32>>::try_build(&Data2D::new_same(1, 3, 1.0)).err(), Some(BuildError::Empty));
+        assert_eq!(<Bilinear2D<f32> as Discrete2D<f32>>::try_build(&Data2D::new_same(3, 1, 1.0)).err(), Some(BuildError::Empty));
+    }
+
+    #[test]
+    fn remap() {
+        let dist = <PiecewiseLinear1D<f64> as Discrete1D<f64>>::build(&[0.0, 1.0, 3.0, 2.0, 0.5]);
+
+        // remapped u should split each segment's area evenly
+        let subdivisions = 4;
+        let sample_count = 100_000;
+        let mut hist = vec![0usize; Discrete1D::<f64>::size(&dist) * subdivisions];
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..sample_count {
+            let (idx, u) = Discrete1D::<f64>::sample_remap(&dist, rng.r#gen::<f64>());
+            hist[idx * subdivisions + (u * subdivisions as f64) as usize] += 1;
+        }
+
+        let mut chsq = 0.0;
+        for (i, observed) in hist.iter().enumerate() {
+            let expected = Discrete1DPdf::<f64>::pdf(&dist, i / subdivisions) / Discrete1D::<f64>::integral(&dist) / subdivisions as f64 * sample_count as f64;
+            let diff = *observed as f64 - expected;
+            chsq += diff * diff / expected;
+        }
+        let pval = 1.0 - ChiSquared::new((hist.len() - 1) as f64).unwrap().cdf(chsq);
+        assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+    }
+
+    #[test]
+    fn sample_with_pdf() {
+        crate::distribution::test_sample_with_pdf_1d::<PiecewiseLinear1D<f64>>(&[0.0, 1.0, 3.0, 2.0, 0.5], 1000);
+    }
+
+    fn bilinear_weights() -> Data2D<f64> {
+        let mut weights = Data2D::new_same(4, 3, 0.0);
+        for (j, row) in weights.iter_mut().enumerate() {
+            for (i, weight) in row.iter_mut().enumerate() {
+                *weight = ((i * 7 + j * 3) % 5) as f64;
+            }
+        }
+        weights
+    }
+
+    #[test]
+    fn bilinear_pdf_matches_samples() {
+        let dist = <Bilinear2D<f64> as Discrete2D<f64>>::build(&bilinear_weights());
+
+        // integral of a bilinear function over a rectangle is the average of its corners,
+        // so histogram bins that subdivide cells give exact expected counts
+        let subdivisions = 4;
+        let width = Discrete2D::<f64>::width(&dist) * subdivisions;
+        let height = Discrete2D::<f64>::height(&dist) * subdivisions;
+        let sample_count = 1_000_000;
+        let mut hist = Data2D::new_same(width, height, 0usize);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..sample_count {
+            let [x, y] = dist.sample_continuous([rng.r#gen::<f64>(), rng.r#gen::<f64>()]);
+            hist[[(x * width as f64) as usize, (y * height as f64) as usize]] += 1;
+        }
+
+        let mut chsq = 0.0;
+        let mut dof = 0;
+        for j in 0..height {
+            for i in 0..width {
+                let corner = |di: usize, dj: usize| {
+                    let x = ((i + di) as f64 / width as f64).min(1.0 - 1e-12);
+                    let y = ((j + dj) as f64 / height as f64).min(1.0 - 1e-12);
+                    dist.pdf_continuous([x, y])
+                };
+                let average = (corner(0, 0) + corner(1, 0) + corner(0, 1) + corner(1, 1)) / 4.0;
+                let expected = average / (width * height) as f64 * sample_count as f64;
+                if expected == 0.0 {
+                    assert_eq!(hist[[i, j]], 0);
+                    continue;
+                }
+                let diff = hist[[i, j]] as f64 - expected;
+                chsq += diff * diff / expected;
+                dof += 1;
+            }
+        }
+        let pval = 1.0 - ChiSquared::new((dof - 1) as f64).unwrap().cdf(chsq);
+        assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+    }
+
+    #[test]
+    fn bilinear_remap() {
+        let dist = <Bilinear2D<f64> as Discrete2D<f64>>::build(&bilinear_weights());
+
+        // remapped uv should split each cell's area evenly
+        let subdivisions = 4;
+        let width = Discrete2D::<f64>::width(&dist);
+        let height = Discrete2D::<f64>::height(&dist);
+        let sample_count = 1_000_000;
+        let mut hist = Data2D::new_same(width * subdivisions, height * subdivisions, 0usize);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..sample_count {
+            let ([i, j], [u, v]) = dist.sample_remap([rng.r#gen::<f64>(), rng.r#gen::<f64>()]);
+            hist[[i * subdivisions + (u * subdivisions as f64) as usize, j * subdivisions + (v * subdivisions as f64) as usize]] += 1;
+        }
+
+        let integral = Discrete2D::<f64>::integral(&dist);
+        let mut chsq = 0.0;
+        let mut dof = 0;
+        for j in 0..height * subdivisions {
+            for i in 0..width * subdivisions {
+                let area = Discrete2DPdf::<f64>::pdf(&dist, [i / subdivisions, j / subdivisions]);
+                let expected = area / integral / (subdivisions * subdivisions) as f64 * sample_count as f64;
+                if expected == 0.0 {
+                    assert_eq!(hist[[i, j]], 0);
+                    continue;
+                }
+                let diff = hist[[i, j]] as f64 - expected;
+                chsq += diff * diff / expected;
+                dof += 1;
+            }
+        }
+        let pval = 1.0 - ChiSquared::new((dof - 1) as f64).unwrap().cdf(chsq);
+        assert!(pval >= 0.01, "failed chi-squared statistical test, p = {}", pval);
+    }
+
+    #[test]
+    fn bilinear_continuous_with_pdf() {
+        let mut weights = bilinear_weights();
+        for weight in weights.iter_mut().flatten() {
+            *weight += 1.0;
+        }
+        crate::distribution::test_continuous_with_pdf_2d::<Bilinear2D<f64>>(&weights, 300);
+        crate::distribution::test_pdf_continuous_2d::<Bilinear2D<f64>>(&weights, 300);
+    }
+
+    #[test]
+    fn bilinear_sample_with_pdf() {
+        let dist = <Bilinear2D<f64> as Discrete2D<f64>>::build(&bilinear_weights());
+        let integral = Discrete2D::<f64>::integral(&dist);
+        let sample_count = 100;
+        for j in 0..sample_count {
+            for i in 0..sample_count {
+                let uv = [i as f64 / sample_count as f64, j as f64 / sample_count as f64];
+                let (idx, pdf) = Discrete2DPdf::<f64>::sample_with_pdf(&dist, uv);
+                assert_eq!(idx, Discrete2D::<f64>::sample(&dist, uv));
+                assert!((pdf - Discrete2DPdf::<f64>::pdf(&dist, idx) / integral).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_inverse() {
+        let dist = <Bilinear2D<f64> as Discrete2D<f64>>::build(&bilinear_weights());
+        let sample_count = 100;
+        for j in 0..sample_count {
+            for i in 0..sample_count {
+                let uv = [i as f64 / sample_count as f64, j as f64 / sample_count as f64];
+                let inv = dist.invert_continuous(dist.sample_continuous(uv));
+                assert!((inv[0] - uv[0]).abs() < 1e-6 && (inv[1] - uv[1]).abs() < 1e-6, "{:?} original not equal to {:?} inverse", uv, inv);
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_pdf_matches_interpolant() {
+        let weights = bilinear_weights();
+        let dist = <Bilinear2D<f64> as Discrete2D<f64>>::build(&weights);
+        let integral = Discrete2D::<f64>::integral(&dist) / 6.0;
+        assert!((dist.pdf_continuous([1.0 / 3.0, 0.5]) - weights[[1, 1]] / integral).abs() < 1e-12);
+        assert!((dist.pdf_continuous([0.5, 0.25]) - (weights[[1, 0]] + weights[[2, 0]] + weights[[1, 1]] + weights[[2, 1]]) / 4.0 / integral).abs() < 1e-12);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived() {
+        use rkyv::rancor::Error;
+
+        let dist = <PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 0.0, 2.0, 4.0, 8.0, 3.0]);
+        let bytes = rkyv::to_bytes::<Error>(&dist).unwrap();
+        crate::distribution::test_archived_1d(&dist, crate::distribution::access_checked::<super::ArchivedPiecewiseLinear1D<f32>>(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn archived_invalid() {
+        use crate::distribution::test_archived_invalid;
+
+        let dist = <PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 0.0, 2.0, 4.0, 8.0, 3.0]);
+        test_archived_invalid(&dist, &PiecewiseLinear1D { values: dist.values[1..].into(), cdf: dist.cdf.clone() });
+        let mut cdf = dist.cdf.clone();
+        cdf[4] = 0.0;
+        test_archived_invalid(&dist, &PiecewiseLinear1D { values: dist.values.clone(), cdf });
+
+        let build = || <Bilinear2D<f32> as Discrete2D<f32>>::build(&Data2D::new_same(4, 3, 1.0f32));
+        let mut invalid = build();
+        let mut conditional = invalid.conditional.into_vec();
+        conditional.pop();
+        invalid.conditional = conditional.into();
+        test_archived_invalid(&build(), &invalid);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::distribution::{test_serde_1d, test_serde_2d, test_serde_invalid};
+
+        let dist = <PiecewiseLinear1D<f32> as Discrete1D<f32>>::build(&[1.0, 0.0, 0.0, 2.0, 4.0, 8.0, 3.0]);
+        test_serde_1d(&dist);
+        test_serde_invalid(&dist, |json| { json["values"].as_array_mut().unwrap().pop(); });
+        test_serde_invalid(&dist, |json| json["cdf"][4] = 0.0.into());
+
+        let mut weights = Data2D::new_same(4, 3, 1.0f32);
+        weights[[0, 0]] = 0.0;
+        weights[[2, 1]] = 5.0;
+        let dist = <Bilinear2D<f32> as Discrete2D<f32>>::build(&weights);
+        test_serde_2d(&dist);
+        test_serde_invalid(&dist, |json| { json["conditional"].as_array_mut().unwrap().pop(); });
+    }
+}
@@ -1,8 +1,17 @@
 use num_traits::{
     Zero,
     One,
+    Num,
     NumOps,
     real::Real,
+    AsPrimitive,
+};
+use crate::distribution::BuildError;
+use core::simd::{
+    Simd,
+    Select,
+    cmp::{SimdOrd, SimdPartialEq, SimdPartialOrd},
+    num::{SimdFloat, SimdUint},
 };
 
 // a little bit of a workaround to allow SIMD summation for all types
@@ -42,6 +51,216 @@ impl<F: core::ops::Add + Zero> Sum for F {
     }
 }
 
+// checks for weights, where integers can overflow, and floats can be NaN or infinite
+pub trait CheckedWeight: Sized {
+    fn valid(self) -> bool;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+impl<W: Num + PartialOrd + Copy> CheckedWeight for W {
+    default fn valid(self) -> bool {
+        self >= W::zero()
+    }
+
+    default fn checked_add(self, other: W) -> Option<W> {
+        Some(self + other)
+    }
+
+    default fn checked_mul(self, other: W) -> Option<W> {
+        Some(self * other)
+    }
+}
+
+macro_rules! checked_weight_int {
+    ($($t:ty),*) => {
+        $(
+            impl CheckedWeight for $t {
+                fn valid(self) -> bool {
+                    #[allow(unused_comparisons)]
+                    let valid = self >= 0;
+                    valid
+                }
+
+                fn checked_add(self, other: $t) -> Option<$t> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: $t) -> Option<$t> {
+                    <$t>::checked_mul(self, other)
+                }
+            }
+        )*
+    }
+}
+
+checked_weight_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! checked_weight_float {
+    ($($t:ty),*) => {
+        $(
+            impl CheckedWeight for $t {
+                fn valid(self) -> bool {
+                    self >= 0.0 && self.is_finite()
+                }
+
+                fn checked_add(self, other: $t) -> Option<$t> {
+                    Some(self + other).filter(|sum| sum.is_finite())
+                }
+
+                fn checked_mul(self, other: $t) -> Option<$t> {
+                    Some(self * other).filter(|product| product.is_finite())
+                }
+            }
+        )*
+    }
+}
+
+checked_weight_float!(f32, f64);
+
+// weights as 64 bit fixed point fractions of a total, for sample_bits to compare against exactly
+// returns the smallest bits for which bits / 2^64 >= self / total, 2^64 if self is total
+// self has to be between zero and total, and total non-zero
+pub trait FixedPoint: Sized {
+    fn fixed_threshold(self, total: Self) -> u128;
+}
+
+impl<W: Num + PartialOrd + Copy> FixedPoint for W {
+    // long division, a bit at a time, which is exact even for floats,
+    // as doubling is, and so is subtracting total from anything between it and twice it
+    default fn fixed_threshold(self, total: W) -> u128 {
+        let mut quotient = 0u128;
+        let mut remainder = self;
+        for _ in 0..=64 {
+            quotient <<= 1;
+            if remainder >= total {
+                quotient |= 1;
+                remainder = remainder - total;
+            }
+            remainder = remainder + remainder;
+        }
+        quotient + !remainder.is_zero() as u128
+    }
+}
+
+macro_rules! fixed_point_int {
+    ($($t:ty),*) => {
+        $(
+            impl FixedPoint for $t {
+                fn fixed_threshold(self, total: $t) -> u128 {
+                    ((self as u128) << 64).div_ceil(total as u128)
+                }
+            }
+        )*
+    }
+}
+
+fixed_point_int!(u8, u16, u32, u64, usize);
+
+// mantissa with its top bit at 52, and exponent, so that x is mantissa * 2^exponent
+fn decode_f64(x: f64) -> (u128, i32) {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32;
+    let mantissa = bits & ((1 << 52) - 1);
+    if exponent == 0 {
+        let shift = mantissa.leading_zeros() as i32 - 11;
+        ((mantissa << shift) as u128, -1074 - shift)
+    } else {
+        ((mantissa | (1 << 52)) as u128, exponent - 1075)
+    }
+}
+
+macro_rules! fixed_point_float {
+    ($($t:ty),*) => {
+        $(
+            impl FixedPoint for $t {
+                // both as integers, the ratio is self_mantissa * 2^shift / total_mantissa,
+                // where shift is at most 64 as self is at most total, so it all fits in 128 bits
+                fn fixed_threshold(self, total: $t) -> u128 {
+                    if self <= 0.0 {
+                        return 0;
+                    }
+                    let (numerator, self_exponent) = decode_f64(self as f64);
+                    let (denominator, total_exponent) = decode_f64(total as f64);
+                    let shift = self_exponent - total_exponent + 64;
+                    if shift >= 0 {
+                        (numerator << shift).div_ceil(denominator)
+                    } else if shift > -64 {
+                        numerator.div_ceil(denominator << -shift)
+                    } else {
+                        // far below 2^-64 of the total, but still non-zero
+                        1
+                    }
+                }
+            }
+        )*
+    }
+}
+
+fixed_point_float!(f32, f64);
+
+// shared checks of try_build, returns sum of weights
+pub fn validate_weights<W: Num + PartialOrd + Copy>(weights: impl IntoIterator<Item = W>) -> Result<W, BuildError> {
+    let mut empty = true;
+    let mut sum = W::zero();
+    for (i, weight) in weights.into_iter().enumerate() {
+        empty = false;
+        if !weight.valid() {
+            return Err(BuildError::InvalidWeight(i));
+        }
+        sum = CheckedWeight::checked_add(sum, weight).ok_or(BuildError::Overflow)?;
+    }
+
+    if empty {
+        Err(BuildError::Empty)
+    } else if sum == W::zero() {
+        Err(BuildError::ZeroIntegral)
+    } else {
+        Ok(sum)
+    }
+}
+
+// shared checks of deserialized or archived tables, a cdf has to start non-negative and never decrease,
+// which also rules out NaN
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+pub fn valid_cdf<W: Zero + PartialOrd, E: Copy + Into<W>>(cdf: &[E]) -> bool {
+    cdf.first().is_some_and(|first| (*first).into() >= W::zero()) && cdf.windows(2).all(|pair| pair[0].into() <= pair[1].into())
+}
+
+// number of entries of a 1D table, so deserialized or archived Adapter2D can check its rows agree
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+pub trait TableSize {
+    fn table_size(&self) -> usize;
+}
+
+// in place prefix sum across threads, in blocks that are each summed on their own and then offset by the blocks before them
+// the first block is a plain running sum, and so is all of it for integers,
+// but floats past the first block round differently than a plain running sum would
+#[cfg(feature = "rayon")]
+pub const PREFIX_BLOCK: usize = 1 << 14;
+
+#[cfg(feature = "rayon")]
+pub fn par_prefix_sum<W: Num + Copy + Send + Sync>(values: &mut [W]) {
+    use rayon::prelude::*;
+
+    let totals = values.par_chunks_mut(PREFIX_BLOCK).map(|block| {
+        for i in 1..block.len() {
+            block[i] = block[i - 1] + block[i];
+        }
+        block.last().copied().unwrap_or(W::zero())
+    }).collect::<Vec<_>>();
+    let mut offsets = Vec::with_capacity(totals.len());
+    let mut offset = W::zero();
+    for total in totals {
+        offsets.push(offset);
+        offset = offset + total;
+    }
+    // skipped for the first block, where offset is zero, so it stays the same even for -0.0
+    values.par_chunks_mut(PREFIX_BLOCK).zip(offsets).skip(1).for_each(|(block, offset)| {
+        block.iter_mut().for_each(|value| *value = offset + *value);
+    });
+}
+
 // from pbrt
 pub fn radical_inverse<R: Real>(base_index: usize, mut a: u64) -> R {
     let primes = [ 2, 3, 5, 7, 11 ];
@@ -71,7 +290,195 @@ pub fn u64_to_color(a: u64) -> [f32; 3] {
     ]
 }
 
+// splits u * n into integer and fractional parts
+// done on u's integer mantissa, so the index is exact for any n, and the fractional part
+// is only rounded once at the end, whereas in float the product loses low bits and may even round up to the next integer
+// clamped so that u at or rounding up to 1.0 still gives an index in range, and a fractional part below 1.0
+pub fn scale_unit<R: Real + AsPrimitive<f64>>(u: R, n: usize) -> (usize, R)
+    where f64: AsPrimitive<R>,
+{
+    let max_fraction = R::one() - R::epsilon() / (R::one() + R::one());
+    let u: f64 = u.as_();
+    if u >= 1.0 {
+        return (n - 1, max_fraction);
+    }
+    if u.is_nan() || u <= 0.0 {
+        return (0, R::zero());
+    }
+
+    // u is mantissa / 2^shift, with 53 bits of mantissa, so the product fits easily in 128 bits
+    let bits = u.to_bits();
+    let exponent = (bits >> 52) as u32;
+    let (mantissa, shift) = if exponent == 0 {
+        (bits, 1074)
+    } else {
+        (bits & ((1 << 52) - 1) | (1 << 52), 1075 - exponent)
+    };
+    let scaled = mantissa as u128 * n as u128;
+    let (index, remainder) = if shift < 128 {
+        ((scaled >> shift) as usize, scaled & ((1 << shift) - 1))
+    } else {
+        (0, scaled)
+    };
+
+    // split in two, as 2^-shift can be below the smallest normal f64
+    let fraction = remainder as f64 * exp2_neg(shift / 2) * exp2_neg(shift - shift / 2);
+    (index.min(n - 1), AsPrimitive::<R>::as_(fraction).min(max_fraction))
+}
+
+// same as scale_unit, but in float, for points from sample_continuous, which were (index + fraction) / n
+// rounded, as then x * n rounds back the same way, and lands in the same place, where done exactly
+// it can land just past the end of a region
+pub fn scale_unit_inverse<R: Real + AsPrimitive<f64>>(x: R, n: usize) -> (usize, R)
+    where f64: AsPrimitive<R>,
+{
+    let scaled = x.as_() * n as f64;
+    let index = (scaled as usize).min(n - 1);
+    let fraction: R = (scaled - index as f64).as_();
+    (index, fraction.min(R::one() - R::epsilon() / (R::one() + R::one())))
+}
+
+// 2^-e, for e up to 1022
+fn exp2_neg(e: u32) -> f64 {
+    f64::from_bits(((1023 - e) as u64) << 52)
+}
+
+// batches are sampled LANES at a time with core::simd where a table supports it,
+// and otherwise, as well as any remainder, one at a time
+pub const LANES: usize = 8;
+pub type Lanes<T> = Simd<T, LANES>;
+
+pub fn batch<T: Copy, U>(input: &[T], out: &mut [U], single: impl Fn(T) -> U) {
+    assert_eq!(input.len(), out.len(), "batch output length doesn't match input");
+    for (input, out) in input.iter().zip(out) {
+        *out = single(*input);
+    }
+}
+
+pub fn batch_lanes<T: Copy, U>(input: &[T], out: &mut [U], lanes: impl Fn([T; LANES]) -> [U; LANES], single: impl Fn(T) -> U) {
+    assert_eq!(input.len(), out.len(), "batch output length doesn't match input");
+    let (input_lanes, input_rest) = input.as_chunks::<LANES>();
+    let (out_lanes, out_rest) = out.as_chunks_mut::<LANES>();
+    for (input, out) in input_lanes.iter().zip(out_lanes) {
+        *out = lanes(*input);
+    }
+    batch(input_rest, out_rest, single);
+}
+
+// same as scale_unit, for f32, where the 24 bit mantissa times n fits in 64 bits for n below 2^40
+pub fn scale_unit_lanes(u: Lanes<f32>, n: usize) -> (Lanes<usize>, Lanes<f32>) {
+    assert!(n < 1 << 40, "scale_unit_lanes only supports n below 2^40");
+    let max_fraction = Lanes::splat(1.0 - f32::EPSILON / 2.0);
+    let bits = u.simd_max(Lanes::splat(0.0)).to_bits().cast::<u64>();
+
+    let exponent = bits >> 23;
+    let normal = exponent.simd_ne(Lanes::splat(0));
+    let mantissa = normal.select(bits & Lanes::splat((1 << 23) - 1) | Lanes::splat(1 << 23), bits);
+    let shift = normal.select(Lanes::splat(150) - exponent.simd_min(Lanes::splat(150)), Lanes::splat(149));
+
+    // products are below 2^64, so a shift of 63 already gives zero
+    let scaled = mantissa * Lanes::splat(n as u64);
+    let clamped_shift = shift.simd_min(Lanes::splat(63));
+    let index = scaled >> clamped_shift;
+    let remainder = scaled - (index << clamped_shift);
+    let fraction = remainder.cast::<f64>() * Lanes::from_bits((Lanes::splat(1023) - shift) << Lanes::splat(52));
+
+    let past_end = u.simd_ge(Lanes::splat(1.0));
+    let index = past_end.cast::<i64>().select(Lanes::splat(n as u64 - 1), index).cast::<usize>();
+    let fraction = past_end.select(max_fraction, fraction.cast::<f32>().simd_min(max_fraction));
+    (index, fraction)
+}
+
+// same as clamp_unit, for f32
+pub fn clamp_unit_lanes(u: Lanes<f32>) -> Lanes<f32> {
+    u.simd_max(Lanes::splat(0.0)).simd_min(Lanes::splat(1.0 - f32::EPSILON / 2.0))
+}
+
+// scales 64 bit fixed point [0-1) to [0-n)
+// returns integer and fractional parts, both exact
+pub fn scale_bits(bits: u64, n: usize) -> (usize, u64) {
+    let scaled = bits as u128 * n as u128;
+    ((scaled >> 64) as usize, scaled as u64)
+}
+
+// remapped rand may end up just outside [0-1) due to rounding
+pub fn clamp_unit<R: Real>(u: R) -> R {
+    u.max(R::zero()).min(R::one() - R::epsilon() / (R::one() + R::one()))
+}
+
 pub fn lerp<T: One + NumOps + Copy>(by: T, from: T, to: T) -> T {
     (T::one() - by) * from + by * to 
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // u * n done exactly, for f32 u that are a multiple of 2^-24, as u * 2^24 * n / 2^24
+    fn exact_f32(u: f32, n: usize) -> (usize, f64) {
+        let fixed = (u as f64 * (1u64 << 24) as f64) as u128 * n as u128;
+        ((fixed >> 24) as usize, (fixed & ((1 << 24) - 1)) as f64 / (1u64 << 24) as f64)
+    }
+
+    #[test]
+    fn scale_unit_exact() {
+        let largest = 1.0 - f32::EPSILON / 2.0;
+        for n in [1, 3, 1000, (1 << 29) + 1, (1 << 31) - 1, u32::MAX as usize - 1] {
+            for u in [0.0, f32::EPSILON / 2.0, 0.25, 0.3, 0.5, 0.7, 0.999, largest] {
+                let (index, fraction) = scale_unit(u, n);
+                let (exact_index, exact_fraction) = exact_f32(u, n);
+                assert_eq!(index, exact_index, "{} * {}", u, n);
+                assert_eq!(fraction, (exact_fraction as f32).min(largest), "{} * {}", u, n);
+                assert_eq!(scale_unit(u as f64, n).0, exact_index, "{} * {}", u, n);
+
+                let (lanes_index, lanes_fraction) = scale_unit_lanes(Lanes::splat(u), n);
+                assert_eq!(lanes_index, Lanes::splat(index), "{} * {}", u, n);
+                assert_eq!(lanes_fraction, Lanes::splat(fraction), "{} * {}", u, n);
+            }
+        }
+    }
+
+    // far below 2^-24, where the mantissa has to be shifted further than the product has bits
+    #[test]
+    fn scale_unit_tiny() {
+        for u in [1e-30, f32::MIN_POSITIVE, f32::from_bits(1)] {
+            let fraction = (u as f64 * 1000.0) as f32;
+            assert_eq!(scale_unit(u, 1000), (0, fraction));
+            assert_eq!(scale_unit_lanes(Lanes::splat(u), 1000), (Lanes::splat(0), Lanes::splat(fraction)));
+        }
+        assert_eq!(scale_unit(f64::from_bits(1), 1000), (0, f64::from_bits(1000)));
+    }
+
+    #[test]
+    fn scale_unit_out_of_range() {
+        for u in [1.0, 1.5, f32::INFINITY] {
+            assert_eq!(scale_unit(u, 7), (6, 1.0 - f32::EPSILON / 2.0));
+            assert_eq!(scale_unit_lanes(Lanes::splat(u), 7), (Lanes::splat(6), Lanes::splat(1.0 - f32::EPSILON / 2.0)));
+        }
+        for u in [-0.5, -0.0, f32::NAN] {
+            assert_eq!(scale_unit(u, 7), (0, 0.0));
+            assert_eq!(scale_unit_lanes(Lanes::splat(u), 7), (Lanes::splat(0), Lanes::splat(0.0)));
+        }
+    }
+
+    // floats scaled by a power of two, and the long division other types fall back to, should agree with integers
+    #[test]
+    fn fixed_threshold_exact() {
+        let pairs = [(0u64, 1u64), (1, 3), (2, 3), (3, 3), (1, 1 << 52), (12345, 67890), ((1 << 53) - 1, 1 << 53), (1, (1 << 53) - 1)];
+        for (a, b) in pairs {
+            let expected = ((a as u128) << 64).div_ceil(b as u128);
+            assert_eq!(a.fixed_threshold(b), expected, "{} / {}", a, b);
+            assert_eq!((a as i64).fixed_threshold(b as i64), expected, "{} / {}", a, b);
+            for scale in [1.0, 0.125, 2.0f64.powi(-1000), 2.0f64.powi(1000), f64::from_bits(1)] {
+                if b as f64 * scale < f64::MAX {
+                    assert_eq!((a as f64 * scale).fixed_threshold(b as f64 * scale), expected, "{} / {} at {}", a, b, scale);
+                }
+            }
+            if b < 1 << 24 {
+                assert_eq!((a as f32 * 0.5).fixed_threshold(b as f32 * 0.5), expected, "{} / {}", a, b);
+            }
+        }
+        assert_eq!(f32::from_bits(1).fixed_threshold(1.0), 1);
+    }
+}